@@ -0,0 +1,295 @@
+//! Serve the static assets with the same caching behaviour as the production CDN.
+//!
+//! Assets under a run-versioned prefix, `runs/{run_id}/...`, never change, so they can be cached
+//! forever. Index files are rewritten as a run progresses, so they only get a short TTL. Everything
+//! gets a strong `ETag` so that clients can cheaply revalidate.
+
+use axum::http::{HeaderValue, StatusCode, header};
+use tower::ServiceExt as _;
+
+/// The prefix for all the assets of a versioned world run.
+const RUNS_PREFIX: &str = "runs";
+
+/// Files that get rewritten in place, even under a versioned prefix.
+const INDEX_FILES: &[&str] = &[
+    "index.html",
+    tasks::atlas::longest_lines::lookup::INDEX_FILENAME,
+    tasks::atlas::longest_lines::overview::LONGEST_LINES_GRIDED_FILENAME,
+];
+
+/// `Cache-Control` for assets that never change.
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+/// `Cache-Control` for index files.
+const SHORT_TTL: &str = "public, max-age=60";
+
+/// `Cache-Control` for everything else.
+const DEFAULT_TTL: &str = "public, max-age=3600";
+
+/// Serve a directory of static assets, preferring `.br` and `.gz` siblings when the client
+/// accepts them.
+pub fn serve_dir(root: &std::path::Path) -> tower_http::services::ServeDir {
+    tower_http::services::ServeDir::new(root)
+        .precompressed_br()
+        .precompressed_gzip()
+}
+
+/// Handle `GET` for any static asset.
+pub async fn handler(
+    axum::extract::State(state): axum::extract::State<std::sync::Arc<crate::State>>,
+    mut request: axum::extract::Request,
+) -> axum::response::Response {
+    let decoded = decode_path(request.uri().path());
+
+    // Ranges of a compressed file aren't ranges of the original file, and the website makes range
+    // requests to COGs and `PMTiles`. So only ever serve the original file for ranges.
+    if request.headers().contains_key(header::RANGE) {
+        request.headers_mut().remove(header::ACCEPT_ENCODING);
+    }
+
+    // Revalidations are answered before `ServeDir` opens the file, so a fresh cache doesn't cost
+    // reading the whole of it.
+    if let Some(path) = &decoded
+        && let Some(if_none_match) = request.headers().get(header::IF_NONE_MATCH)
+    {
+        for encoding in accepted_encodings(request.headers()) {
+            if let Some(etag) = etag(&state.paths.root, path, encoding).await
+                && matches_etag(if_none_match, &etag)
+            {
+                return not_modified(path, etag);
+            }
+        }
+    }
+
+    let Ok(served) = state.assets.clone().oneshot(request).await;
+    let mut response = served.map(axum::body::Body::new);
+
+    response
+        .headers_mut()
+        .insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+    let Some(path) = decoded else {
+        return response;
+    };
+    if !response.status().is_success() {
+        return response;
+    }
+
+    response.headers_mut().insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(cache_control(&path)),
+    );
+
+    let encoding = response
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+    if let Some(etag) = etag(&state.paths.root, &path, encoding.as_deref()).await {
+        response.headers_mut().insert(header::ETAG, etag);
+    }
+    response
+}
+
+/// Percent-decode a request's path, in the same way as `ServeDir` does, so that `ETag`s are for
+/// the same files that it serves.
+fn decode_path(path: &str) -> Option<String> {
+    percent_encoding::percent_decode_str(path)
+        .decode_utf8()
+        .ok()
+        .map(std::borrow::Cow::into_owned)
+}
+
+/// The response for a client whose cached copy of an asset is still current.
+fn not_modified(path: &str, etag: HeaderValue) -> axum::response::Response {
+    let mut response = axum::response::Response::new(axum::body::Body::empty());
+    *response.status_mut() = StatusCode::NOT_MODIFIED;
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(cache_control(path)),
+    );
+    headers.insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+    headers.insert(header::ETAG, etag);
+    response
+}
+
+/// The encodings that the client would accept an asset in, as the `Content-Encoding` that
+/// `ServeDir` would serve it with. The original file is always acceptable, because it's served
+/// when there isn't a precompressed one.
+fn accepted_encodings(headers: &axum::http::HeaderMap) -> Vec<Option<&'static str>> {
+    let mut encodings = vec![None];
+    let accepted = headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|candidate| {
+            let mut parts = candidate.split(';').map(str::trim);
+            let name = parts.next()?;
+            let is_refused = parts.any(|parameter| {
+                parameter
+                    .strip_prefix("q=")
+                    .and_then(|quality| quality.parse::<f32>().ok())
+                    .is_some_and(|quality| quality <= 0.0)
+            });
+            (!is_refused).then_some(name)
+        });
+    for name in accepted {
+        let encoding = match name {
+            "br" => "br",
+            "gzip" => "gzip",
+            _ => continue,
+        };
+        if !encodings.contains(&Some(encoding)) {
+            encodings.push(Some(encoding));
+        }
+    }
+    encodings
+}
+
+/// Decide how long an asset can be cached for.
+fn cache_control(path: &str) -> &'static str {
+    let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+    let first = segments.next();
+    let is_versioned = first == Some(RUNS_PREFIX) && segments.next().is_some();
+    let filename = path.rsplit('/').next().unwrap_or_default();
+
+    if path.ends_with('/') || INDEX_FILES.contains(&filename) {
+        return SHORT_TTL;
+    }
+
+    if is_versioned && segments.next().is_some() {
+        return IMMUTABLE;
+    }
+
+    DEFAULT_TTL
+}
+
+/// Make a strong `ETag` from the file's size and modification time. Files are only ever replaced
+/// whole, so this changes whenever the content does, without having to hash gigabytes of COGs.
+/// Each encoding is a different representation so it gets its own tag. The path must already be
+/// percent-decoded.
+async fn etag(root: &std::path::Path, path: &str, encoding: Option<&str>) -> Option<HeaderValue> {
+    let mut file = root.to_path_buf();
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        let component = std::path::Path::new(segment);
+        if !matches!(
+            component.components().next(),
+            Some(std::path::Component::Normal(_))
+        ) {
+            return None;
+        }
+        file.push(component);
+    }
+    if path.ends_with('/') || path.is_empty() {
+        file.push("index.html");
+    }
+
+    let suffix = match encoding {
+        Some("br") => ".br",
+        Some("gzip") => ".gz",
+        _ => "",
+    };
+    let mut file_name = file.file_name()?.to_os_string();
+    file_name.push(suffix);
+    file.set_file_name(file_name);
+
+    let metadata = tokio::fs::metadata(&file).await.ok()?;
+    if !metadata.is_file() {
+        return None;
+    }
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?;
+    let encoding_tag = encoding.map(|name| format!("-{name}")).unwrap_or_default();
+
+    HeaderValue::from_str(&format!(
+        "\"{:x}-{:x}{encoding_tag}\"",
+        metadata.len(),
+        modified.as_nanos()
+    ))
+    .ok()
+}
+
+/// Whether an `If-None-Match` header matches the `ETag`. It's the weak comparison, so a `W/`
+/// prefix on either tag is ignored, as RFC 9110 requires for `If-None-Match`.
+fn matches_etag(if_none_match: &HeaderValue, etag: &HeaderValue) -> bool {
+    let (Ok(candidates), Ok(current)) = (if_none_match.to_str(), etag.to_str()) else {
+        return false;
+    };
+    let opaque = |tag: &str| tag.strip_prefix("W/").unwrap_or(tag).to_owned();
+    let expected = opaque(current);
+
+    candidates
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || opaque(candidate) == expected)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cache_policies() {
+        assert_eq!(
+            cache_control("/runs/0.1/longest_lines_cogs/-2.5_51.4.tiff"),
+            IMMUTABLE
+        );
+        assert_eq!(
+            cache_control("/runs/0.1/longest_lines_cogs/index.txt"),
+            SHORT_TTL
+        );
+        assert_eq!(
+            cache_control("/runs/0.1/longest_lines_grided.bin"),
+            SHORT_TTL
+        );
+        assert_eq!(cache_control("/runs/0.1"), DEFAULT_TTL);
+        assert_eq!(cache_control("/"), SHORT_TTL);
+        assert_eq!(cache_control("/world.pmtiles"), DEFAULT_TTL);
+    }
+
+    #[test]
+    fn if_none_match() {
+        let etag = HeaderValue::from_static("\"10-20\"");
+        assert!(matches_etag(
+            &HeaderValue::from_static("\"1-2\", \"10-20\""),
+            &etag
+        ));
+        assert!(matches_etag(&HeaderValue::from_static("*"), &etag));
+        assert!(!matches_etag(&HeaderValue::from_static("\"10-21\""), &etag));
+        assert!(matches_etag(
+            &HeaderValue::from_static("W/\"10-20\""),
+            &etag
+        ));
+    }
+
+    #[test]
+    fn encodings_the_client_accepts() {
+        let mut headers = axum::http::HeaderMap::new();
+        assert_eq!(accepted_encodings(&headers), vec![None]);
+
+        headers.insert(
+            header::ACCEPT_ENCODING,
+            HeaderValue::from_static("gzip, deflate, br;q=0, zstd"),
+        );
+        assert_eq!(accepted_encodings(&headers), vec![None, Some("gzip")]);
+    }
+
+    #[tokio::test]
+    async fn etag_of_a_percent_encoded_path() {
+        let root = std::env::temp_dir().join(format!("assets-etag-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("with space")).unwrap();
+        std::fs::write(root.join("with space/index.html"), "hello").unwrap();
+
+        let path = decode_path("/with%20space/").unwrap();
+        let tag = etag(&root, &path, None).await;
+        let directory = etag(&root, "/with space", None).await;
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(tag.unwrap().to_str().unwrap().starts_with("\"5-"));
+        assert_eq!(directory, None);
+    }
+}
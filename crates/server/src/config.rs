@@ -0,0 +1,91 @@
+//! Defines all the CLI arguments.
+
+/// `Config`.
+#[derive(clap::Parser, Debug, Clone)]
+#[clap(author, version)]
+#[command(name = "vv-server")]
+#[command(about = "Serves the View View website and its APIs")]
+pub struct Config {
+    /// The address and port to listen on.
+    #[arg(long, value_name = "Address", default_value = "0.0.0.0:3333")]
+    pub bind: std::net::SocketAddr,
+
+    /// The root of all the website's static assets.
+    #[arg(long, value_name = "Path to assets folder", default_value = "./assets")]
+    pub assets: std::path::PathBuf,
+
+    /// Serve a bundle made by `tasks atlas bundle` instead of `--assets`. It has the same URL
+    /// layout as the CDN, so the website works without any internet access.
+    #[arg(long, value_name = "Bundle directory", conflicts_with = "assets")]
+    pub bundle: Option<std::path::PathBuf>,
+
+    /// The folder of DEM files, containing the virtual DEM built by `tasks stitch`. Used for
+    /// elevation profiles.
+    #[arg(long, value_name = "Path to DEMs folder", default_value = "./dems")]
+    pub dems: std::path::PathBuf,
+
+    /// An origin that is allowed to make cross-origin requests, eg `https://alltheviews.world`.
+    /// Can be given more than once. Use `*` to allow any origin. No cross-origin requests are
+    /// allowed by default.
+    #[arg(long = "cors-origin", value_name = "Allowed CORS origin")]
+    pub cors_origins: Vec<String>,
+
+    /// Path to a PEM TLS certificate. Serves HTTPS when given along with `--tls-key`.
+    #[arg(long, value_name = "TLS certificate", requires = "tls_key")]
+    pub tls_cert: Option<std::path::PathBuf>,
+
+    /// Path to a PEM TLS private key.
+    #[arg(long, value_name = "TLS private key", requires = "tls_cert")]
+    pub tls_key: Option<std::path::PathBuf>,
+
+    /// How many seconds to wait for in-flight requests to finish when shutting down.
+    #[arg(long, value_name = "Seconds", default_value_t = 10)]
+    pub shutdown_timeout: u64,
+}
+
+impl Config {
+    /// Build the CORS layer from the allowed origins. `None` means cross-origin requests aren't
+    /// allowed.
+    pub fn cors(&self) -> color_eyre::Result<Option<tower_http::cors::CorsLayer>> {
+        if self.cors_origins.is_empty() {
+            return Ok(None);
+        }
+
+        let allow_origin = if self.cors_origins.iter().any(|origin| origin == "*") {
+            tower_http::cors::AllowOrigin::any()
+        } else {
+            let mut origins = Vec::new();
+            for origin in &self.cors_origins {
+                origins.push(axum::http::HeaderValue::from_str(origin)?);
+            }
+            tower_http::cors::AllowOrigin::list(origins)
+        };
+
+        Ok(Some(
+            tower_http::cors::CorsLayer::new()
+                .allow_origin(allow_origin)
+                .allow_methods([axum::http::Method::GET, axum::http::Method::HEAD]),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use clap::Parser as _;
+
+    use super::*;
+
+    #[test]
+    fn tls_needs_both_cert_and_key() {
+        Config::try_parse_from(["vv-server", "--tls-cert", "cert.pem"]).unwrap_err();
+        let config = Config::try_parse_from([
+            "vv-server",
+            "--tls-cert",
+            "cert.pem",
+            "--tls-key",
+            "key.pem",
+        ])
+        .unwrap();
+        assert!(config.tls_key.is_some());
+    }
+}
@@ -0,0 +1,46 @@
+//! Errors returned from the API routes.
+
+/// An error that can be converted into an HTTP response.
+pub struct Error {
+    /// The HTTP status code to respond with.
+    status: axum::http::StatusCode,
+    /// The underlying error.
+    report: color_eyre::Report,
+}
+
+impl Error {
+    /// The request itself is invalid.
+    pub fn bad_request(message: String) -> Self {
+        Self {
+            status: axum::http::StatusCode::BAD_REQUEST,
+            report: color_eyre::eyre::eyre!(message),
+        }
+    }
+
+    /// The requested resource doesn't exist.
+    pub fn not_found(message: String) -> Self {
+        Self {
+            status: axum::http::StatusCode::NOT_FOUND,
+            report: color_eyre::eyre::eyre!(message),
+        }
+    }
+}
+
+impl<E: Into<color_eyre::Report>> From<E> for Error {
+    fn from(error: E) -> Self {
+        Self {
+            status: axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            report: error.into(),
+        }
+    }
+}
+
+impl axum::response::IntoResponse for Error {
+    fn into_response(self) -> axum::response::Response {
+        if self.status.is_server_error() {
+            tracing::error!("{:?}", self.report);
+        }
+
+        (self.status, self.report.to_string()).into_response()
+    }
+}
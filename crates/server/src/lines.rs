@@ -0,0 +1,288 @@
+//! Query the overview of the world's longest lines, one line per H3 cell.
+//!
+//! The website downloads the whole `longest_lines_grided.bin` file, here we load it into a spatial
+//! index so that clients can just fetch the lines that they need.
+
+use tasks::atlas::longest_lines::grided::Grided;
+
+/// The default number of lines to return.
+const DEFAULT_LIMIT: usize = 100;
+
+/// The maximum number of lines that can be requested at once.
+const MAX_LIMIT: usize = 10_000;
+
+/// How we store lines for fast spatial lookups.
+type LineRstar = rstar::primitives::GeomWithData<[f64; 2], Grided>;
+
+/// All the longest lines, indexed both spatially and by their H3 cell.
+pub struct Overview {
+    /// Spatial index of all the lines.
+    tree: rstar::RTree<LineRstar>,
+    /// The line for each H3 cell.
+    cells: std::collections::HashMap<h3o::CellIndex, Grided>,
+}
+
+impl Overview {
+    /// Load the grided longest lines file.
+    pub async fn load(path: &std::path::Path) -> color_eyre::Result<Self> {
+        let grided = tasks::atlas::longest_lines::grided::read(path).await?;
+        tracing::info!("Loaded {} longest lines from {path:?}", grided.len());
+
+        let mut cells = std::collections::HashMap::new();
+        let mut points = Vec::new();
+        for line in grided {
+            let lon = f64::from(line.lon);
+            let lat = f64::from(line.lat);
+            let cell = h3o::LatLng::new(lat, lon)?
+                .to_cell(tasks::atlas::longest_lines::overview::H3_RESOLUTION);
+            cells.insert(cell, line);
+            points.push(LineRstar::new([lon, lat], line));
+        }
+
+        Ok(Self {
+            tree: rstar::RTree::bulk_load(points),
+            cells,
+        })
+    }
+
+    /// Find all the lines within a bounding box. Boxes that cross the antimeridian, where the
+    /// western longitude is greater than the eastern one, are supported.
+    fn in_bbox(&self, bbox: BoundingBox) -> Vec<Grided> {
+        let envelopes = if bbox.west > bbox.east {
+            vec![
+                rstar::AABB::from_corners([bbox.west, bbox.south], [180.0f64, bbox.north]),
+                rstar::AABB::from_corners([-180.0f64, bbox.south], [bbox.east, bbox.north]),
+            ]
+        } else {
+            vec![rstar::AABB::from_corners(
+                [bbox.west, bbox.south],
+                [bbox.east, bbox.north],
+            )]
+        };
+
+        envelopes
+            .iter()
+            .flat_map(|envelope| self.tree.locate_in_envelope(envelope))
+            .map(|line| line.data)
+            .collect()
+    }
+
+    /// Find all the lines inside an H3 cell. Cells coarser than the grid contain many lines.
+    fn in_cell(&self, cell: h3o::CellIndex) -> Vec<Grided> {
+        cell.children(tasks::atlas::longest_lines::overview::H3_RESOLUTION)
+            .filter_map(|child| self.cells.get(&child).copied())
+            .collect()
+    }
+}
+
+/// A bounding box in lon/lat degrees.
+#[derive(Debug, Clone, Copy)]
+struct BoundingBox {
+    /// Minimum longitude.
+    west: f64,
+    /// Minimum latitude.
+    south: f64,
+    /// Maximum longitude.
+    east: f64,
+    /// Maximum latitude.
+    north: f64,
+}
+
+impl std::str::FromStr for BoundingBox {
+    type Err = String;
+
+    /// Parse `west,south,east,north`.
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let parts = string
+            .split(',')
+            .map(str::parse::<f64>)
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|error| format!("Invalid bbox `{string}`: {error}"))?;
+
+        let [west, south, east, north] = parts.as_slice() else {
+            return Err(format!(
+                "Bbox `{string}` must be 4 numbers: west,south,east,north"
+            ));
+        };
+
+        let is_valid_longitude = |lon: &f64| (-180.0f64..=180.0f64).contains(lon);
+        let is_valid_latitude = |lat: &f64| (-90.0f64..=90.0f64).contains(lat);
+        if !is_valid_longitude(west)
+            || !is_valid_longitude(east)
+            || !is_valid_latitude(south)
+            || !is_valid_latitude(north)
+            || south > north
+        {
+            return Err(format!("Bbox `{string}` is outside of lon/lat bounds"));
+        }
+
+        Ok(Self {
+            west: *west,
+            south: *south,
+            east: *east,
+            north: *north,
+        })
+    }
+}
+
+/// Query parameters for `/api/lines`.
+#[derive(serde::Deserialize)]
+pub struct Query {
+    /// `west,south,east,north`. Defaults to the whole world.
+    bbox: Option<String>,
+    /// Only return lines at least this long, in meters.
+    min_distance: Option<u32>,
+    /// The maximum number of lines to return.
+    limit: Option<usize>,
+}
+
+/// A single longest line in the response.
+#[derive(serde::Serialize)]
+pub struct Line {
+    /// Longitude of the line's point of view.
+    lon: f32,
+    /// Latitude of the line's point of view.
+    lat: f32,
+    /// The distance of the line of sight in meters.
+    distance: u32,
+    /// The H3 cell containing the line.
+    cell: String,
+}
+
+impl Line {
+    /// Convert from the grided format.
+    fn from_grided(grided: Grided) -> Self {
+        let cell = h3o::LatLng::new(f64::from(grided.lat), f64::from(grided.lon))
+            .map(|latlng| {
+                latlng
+                    .to_cell(tasks::atlas::longest_lines::overview::H3_RESOLUTION)
+                    .to_string()
+            })
+            .unwrap_or_default();
+
+        Self {
+            lon: grided.lon,
+            lat: grided.lat,
+            distance: grided.distance,
+            cell,
+        }
+    }
+}
+
+/// Sort lines longest first, and convert them for the response.
+fn longest_first(mut lines: Vec<Grided>, min_distance: u32, limit: usize) -> Vec<Line> {
+    lines.retain(|line| line.distance >= min_distance);
+    lines.sort_by_key(|line| std::cmp::Reverse(line.distance));
+    lines.truncate(limit);
+    lines.into_iter().map(Line::from_grided).collect()
+}
+
+/// Handle `GET /api/lines?bbox=&min_distance=&limit=`.
+pub async fn handler(
+    axum::extract::State(state): axum::extract::State<std::sync::Arc<crate::State>>,
+    axum::extract::Query(query): axum::extract::Query<Query>,
+) -> Result<axum::Json<Vec<Line>>, crate::error::Error> {
+    let overview = overview(&state)?;
+    let bbox = match query.bbox {
+        Some(string) => string
+            .parse::<BoundingBox>()
+            .map_err(crate::error::Error::bad_request)?,
+        None => BoundingBox {
+            west: -180.0,
+            south: -90.0,
+            east: 180.0,
+            north: 90.0,
+        },
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+
+    Ok(axum::Json(longest_first(
+        overview.in_bbox(bbox),
+        query.min_distance.unwrap_or(0),
+        limit,
+    )))
+}
+
+/// Handle `GET /api/h3/{cell}`. The cell is in its hexadecimal string form.
+pub async fn cell_handler(
+    axum::extract::State(state): axum::extract::State<std::sync::Arc<crate::State>>,
+    axum::extract::Path(cell_string): axum::extract::Path<String>,
+) -> Result<axum::Json<Vec<Line>>, crate::error::Error> {
+    let overview = overview(&state)?;
+    let cell = cell_string
+        .parse::<h3o::CellIndex>()
+        .map_err(|error| crate::error::Error::bad_request(format!("Invalid H3 cell: {error}")))?;
+    if cell.resolution() > tasks::atlas::longest_lines::overview::H3_RESOLUTION {
+        return Err(crate::error::Error::bad_request(format!(
+            "H3 cell {cell} is finer than the longest lines grid ({})",
+            tasks::atlas::longest_lines::overview::H3_RESOLUTION
+        )));
+    }
+
+    let lines = longest_first(overview.in_cell(cell), 0, MAX_LIMIT);
+    if lines.is_empty() {
+        return Err(crate::error::Error::not_found(format!(
+            "No longest lines in H3 cell: {cell}"
+        )));
+    }
+
+    Ok(axum::Json(lines))
+}
+
+/// Get the longest lines overview, if the server was started with one.
+fn overview(state: &crate::State) -> Result<&Overview, crate::error::Error> {
+    state.lines.as_ref().ok_or_else(|| {
+        crate::error::Error::not_found("No longest lines overview is being served".to_owned())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_bbox() {
+        let bbox = "-10.5,40,20,60.25".parse::<BoundingBox>().unwrap();
+        assert!((bbox.west - -10.5).abs() < f64::EPSILON);
+        assert!((bbox.north - 60.25).abs() < f64::EPSILON);
+
+        "1,2,3".parse::<BoundingBox>().unwrap_err();
+        "0,10,1,5".parse::<BoundingBox>().unwrap_err();
+        "0,0,200,10".parse::<BoundingBox>().unwrap_err();
+    }
+
+    #[test]
+    fn antimeridian_bbox() {
+        let lines = [
+            Grided {
+                lon: 179.5,
+                lat: 0.0,
+                distance: 1,
+            },
+            Grided {
+                lon: -179.5,
+                lat: 0.0,
+                distance: 2,
+            },
+            Grided {
+                lon: 0.0,
+                lat: 0.0,
+                distance: 3,
+            },
+        ];
+        let overview = Overview {
+            tree: rstar::RTree::bulk_load(
+                lines
+                    .iter()
+                    .map(|line| LineRstar::new([f64::from(line.lon), f64::from(line.lat)], *line))
+                    .collect(),
+            ),
+            cells: std::collections::HashMap::new(),
+        };
+
+        let found = overview.in_bbox("179,-1,-179,1".parse().unwrap());
+        let mut distances: Vec<u32> = found.iter().map(|line| line.distance).collect();
+        distances.sort_unstable();
+        assert_eq!(distances, vec![1, 2]);
+    }
+}
@@ -0,0 +1,65 @@
+//! Get the longest line of sight at a coordinate.
+
+/// Query parameters for `/api/longest`.
+#[derive(serde::Deserialize)]
+pub struct Query {
+    /// Longitude of the point of view.
+    lon: f64,
+    /// Latitude of the point of view.
+    lat: f64,
+}
+
+/// The longest line of sight as JSON. Coordinates are `[lon, lat]`.
+#[derive(serde::Serialize)]
+pub struct Response {
+    /// The distance of the line of sight in meters.
+    distance: u32,
+    /// The angle of the line of sight in degrees, anti-clockwise from East.
+    angle: u16,
+    /// The point of view.
+    from: (f64, f64),
+    /// The furthest visible point.
+    to: (f64, f64),
+}
+
+/// Handle `GET /api/longest?lon=&lat=`.
+pub async fn handler(
+    axum::extract::State(state): axum::extract::State<std::sync::Arc<crate::State>>,
+    axum::extract::Query(query): axum::extract::Query<Query>,
+) -> Result<axum::Json<Response>, crate::error::Error> {
+    if !(-180.0f64..=180.0f64).contains(&query.lon) || !(-90.0f64..=90.0f64).contains(&query.lat) {
+        return Err(crate::error::Error::bad_request(format!(
+            "Invalid coordinate: {},{}",
+            query.lon, query.lat
+        )));
+    }
+
+    let coordinate = tasks::projector::LonLatCoord(geo::coord! { x: query.lon, y: query.lat });
+    let index = std::sync::Arc::clone(index(&state)?);
+
+    // Reading the COGs with GDAL is blocking.
+    let longest = tokio::task::spawn_blocking(move || index.longest_line(coordinate)).await??;
+
+    let Some(line) = longest else {
+        return Err(crate::error::Error::not_found(format!(
+            "No longest line of sight found at: {},{}",
+            query.lon, query.lat
+        )));
+    };
+
+    Ok(axum::Json(Response {
+        distance: line.distance,
+        angle: line.angle,
+        from: (line.from.0.x, line.from.0.y),
+        to: (line.to.0.x, line.to.0.y),
+    }))
+}
+
+/// The longest lines index, if it's being served.
+fn index(
+    state: &crate::State,
+) -> Result<&std::sync::Arc<tasks::atlas::longest_lines::lookup::Index>, crate::error::Error> {
+    state.longest.as_ref().ok_or_else(|| {
+        crate::error::Error::not_found("No longest lines index is being served".to_owned())
+    })
+}
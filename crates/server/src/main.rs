@@ -1,16 +1,194 @@
-//! serves the website
+//! Serves the website and its APIs.
+
+use clap::Parser as _;
+use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _};
+
+mod assets;
+mod config;
+mod error;
+mod lines;
+mod longest;
+mod paths;
+mod pmtiles;
+mod profile;
+mod tiles;
+
+/// State shared between all the routes.
+pub struct State {
+    /// The server's CLI config.
+    config: config::Config,
+    /// Where all the files being served are.
+    paths: paths::Paths,
+    /// The static assets.
+    assets: tower_http::services::ServeDir,
+    /// The world heatmap archive. It's optional so that the rest of the site can still be served
+    /// without it.
+    pmtiles: Option<pmtiles::Archive>,
+    /// The overview of the longest lines in every H3 cell. Also optional.
+    lines: Option<lines::Overview>,
+    /// The index of the longest lines of sight COGs, loaded once at startup. Also optional.
+    longest: Option<std::sync::Arc<tasks::atlas::longest_lines::lookup::Index>>,
+}
 
 #[tokio::main]
-async fn main() {
-    let serve_index = tower_http::services::ServeDir::new("./assets");
+async fn main() -> color_eyre::Result<()> {
+    setup_logging()?;
+
+    let config = config::Config::parse();
+    tracing::info!("Initialising with config: {config:?}");
+
+    let paths = paths::Paths::new(&config)?;
+    let pmtiles = match pmtiles::Archive::open(&paths.pmtiles).await {
+        Ok(archive) => Some(archive),
+        Err(error) => {
+            tracing::warn!(
+                "Not serving tiles, couldn't open {:?}: {error}",
+                paths.pmtiles
+            );
+            None
+        }
+    };
+
+    let lines = match lines::Overview::load(&paths.longest_lines_grided).await {
+        Ok(overview) => Some(overview),
+        Err(error) => {
+            tracing::warn!(
+                "Not serving longest lines, couldn't load {:?}: {error}",
+                paths.longest_lines_grided
+            );
+            None
+        }
+    };
+
+    let longest = match tasks::atlas::longest_lines::lookup::Index::load(&paths.longest_lines_cogs)
+    {
+        Ok(index) => Some(std::sync::Arc::new(index)),
+        Err(error) => {
+            tracing::warn!(
+                "Not serving longest lines of sight, couldn't load {:?}: {error}",
+                paths.longest_lines_cogs
+            );
+            None
+        }
+    };
+
+    let cors = config.cors()?;
+    let state = std::sync::Arc::new(State {
+        config: config.clone(),
+        assets: assets::serve_dir(&paths.root),
+        paths,
+        pmtiles,
+        lines,
+        longest,
+    });
 
     let app = axum::Router::new()
-        .fallback_service(serve_index)
-        .layer(tower::ServiceBuilder::new().layer(tower_http::trace::TraceLayer::new_for_http()));
-
-    // run our app with hyper, listening globally on port 3000
-    #[expect(clippy::unwrap_used, reason="we need to crash the server if it can't start")]
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3333").await.unwrap();
-    #[expect(clippy::unwrap_used, reason="we need to crash the server if it can't start")]
-    axum::serve(listener, app).await.unwrap();
+        .route("/healthz", axum::routing::get(healthz))
+        .route("/api/longest", axum::routing::get(longest::handler))
+        .route("/api/profile", axum::routing::get(profile::handler))
+        .route("/api/lines", axum::routing::get(lines::handler))
+        .route("/api/h3/{cell}", axum::routing::get(lines::cell_handler))
+        .route("/tiles/metadata", axum::routing::get(tiles::metadata))
+        .route("/tiles/{z}/{x}/{y}", axum::routing::get(tiles::handler))
+        .route(
+            "/runs/{run_id}/pmtiles/world.pmtiles/world/{z}/{x}/{y}",
+            axum::routing::get(tiles::run_handler),
+        )
+        .route(
+            "/runs/{run_id}/pmtiles/cache/{z}/{x}/{y}",
+            axum::routing::get(tiles::run_handler),
+        )
+        .fallback(assets::handler)
+        .with_state(state)
+        .layer(
+            tower::ServiceBuilder::new()
+                .layer(tower_http::trace::TraceLayer::new_for_http())
+                .option_layer(cors),
+        );
+
+    let handle = axum_server::Handle::new();
+    tokio::spawn(shutdown_on_signal(
+        handle.clone(),
+        std::time::Duration::from_secs(config.shutdown_timeout),
+    ));
+
+    let service = app.into_make_service();
+    if let (Some(cert), Some(key)) = (&config.tls_cert, &config.tls_key) {
+        if rustls::crypto::ring::default_provider()
+            .install_default()
+            .is_err()
+        {
+            tracing::debug!("A TLS crypto provider is already installed");
+        }
+        let tls = axum_server::tls_rustls::RustlsConfig::from_pem_file(cert, key).await?;
+
+        tracing::info!("Listening on https://{}", config.bind);
+        axum_server::bind_rustls(config.bind, tls)
+            .handle(handle)
+            .serve(service)
+            .await?;
+    } else {
+        tracing::info!("Listening on http://{}", config.bind);
+        axum_server::bind(config.bind)
+            .handle(handle)
+            .serve(service)
+            .await?;
+    }
+
+    tracing::info!("Server stopped");
+    Ok(())
+}
+
+/// Handle `GET /healthz`. For load balancers and service managers to check that we're up.
+async fn healthz() -> &'static str {
+    "OK"
+}
+
+/// Wait for Ctrl-C or `SIGTERM` and then gracefully shutdown the server. In-flight requests get
+/// `timeout` to finish.
+#[expect(
+    clippy::integer_division_remainder_used,
+    reason = "`tokio::select!` uses `%` internally"
+)]
+async fn shutdown_on_signal(handle: axum_server::Handle, timeout: std::time::Duration) {
+    let ctrl_c = async {
+        if let Err(error) = tokio::signal::ctrl_c().await {
+            tracing::error!("Couldn't listen for Ctrl-C: {error}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(error) => {
+                tracing::error!("Couldn't listen for SIGTERM: {error}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    tokio::select! {
+        () = ctrl_c => {},
+        () = terminate => {},
+    }
+
+    tracing::info!("Shutting down, waiting up to {timeout:?} for requests to finish");
+    handle.graceful_shutdown(Some(timeout));
+}
+
+/// Setup logging.
+fn setup_logging() -> color_eyre::Result<()> {
+    let filters = tracing_subscriber::EnvFilter::builder()
+        .with_default_directive("info".parse()?)
+        .from_env_lossy();
+
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer())
+        .with(filters)
+        .init();
+
+    Ok(())
 }
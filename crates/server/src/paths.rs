@@ -0,0 +1,59 @@
+//! Where to find all the files that the server needs.
+//!
+//! They either come from a plain assets directory, or from a bundle made by `tasks atlas bundle`
+//! which has the same layout as the CDN.
+
+/// The world heatmap archive, relative to the assets root.
+const WORLD_PMTILES: &str = "world.pmtiles";
+
+/// Where the longest lines COGs and their index live, relative to the assets root.
+const LONGEST_LINES_COGS: &str = "longest_lines_cogs";
+
+/// All the paths the server reads from.
+#[derive(Debug, Clone)]
+pub struct Paths {
+    /// The root of all the static assets.
+    pub root: std::path::PathBuf,
+    /// The run being served, only when serving a bundle.
+    pub run_id: Option<String>,
+    /// The world heatmap archive.
+    pub pmtiles: std::path::PathBuf,
+    /// The directory of longest lines COGs and their index.
+    pub longest_lines_cogs: std::path::PathBuf,
+    /// The grid of longest lines made by `tasks atlas longest-lines-overviews`.
+    pub longest_lines_grided: std::path::PathBuf,
+}
+
+impl Paths {
+    /// Work out all the paths from the server's config.
+    pub fn new(config: &crate::config::Config) -> color_eyre::Result<Self> {
+        let Some(bundle) = &config.bundle else {
+            let longest_lines_cogs = config.assets.join(LONGEST_LINES_COGS);
+            return Ok(Self {
+                root: config.assets.clone(),
+                run_id: None,
+                pmtiles: config.assets.join(WORLD_PMTILES),
+                longest_lines_grided: longest_lines_cogs
+                    .join(tasks::atlas::longest_lines::overview::LONGEST_LINES_GRIDED_FILENAME),
+                longest_lines_cogs,
+            });
+        };
+
+        let manifest = tasks::atlas::bundle::Manifest::load(bundle)?;
+        tracing::info!(
+            "Serving bundle of run `{}` with {} files",
+            manifest.run_id,
+            manifest.files.len()
+        );
+        let run = bundle.join(manifest.run_directory());
+
+        Ok(Self {
+            root: bundle.clone(),
+            pmtiles: run.join(tasks::atlas::bundle::PMTILES_PATH),
+            longest_lines_cogs: run.join(tasks::atlas::bundle::LONGEST_LINES_COGS),
+            longest_lines_grided: run
+                .join(tasks::atlas::longest_lines::overview::LONGEST_LINES_GRIDED_FILENAME),
+            run_id: Some(manifest.run_id),
+        })
+    }
+}
@@ -0,0 +1,538 @@
+//! Read individual tiles from a `PMTiles` v3 archive.
+//!
+//! See the spec: <https://github.com/protomaps/PMTiles/blob/main/spec/v3/spec.md>.
+//!
+//! The archive is a header, a root directory, optional metadata, optional leaf directories and
+//! then all the tile data. Directories map Hilbert-curve tile IDs to byte ranges in either the
+//! tile data or the leaf directories.
+
+use std::io::Read as _;
+
+use color_eyre::Result;
+use tokio::io::{AsyncReadExt as _, AsyncSeekExt as _};
+
+/// The length in bytes of the fixed-size header.
+const HEADER_LENGTH: usize = 127;
+
+/// The magic bytes at the start of every `PMTiles` file.
+const MAGIC: &[u8] = b"PMTiles";
+
+/// The only version of the spec that we support.
+const VERSION: u8 = 3;
+
+/// The spec guarantees that a tile can be found by following at most this many directories.
+const MAX_DIRECTORY_DEPTH: usize = 4;
+
+/// How the directories, metadata or tiles are compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Not declared in the archive.
+    Unknown,
+    /// No compression.
+    None,
+    /// Gzip.
+    Gzip,
+    /// Brotli.
+    Brotli,
+    /// Zstandard.
+    Zstd,
+}
+
+impl Compression {
+    /// Parse the compression byte from the header.
+    fn from_byte(byte: u8) -> Result<Self> {
+        Ok(match byte {
+            0 => Self::Unknown,
+            1 => Self::None,
+            2 => Self::Gzip,
+            3 => Self::Brotli,
+            4 => Self::Zstd,
+            _ => color_eyre::eyre::bail!("Unknown PMTiles compression: {byte}"),
+        })
+    }
+
+    /// The value for the HTTP `Content-Encoding` header, if the tile data needs one.
+    pub const fn content_encoding(self) -> Option<&'static str> {
+        match self {
+            Self::Gzip => Some("gzip"),
+            Self::Brotli => Some("br"),
+            Self::Zstd => Some("zstd"),
+            Self::Unknown | Self::None => None,
+        }
+    }
+}
+
+/// The format of the tiles in the archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileType {
+    /// Not declared in the archive. Our world heatmap is like this, as its tiles are just raw
+    /// `f32`s.
+    Unknown,
+    /// Mapbox Vector Tiles.
+    Mvt,
+    /// PNG images.
+    Png,
+    /// JPEG images.
+    Jpeg,
+    /// WebP images.
+    Webp,
+    /// AVIF images.
+    Avif,
+}
+
+impl TileType {
+    /// Parse the tile type byte from the header.
+    fn from_byte(byte: u8) -> Result<Self> {
+        Ok(match byte {
+            0 => Self::Unknown,
+            1 => Self::Mvt,
+            2 => Self::Png,
+            3 => Self::Jpeg,
+            4 => Self::Webp,
+            5 => Self::Avif,
+            _ => color_eyre::eyre::bail!("Unknown PMTiles tile type: {byte}"),
+        })
+    }
+
+    /// The value for the HTTP `Content-Type` header.
+    pub const fn content_type(self) -> &'static str {
+        match self {
+            Self::Unknown => "application/octet-stream",
+            Self::Mvt => "application/vnd.mapbox-vector-tile",
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Webp => "image/webp",
+            Self::Avif => "image/avif",
+        }
+    }
+}
+
+/// The fixed-size header at the start of the archive. We only keep what we need to find tiles.
+#[derive(Debug, Clone, Copy)]
+pub struct Header {
+    /// Byte offset of the root directory.
+    root_directory_offset: u64,
+    /// Byte length of the root directory.
+    root_directory_length: u64,
+    /// Byte offset of the JSON metadata.
+    metadata_offset: u64,
+    /// Byte length of the JSON metadata.
+    metadata_length: u64,
+    /// Byte offset of the leaf directories section.
+    leaf_directories_offset: u64,
+    /// Byte offset of the tile data section.
+    tile_data_offset: u64,
+    /// How the directories and metadata are compressed.
+    internal_compression: Compression,
+    /// How each tile is compressed.
+    pub tile_compression: Compression,
+    /// The format of each tile.
+    pub tile_type: TileType,
+    /// The lowest zoom level in the archive.
+    pub min_zoom: u8,
+    /// The highest zoom level in the archive.
+    pub max_zoom: u8,
+}
+
+impl Header {
+    /// Parse the header bytes.
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_LENGTH || bytes.get(..MAGIC.len()) != Some(MAGIC) {
+            color_eyre::eyre::bail!("Not a PMTiles archive");
+        }
+
+        let version = byte_at(bytes, 7)?;
+        if version != VERSION {
+            color_eyre::eyre::bail!("Unsupported PMTiles version: {version}");
+        }
+
+        Ok(Self {
+            root_directory_offset: u64_at(bytes, 8)?,
+            root_directory_length: u64_at(bytes, 16)?,
+            metadata_offset: u64_at(bytes, 24)?,
+            metadata_length: u64_at(bytes, 32)?,
+            leaf_directories_offset: u64_at(bytes, 40)?,
+            tile_data_offset: u64_at(bytes, 56)?,
+            internal_compression: Compression::from_byte(byte_at(bytes, 97)?)?,
+            tile_compression: Compression::from_byte(byte_at(bytes, 98)?)?,
+            tile_type: TileType::from_byte(byte_at(bytes, 99)?)?,
+            min_zoom: byte_at(bytes, 100)?,
+            max_zoom: byte_at(bytes, 101)?,
+        })
+    }
+}
+
+/// Get a single byte from the header.
+fn byte_at(bytes: &[u8], offset: usize) -> Result<u8> {
+    bytes
+        .get(offset)
+        .copied()
+        .ok_or_else(|| color_eyre::eyre::eyre!("PMTiles header too short"))
+}
+
+/// Get a little-endian `u64` from the header.
+#[expect(
+    clippy::little_endian_bytes,
+    reason = "The PMTiles spec defines the header as little-endian"
+)]
+fn u64_at(bytes: &[u8], offset: usize) -> Result<u64> {
+    let slice = bytes
+        .get(offset..offset + 8)
+        .ok_or_else(|| color_eyre::eyre::eyre!("PMTiles header too short"))?;
+    Ok(u64::from_le_bytes(slice.try_into()?))
+}
+
+/// A single entry in a directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Entry {
+    /// The first tile ID that the entry covers.
+    tile_id: u64,
+    /// Byte offset relative to the tile data section, or the leaf directories section if this
+    /// entry points to a leaf directory.
+    offset: u64,
+    /// Byte length of the tile or leaf directory.
+    length: u32,
+    /// How many consecutive tile IDs share the same tile data. Zero means that this entry points
+    /// to a leaf directory.
+    run_length: u32,
+}
+
+/// A decoded directory, sorted by tile ID.
+type Directory = Vec<Entry>;
+
+/// An open `PMTiles` archive.
+pub struct Archive {
+    /// Path to the `.pmtiles` file.
+    path: std::path::PathBuf,
+    /// The parsed header.
+    pub header: Header,
+    /// The root directory, it's always needed so we keep it in memory.
+    root: std::sync::Arc<Directory>,
+    /// Leaf directories that we've already decoded, keyed by their offset.
+    leaves: tokio::sync::RwLock<std::collections::HashMap<u64, std::sync::Arc<Directory>>>,
+}
+
+impl Archive {
+    /// Open an archive and parse its header and root directory.
+    pub async fn open(path: &std::path::Path) -> Result<Self> {
+        let mut file = tokio::fs::File::open(path).await?;
+        let mut header_bytes = vec![0u8; HEADER_LENGTH];
+        file.read_exact(&mut header_bytes).await?;
+        let header = Header::parse(&header_bytes)?;
+        tracing::debug!("PMTiles header for {path:?}: {header:?}");
+
+        let root_bytes = read_range(
+            &mut file,
+            header.root_directory_offset,
+            header.root_directory_length,
+        )
+        .await?;
+        let root = decode_directory(&decompress(&root_bytes, header.internal_compression)?)?;
+        tracing::debug!("PMTiles root directory has {} entries", root.len());
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            header,
+            root: std::sync::Arc::new(root),
+            leaves: tokio::sync::RwLock::default(),
+        })
+    }
+
+    /// The archive's JSON metadata, decompressed.
+    pub async fn metadata(&self) -> Result<Vec<u8>> {
+        let mut file = tokio::fs::File::open(&self.path).await?;
+        let bytes = read_range(
+            &mut file,
+            self.header.metadata_offset,
+            self.header.metadata_length,
+        )
+        .await?;
+        decompress(&bytes, self.header.internal_compression)
+    }
+
+    /// Get the raw bytes of a single tile. The bytes are still compressed with the archive's
+    /// `tile_compression`.
+    pub async fn tile(&self, z: u8, x: u32, y: u32) -> Result<Option<Vec<u8>>> {
+        if z < self.header.min_zoom || z > self.header.max_zoom {
+            return Ok(None);
+        }
+        let Some(tile_id) = zxy_to_tile_id(z, x, y) else {
+            return Ok(None);
+        };
+
+        let mut file = tokio::fs::File::open(&self.path).await?;
+        let mut directory = std::sync::Arc::clone(&self.root);
+        for _ in 0..MAX_DIRECTORY_DEPTH {
+            let Some(entry) = find_entry(&directory, tile_id) else {
+                return Ok(None);
+            };
+
+            if entry.run_length > 0 {
+                let offset = self.header.tile_data_offset + entry.offset;
+                let bytes = read_range(&mut file, offset, entry.length.into()).await?;
+                return Ok(Some(bytes));
+            }
+
+            directory = self.leaf(&mut file, entry).await?;
+        }
+
+        color_eyre::eyre::bail!("PMTiles directories are nested too deeply for tile {z}/{x}/{y}");
+    }
+
+    /// Get a leaf directory, either from the cache or by reading it from the archive.
+    async fn leaf(
+        &self,
+        file: &mut tokio::fs::File,
+        entry: Entry,
+    ) -> Result<std::sync::Arc<Directory>> {
+        if let Some(cached) = self.leaves.read().await.get(&entry.offset) {
+            return Ok(std::sync::Arc::clone(cached));
+        }
+
+        let offset = self.header.leaf_directories_offset + entry.offset;
+        let bytes = read_range(file, offset, entry.length.into()).await?;
+        let leaf = std::sync::Arc::new(decode_directory(&decompress(
+            &bytes,
+            self.header.internal_compression,
+        )?)?);
+        self.leaves
+            .write()
+            .await
+            .insert(entry.offset, std::sync::Arc::clone(&leaf));
+
+        Ok(leaf)
+    }
+}
+
+/// Read a byte range from the archive.
+async fn read_range(file: &mut tokio::fs::File, offset: u64, length: u64) -> Result<Vec<u8>> {
+    file.seek(std::io::SeekFrom::Start(offset)).await?;
+    let mut bytes = vec![0u8; usize::try_from(length)?];
+    file.read_exact(&mut bytes).await?;
+    Ok(bytes)
+}
+
+/// Decompress directories or metadata.
+fn decompress(bytes: &[u8], compression: Compression) -> Result<Vec<u8>> {
+    match compression {
+        Compression::Unknown | Compression::None => Ok(bytes.to_vec()),
+        Compression::Gzip => {
+            let mut decompressed = Vec::new();
+            flate2::read::GzDecoder::new(bytes).read_to_end(&mut decompressed)?;
+            Ok(decompressed)
+        }
+        Compression::Brotli | Compression::Zstd => {
+            color_eyre::eyre::bail!("Unsupported PMTiles internal compression: {compression:?}")
+        }
+    }
+}
+
+/// Read a single unsigned LEB128 varint.
+fn read_varint(bytes: &[u8], position: &mut usize) -> Result<u64> {
+    let mut value = 0u64;
+    let mut shift = 0u32;
+    loop {
+        let byte = *bytes
+            .get(*position)
+            .ok_or_else(|| color_eyre::eyre::eyre!("Truncated PMTiles directory"))?;
+        *position += 1;
+        if shift >= 64 {
+            color_eyre::eyre::bail!("PMTiles varint too long");
+        }
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+/// Decode a decompressed directory. It's stored in columns: first the number of entries, then all
+/// the delta-encoded tile IDs, then all the run lengths, lengths and finally the offsets.
+fn decode_directory(bytes: &[u8]) -> Result<Directory> {
+    let mut position = 0;
+    let count = usize::try_from(read_varint(bytes, &mut position)?)?;
+    let mut entries = vec![
+        Entry {
+            tile_id: 0,
+            offset: 0,
+            length: 0,
+            run_length: 0,
+        };
+        count
+    ];
+
+    let mut last_id = 0u64;
+    for entry in &mut entries {
+        last_id += read_varint(bytes, &mut position)?;
+        entry.tile_id = last_id;
+    }
+
+    for entry in &mut entries {
+        entry.run_length = u32::try_from(read_varint(bytes, &mut position)?)?;
+    }
+
+    for entry in &mut entries {
+        entry.length = u32::try_from(read_varint(bytes, &mut position)?)?;
+    }
+
+    let mut previous: Option<Entry> = None;
+    for entry in &mut entries {
+        let value = read_varint(bytes, &mut position)?;
+        entry.offset = match previous {
+            // A zero offset means that this entry's data directly follows the previous entry's.
+            Some(last) if value == 0 => last.offset + u64::from(last.length),
+            _ => value.saturating_sub(1),
+        };
+        previous = Some(*entry);
+    }
+
+    Ok(entries)
+}
+
+/// Find the entry in a directory that covers the tile ID.
+fn find_entry(directory: &[Entry], tile_id: u64) -> Option<Entry> {
+    let index = directory.partition_point(|entry| entry.tile_id <= tile_id);
+    let entry = *directory.get(index.checked_sub(1)?)?;
+
+    // Leaf directories cover all the tile IDs up until the next entry.
+    if entry.run_length == 0 || tile_id - entry.tile_id < u64::from(entry.run_length) {
+        return Some(entry);
+    }
+
+    None
+}
+
+/// Convert a tile coordinate to its ID along the Hilbert curve. All the tiles of lower zoom levels
+/// come first.
+fn zxy_to_tile_id(z: u8, x: u32, y: u32) -> Option<u64> {
+    // Beyond zoom 31 the tile IDs wouldn't fit in a `u64`.
+    if z > 31 {
+        return None;
+    }
+    let size = 1u64 << z;
+    let (mut tx, mut ty) = (u64::from(x), u64::from(y));
+    if tx >= size || ty >= size {
+        return None;
+    }
+
+    let tiles_in_lower_zooms = ((1u64 << (2 * u32::from(z))) - 1).div_euclid(3);
+
+    let mut distance = 0u64;
+    let mut step = size >> 1u8;
+    while step > 0 {
+        let rx = u64::from((tx & step) > 0);
+        let ry = u64::from((ty & step) > 0);
+        distance += step * step * ((3 * rx) ^ ry);
+
+        if ry == 0 {
+            if rx == 1 {
+                tx = size - 1 - tx;
+                ty = size - 1 - ty;
+            }
+            std::mem::swap(&mut tx, &mut ty);
+        }
+        step >>= 1u8;
+    }
+
+    Some(tiles_in_lower_zooms + distance)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Encode a directory, the opposite of `decode_directory()`.
+    fn encode_directory(entries: &[Entry]) -> Vec<u8> {
+        fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+            while value >= 0x80 {
+                bytes.push(u8::try_from(value & 0x7f).unwrap() | 0x80);
+                value >>= 7u8;
+            }
+            bytes.push(u8::try_from(value).unwrap());
+        }
+
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, entries.len().try_into().unwrap());
+        let mut last_id = 0u64;
+        for entry in entries {
+            write_varint(&mut bytes, entry.tile_id - last_id);
+            last_id = entry.tile_id;
+        }
+        for entry in entries {
+            write_varint(&mut bytes, entry.run_length.into());
+        }
+        for entry in entries {
+            write_varint(&mut bytes, entry.length.into());
+        }
+        for entry in entries {
+            write_varint(&mut bytes, entry.offset + 1);
+        }
+        bytes
+    }
+
+    #[test]
+    fn tile_ids() {
+        assert_eq!(zxy_to_tile_id(0, 0, 0), Some(0));
+        assert_eq!(zxy_to_tile_id(1, 0, 0), Some(1));
+        assert_eq!(zxy_to_tile_id(1, 0, 1), Some(2));
+        assert_eq!(zxy_to_tile_id(1, 1, 1), Some(3));
+        assert_eq!(zxy_to_tile_id(1, 1, 0), Some(4));
+        assert_eq!(zxy_to_tile_id(2, 0, 0), Some(5));
+        assert_eq!(zxy_to_tile_id(1, 2, 0), None);
+    }
+
+    #[test]
+    fn directory_round_trip() {
+        let entries = vec![
+            Entry {
+                tile_id: 0,
+                offset: 0,
+                length: 100,
+                run_length: 1,
+            },
+            Entry {
+                tile_id: 5,
+                offset: 100,
+                length: 300,
+                run_length: 3,
+            },
+            Entry {
+                tile_id: 1000,
+                offset: 0,
+                length: 42,
+                run_length: 0,
+            },
+        ];
+        let decoded = decode_directory(&encode_directory(&entries)).unwrap();
+        assert_eq!(decoded, entries);
+    }
+
+    #[test]
+    fn find_entries() {
+        let directory = vec![
+            Entry {
+                tile_id: 5,
+                offset: 0,
+                length: 10,
+                run_length: 2,
+            },
+            Entry {
+                tile_id: 100,
+                offset: 0,
+                length: 10,
+                run_length: 0,
+            },
+        ];
+        assert_eq!(find_entry(&directory, 4), None);
+        assert_eq!(
+            find_entry(&directory, 6).map(|entry| entry.tile_id),
+            Some(5)
+        );
+        assert_eq!(find_entry(&directory, 7), None);
+        assert_eq!(
+            find_entry(&directory, 12345).map(|entry| entry.tile_id),
+            Some(100)
+        );
+    }
+}
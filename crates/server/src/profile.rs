@@ -0,0 +1,78 @@
+//! Get the elevation profile between two points.
+
+/// Query parameters for `/api/profile`.
+#[derive(serde::Deserialize)]
+pub struct Query {
+    /// Longitude of the observer.
+    from_lon: f64,
+    /// Latitude of the observer.
+    from_lat: f64,
+    /// Longitude of the target.
+    to_lon: f64,
+    /// Latitude of the target.
+    to_lat: f64,
+    /// The height of the observer above the ground in meters.
+    observer_height: Option<f32>,
+    /// The height of the target above the ground in meters.
+    target_height: Option<f32>,
+    /// How much the atmosphere bends lines of sight back down towards the earth.
+    refraction: Option<f64>,
+}
+
+/// Handle `GET /api/profile?from_lon=&from_lat=&to_lon=&to_lat=&observer_height=`. The
+/// `target_height` and `refraction` can also be set.
+pub async fn handler(
+    axum::extract::State(state): axum::extract::State<std::sync::Arc<crate::State>>,
+    axum::extract::Query(query): axum::extract::Query<Query>,
+) -> Result<axum::Json<tasks::profile::Profile>, crate::error::Error> {
+    let from = coordinate(query.from_lon, query.from_lat)?;
+    let to = coordinate(query.to_lon, query.to_lat)?;
+    let refraction = query
+        .refraction
+        .unwrap_or(tasks::horizon::DEFAULT_REFRACTION);
+    if !refraction.is_finite() || refraction >= 1.0f64 {
+        return Err(crate::error::Error::bad_request(format!(
+            "Refraction coefficient must be less than 1: {refraction}"
+        )));
+    }
+    let line_of_sight = tasks::config::LineOfSight {
+        refraction,
+        observer_height: query.observer_height.unwrap_or(0.0),
+        target_height: query.target_height.unwrap_or(0.0),
+    };
+
+    let dem = tasks::stitch::virtual_dem_path(&state.config.dems);
+    if !dem.exists() {
+        return Err(crate::error::Error::not_found(
+            "No DEM is available for elevation profiles".to_owned(),
+        ));
+    }
+
+    // Reading the DEM with GDAL is blocking.
+    let profile = tokio::task::spawn_blocking(move || {
+        tasks::profile::Profile::sample(
+            &dem,
+            from,
+            to,
+            line_of_sight,
+            tasks::projector::Earth::Wgs84,
+            tasks::profile::DEFAULT_SPACING,
+        )
+    })
+    .await??;
+
+    Ok(axum::Json(profile))
+}
+
+/// Validate a lon/lat coordinate from the query.
+fn coordinate(lon: f64, lat: f64) -> Result<tasks::projector::LonLatCoord, crate::error::Error> {
+    if !(-180.0f64..=180.0f64).contains(&lon) || !(-90.0f64..=90.0f64).contains(&lat) {
+        return Err(crate::error::Error::bad_request(format!(
+            "Invalid coordinate: {lon},{lat}"
+        )));
+    }
+
+    Ok(tasks::projector::LonLatCoord(
+        geo::coord! { x: lon, y: lat },
+    ))
+}
@@ -0,0 +1,84 @@
+//! Serve individual tiles from the world `.pmtiles` archive.
+
+use axum::response::IntoResponse as _;
+
+/// Handle `GET /tiles/{z}/{x}/{y}`.
+///
+/// `y` can have a file extension, eg `.bin`, so that the URLs can match the ones that the website
+/// uses for the production map server.
+pub async fn handler(
+    axum::extract::State(state): axum::extract::State<std::sync::Arc<crate::State>>,
+    axum::extract::Path((z, x, y)): axum::extract::Path<(u8, u32, String)>,
+) -> Result<axum::response::Response, crate::error::Error> {
+    tile(&state, z, x, &y).await
+}
+
+/// Handle the CDN's tile URLs, eg `GET /runs/{run_id}/pmtiles/cache/{z}/{x}/{y}`. Only used when
+/// serving a bundle.
+pub async fn run_handler(
+    axum::extract::State(state): axum::extract::State<std::sync::Arc<crate::State>>,
+    axum::extract::Path((run_id, z, x, y)): axum::extract::Path<(String, u8, u32, String)>,
+) -> Result<axum::response::Response, crate::error::Error> {
+    if state.paths.run_id.as_ref() != Some(&run_id) {
+        return Err(crate::error::Error::not_found(format!(
+            "Run `{run_id}` is not being served"
+        )));
+    }
+
+    tile(&state, z, x, &y).await
+}
+
+/// Get a single tile from the archive.
+async fn tile(
+    state: &crate::State,
+    z: u8,
+    x: u32,
+    y: &str,
+) -> Result<axum::response::Response, crate::error::Error> {
+    let archive = archive(state)?;
+    let y_without_extension = y.split('.').next().unwrap_or_default();
+    let Ok(y_parsed) = y_without_extension.parse::<u32>() else {
+        return Err(crate::error::Error::bad_request(format!(
+            "Invalid tile y coordinate: {y}"
+        )));
+    };
+
+    let Some(tile) = archive.tile(z, x, y_parsed).await? else {
+        // This is normal, there are no tiles for the sea for example.
+        return Ok(axum::http::StatusCode::NO_CONTENT.into_response());
+    };
+
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert(
+        axum::http::header::CONTENT_TYPE,
+        axum::http::HeaderValue::from_static(archive.header.tile_type.content_type()),
+    );
+    if let Some(encoding) = archive.header.tile_compression.content_encoding() {
+        headers.insert(
+            axum::http::header::CONTENT_ENCODING,
+            axum::http::HeaderValue::from_static(encoding),
+        );
+    }
+
+    Ok((headers, tile).into_response())
+}
+
+/// Handle `GET /tiles/metadata`, the archive's JSON metadata.
+pub async fn metadata(
+    axum::extract::State(state): axum::extract::State<std::sync::Arc<crate::State>>,
+) -> Result<axum::response::Response, crate::error::Error> {
+    let metadata = archive(&state)?.metadata().await?;
+    let headers = [(
+        axum::http::header::CONTENT_TYPE,
+        axum::http::HeaderValue::from_static("application/json"),
+    )];
+
+    Ok((headers, metadata).into_response())
+}
+
+/// Get the `.pmtiles` archive, if the server was started with one.
+fn archive(state: &crate::State) -> Result<&crate::pmtiles::Archive, crate::error::Error> {
+    state.pmtiles.as_ref().ok_or_else(|| {
+        crate::error::Error::not_found("No `.pmtiles` archive is being served".to_owned())
+    })
+}
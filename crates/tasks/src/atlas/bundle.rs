@@ -0,0 +1,259 @@
+//! Collect all the outputs of a run into a single directory that can be served offline.
+//!
+//! The bundle has the same layout as the CDN, so the website can point at a local server instead
+//! of the CDN and everything still works:
+//!
+//! ```text
+//! manifest.json
+//! tiles.csv
+//! runs/{run_id}/pmtiles/world.pmtiles
+//! runs/{run_id}/longest_lines_grided.bin
+//! runs/{run_id}/longest_lines_cogs/index.txt
+//! runs/{run_id}/longest_lines_cogs/*.tiff
+//! ```
+//!
+//! A built copy of the website can also be added to the root of the bundle.
+
+use color_eyre::{Result, eyre::ContextCompat as _};
+
+/// The name of the manifest file at the root of every bundle.
+pub const MANIFEST_FILENAME: &str = "manifest.json";
+
+/// The version of the manifest format.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// The world heatmap archive, relative to a run's directory.
+pub const PMTILES_PATH: &str = "pmtiles/world.pmtiles";
+
+/// The longest lines COGs and their index, relative to a run's directory.
+pub const LONGEST_LINES_COGS: &str = "longest_lines_cogs";
+
+/// The master tiles list, relative to the bundle root.
+pub const TILES_FILENAME: &str = "tiles.csv";
+
+/// A single file in the bundle.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BundledFile {
+    /// The path of the file relative to the bundle root.
+    pub path: String,
+    /// The size of the file in bytes.
+    pub bytes: u64,
+}
+
+/// Describes the contents of a bundle.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Manifest {
+    /// The version of the manifest format.
+    pub version: u32,
+    /// The ID of the run that the bundle was made from.
+    pub run_id: String,
+    /// When the bundle was made, in seconds since the Unix epoch.
+    pub created_at: u64,
+    /// Every file in the bundle, apart from the manifest itself.
+    pub files: Vec<BundledFile>,
+}
+
+impl Manifest {
+    /// Load the manifest of a bundle.
+    pub fn load(bundle: &std::path::Path) -> Result<Self> {
+        let path = bundle.join(MANIFEST_FILENAME);
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|error| color_eyre::eyre::eyre!("Couldn't read {path:?}: {error}"))?;
+        let manifest: Self = serde_json::from_str(&contents)?;
+        if manifest.version != MANIFEST_VERSION {
+            color_eyre::eyre::bail!(
+                "Unsupported bundle manifest version {}, expected {MANIFEST_VERSION}",
+                manifest.version
+            );
+        }
+
+        Ok(manifest)
+    }
+
+    /// The directory of the run's assets, relative to the bundle root.
+    pub fn run_directory(&self) -> std::path::PathBuf {
+        run_directory(&self.run_id)
+    }
+}
+
+/// The directory of a run's assets, relative to the bundle root. The same as on the CDN.
+fn run_directory(run_id: &str) -> std::path::PathBuf {
+    std::path::Path::new("runs").join(run_id)
+}
+
+/// Entrypoint.
+pub fn run(config: &crate::config::Bundle) -> Result<()> {
+    if config.run_id.contains(['/', '\\']) || config.run_id.starts_with('.') {
+        color_eyre::eyre::bail!("Invalid run ID: {}", config.run_id);
+    }
+
+    let run = run_directory(&config.run_id);
+    let mut bundler = Bundler {
+        root: config.output.clone(),
+        files: Vec::new(),
+    };
+
+    bundler.add(&config.pmtiles, &run.join(PMTILES_PATH))?;
+    bundler.add(
+        &config.grided,
+        &run.join(crate::atlas::longest_lines::overview::LONGEST_LINES_GRIDED_FILENAME),
+    )?;
+    bundler.add(&config.tiles, std::path::Path::new(TILES_FILENAME))?;
+
+    let cogs = run.join(LONGEST_LINES_COGS);
+    bundler.add(
+        &config
+            .longest_lines_cogs
+            .join(crate::atlas::longest_lines::lookup::INDEX_FILENAME),
+        &cogs.join(crate::atlas::longest_lines::lookup::INDEX_FILENAME),
+    )?;
+    let mut tiffs = Vec::new();
+    for entry in std::fs::read_dir(&config.longest_lines_cogs)? {
+        let path = entry?.path();
+        if path.is_file()
+            && path
+                .extension()
+                .is_some_and(|extension| extension == "tiff")
+        {
+            tiffs.push(path);
+        }
+    }
+    tiffs.sort();
+    tracing::info!("Bundling {} longest lines COGs", tiffs.len());
+    for tiff in tiffs {
+        let filename = tiff.file_name().context("COG without a filename")?;
+        bundler.add(&tiff, &cogs.join(filename))?;
+    }
+
+    if let Some(website) = &config.website {
+        bundler.add_directory(website, std::path::Path::new(""))?;
+    }
+
+    let manifest = Manifest {
+        version: MANIFEST_VERSION,
+        run_id: config.run_id.clone(),
+        created_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs(),
+        files: bundler.files,
+    };
+    let manifest_path = config.output.join(MANIFEST_FILENAME);
+    std::fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)?;
+    tracing::info!(
+        "Bundled {} files for run `{}` into {:?}",
+        manifest.files.len(),
+        manifest.run_id,
+        config.output
+    );
+
+    Ok(())
+}
+
+/// Copies files into the bundle and keeps track of them for the manifest.
+struct Bundler {
+    /// The root of the bundle.
+    root: std::path::PathBuf,
+    /// All the files added so far.
+    files: Vec<BundledFile>,
+}
+
+impl Bundler {
+    /// Add a single file to the bundle. Files are hard linked where possible because COGs and
+    /// `PMTiles` can be very big.
+    fn add(&mut self, source: &std::path::Path, relative: &std::path::Path) -> Result<()> {
+        if !source.is_file() {
+            color_eyre::eyre::bail!("Missing file for bundle: {source:?}");
+        }
+
+        let destination = self.root.join(relative);
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        if destination.exists() {
+            std::fs::remove_file(&destination)?;
+        }
+        if let Err(error) = std::fs::hard_link(source, &destination) {
+            tracing::debug!("Couldn't hard link {source:?}, copying instead: {error}");
+            std::fs::copy(source, &destination)?;
+        }
+
+        tracing::debug!("Bundled {source:?} to {destination:?}");
+        self.files.push(BundledFile {
+            path: relative.display().to_string(),
+            bytes: destination.metadata()?.len(),
+        });
+
+        Ok(())
+    }
+
+    /// Recursively add all the files in a directory to the bundle.
+    fn add_directory(
+        &mut self,
+        source: &std::path::Path,
+        relative: &std::path::Path,
+    ) -> Result<()> {
+        for entry in std::fs::read_dir(source)? {
+            let path = entry?.path();
+            let name = path.file_name().context("Directory entry without a name")?;
+            if path.is_dir() {
+                self.add_directory(&path, &relative.join(name))?;
+            } else {
+                self.add(&path, &relative.join(name))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bundle_and_load_manifest() {
+        let sources = tempfile::tempdir().unwrap();
+        let cogs = sources.path().join("longest_lines");
+        std::fs::create_dir_all(&cogs).unwrap();
+        std::fs::write(cogs.join("index.txt"), "1_2.tiff 100").unwrap();
+        std::fs::write(cogs.join("1_2.tiff"), "cog").unwrap();
+        std::fs::write(cogs.join("notes.md"), "ignored").unwrap();
+        std::fs::write(sources.path().join("world.pmtiles"), "pmtiles").unwrap();
+        std::fs::write(sources.path().join("grided.bin"), "grided").unwrap();
+        std::fs::write(sources.path().join("tiles.csv"), "tiles").unwrap();
+
+        let output = tempfile::tempdir().unwrap();
+        run(&crate::config::Bundle {
+            run_id: "0.1".to_owned(),
+            output: output.path().to_path_buf(),
+            pmtiles: sources.path().join("world.pmtiles"),
+            longest_lines_cogs: cogs,
+            grided: sources.path().join("grided.bin"),
+            tiles: sources.path().join("tiles.csv"),
+            website: None,
+        })
+        .unwrap();
+
+        let manifest = Manifest::load(output.path()).unwrap();
+        assert_eq!(manifest.run_id, "0.1");
+        assert_eq!(manifest.files.len(), 5);
+        assert!(
+            output
+                .path()
+                .join("runs/0.1/longest_lines_cogs/1_2.tiff")
+                .is_file()
+        );
+        assert!(
+            output
+                .path()
+                .join("runs/0.1/longest_lines_grided.bin")
+                .is_file()
+        );
+        assert!(
+            output
+                .path()
+                .join("runs/0.1/pmtiles/world.pmtiles")
+                .is_file()
+        );
+    }
+}
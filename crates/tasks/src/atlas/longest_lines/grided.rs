@@ -26,3 +26,14 @@ pub async fn write(path: std::path::PathBuf, world: &[Grided]) -> Result<()> {
 
     Ok(())
 }
+
+/// Read the grided longest lines of sight that were saved by `write()`.
+pub async fn read(path: &std::path::Path) -> Result<Vec<Grided>> {
+    tracing::debug!("Reading `{path:?}`");
+    let bytes = tokio::fs::read(path).await?;
+    let grided: &[Grided] = bytemuck::try_cast_slice(&bytes).map_err(|error| {
+        color_eyre::eyre::eyre!("Error parsing grided longest lines ({path:?}): {error:?}")
+    })?;
+
+    Ok(grided.to_vec())
+}
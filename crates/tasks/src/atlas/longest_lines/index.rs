@@ -3,6 +3,8 @@
 //! It is a text file that contains a simple list of all the `.tiff` COG files and their
 //! widths in meters. This allows the web UI to easily decide whicg COG to use to look
 //! for longest lines of sight.
+//!
+//! Tiles whose outputs were reused from a previous run, see `reuse.rs`, are in the index too.
 
 use color_eyre::Result;
 
@@ -12,25 +14,10 @@ pub async fn compile() -> Result<()> {
         color_eyre::eyre::bail!("Can't save longest lines when there's no current run config.");
     };
 
-    let tiles = crate::atlas::db::get_completed_tiles().await?;
-    let default_directory = std::path::Path::new(crate::atlas::tile_job::WORKING_DIRECTORY)
-        .join(crate::atlas::tile_job::LONGEST_LINES_DIRECTORY);
-
-    let index_path = config
-        .clone()
-        .longest_lines_cogs
-        .unwrap_or(default_directory)
-        .join("index.txt");
-
-    let mut index = Vec::new();
-    for tile in tiles {
-        let filename = tile.cog_filename();
-        let line = format!(
-            "{filename} {}",
-            tile.width * crate::atlas::tile_job::DEM_SCALE
-        );
-        index.push(line);
-    }
+    let completed = crate::atlas::db::get_completed_tiles().await?;
+    let reused = crate::atlas::reuse::reused_tiles(&config)?;
+    let index_path = directory(&config).join("index.txt");
+    let index = lines(&completed, &reused);
 
     tracing::info!(
         "Saving {} longest line COG summaries to: {:?}",
@@ -51,3 +38,67 @@ pub async fn compile() -> Result<()> {
 
     Ok(())
 }
+
+/// The directory of the longest lines COGs and their index.
+pub fn directory(config: &crate::config::Atlas) -> std::path::PathBuf {
+    config.longest_lines_cogs.clone().unwrap_or_else(|| {
+        std::path::Path::new(crate::atlas::tile_job::WORKING_DIRECTORY)
+            .join(crate::atlas::tile_job::LONGEST_LINES_DIRECTORY)
+    })
+}
+
+/// The lines of the index, for both the tiles completed in this run and the reused ones.
+fn lines(completed: &[crate::tile::Tile], reused: &[crate::atlas::reuse::Reused]) -> Vec<String> {
+    let reused_tiles = reused
+        .iter()
+        .map(|tile| tile.new)
+        .filter(|tile| !completed.contains(tile));
+
+    completed
+        .iter()
+        .copied()
+        .chain(reused_tiles)
+        .map(|tile| {
+            format!(
+                "{} {}",
+                tile.cog_filename(),
+                tile.width * crate::atlas::tile_job::DEM_SCALE
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tile(longitude: f64) -> crate::tile::Tile {
+        crate::tile::Tile {
+            centre: crate::projector::LonLatCoord(geo::coord! { x: longitude, y: 45.0f64 }),
+            width: 1000.0,
+        }
+    }
+
+    #[test]
+    fn unchanged_tiles_are_still_indexed() {
+        let old = [tile(0.0), tile(1.0)];
+        let new = [tile(0.000_001), tile(2.0)];
+        let diff = crate::tiles::diff::Diff::new(
+            &old,
+            &new,
+            crate::tiles::diff::DEFAULT_TOLERANCE,
+            crate::projector::Earth::Wgs84,
+        );
+        let reused = crate::atlas::reuse::unchanged(&diff);
+
+        // Only the added tile was processed in this run.
+        let index = lines(&[tile(2.0)], &reused);
+        assert_eq!(
+            index,
+            vec![
+                "2_45.tiff 100000".to_owned(),
+                "0.000001_45.tiff 100000".to_owned()
+            ]
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! Find the longest line of sight at a single coordinate.
+//!
+//! This is the same lookup that the website does in `getLongestLine.ts`. The longest lines COGs
+//! index tells us which COGs cover the coordinate, then we search a small window of points around
+//! the coordinate in each of those COGs.
+
+use color_eyre::Result;
+
+use crate::projector::LonLatCoord;
+
+/// The name of the index file that lives alongside all the longest lines COGs.
+pub const INDEX_FILENAME: &str = "index.txt";
+
+/// How many points either side of the coordinate to search in. We need to find the longest line in
+/// a 5x5 grid to get around precision loss.
+const SEARCH_AROUND: usize = 2;
+
+/// Inherited from the TVS algorithm. It's to counter unfavourable floating point rounding.
+const ANGLE_SHIFT: f64 = 0.0001;
+
+/// A single COG listed in the longest lines index.
+#[derive(Debug, Clone)]
+pub struct IndexedCog {
+    /// The filename of the COG, relative to the index file.
+    pub filename: String,
+    /// The Packer tile that the COG was calculated from.
+    pub tile: crate::tile::Tile,
+}
+
+/// A longest line of sight from a single point.
+#[derive(Debug, Clone, Copy)]
+pub struct LongestLine {
+    /// The distance of the line of sight in meters.
+    pub distance: u32,
+    /// The angle of the line of sight in degrees, anti-clockwise from East.
+    pub angle: u16,
+    /// The point of view.
+    pub from: LonLatCoord,
+    /// The furthest visible point.
+    pub to: LonLatCoord,
+}
+
+/// The parsed index of all the longest lines COGs.
+pub struct Index {
+    /// The directory containing the index and all the COGs.
+    directory: std::path::PathBuf,
+    /// Every COG in the index.
+    cogs: Vec<IndexedCog>,
+}
+
+impl Index {
+    /// Load the index from a longest lines COGs directory.
+    pub fn load(directory: &std::path::Path) -> Result<Self> {
+        let index_path = directory.join(INDEX_FILENAME);
+        tracing::debug!("Loading longest lines index: {index_path:?}");
+        let contents = std::fs::read_to_string(&index_path)?;
+
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            cogs: Self::parse(&contents)?,
+        })
+    }
+
+    /// Parse the contents of the index file. Each line is a COG filename and its width, see
+    /// `index::compile()`.
+    fn parse(contents: &str) -> Result<Vec<IndexedCog>> {
+        let mut cogs = Vec::new();
+        for line in contents.lines() {
+            let mut parts = line.split(' ');
+            let (Some(filename), Some(width), None) = (parts.next(), parts.next(), parts.next())
+            else {
+                continue;
+            };
+
+            let centre =
+                crate::tile::Tile::centre_from_cog_filename(std::path::Path::new(filename))?;
+            let width = width.parse::<f32>()? / crate::atlas::tile_job::DEM_SCALE;
+            cogs.push(IndexedCog {
+                filename: filename.to_owned(),
+                tile: crate::tile::Tile { centre, width },
+            });
+        }
+
+        Ok(cogs)
+    }
+
+    /// All the COGs in the index.
+    pub fn cogs(&self) -> &[IndexedCog] {
+        &self.cogs
+    }
+
+    /// Find all the COGs whose tiles contain the given coordinate. The COGs are rasters in a WGS84
+    /// AEQD, so distances are measured on the ellipsoid, whatever earth the tiles were packed on.
+    pub fn covering(&self, coordinate: LonLatCoord) -> Result<Vec<&IndexedCog>> {
+        let mut covering = Vec::new();
+        for cog in &self.cogs {
+            let distance = cog
+                .tile
+                .distance_from(coordinate, crate::projector::Earth::Wgs84)?;
+            if distance < f64::from(cog.tile.width / 2.0) {
+                covering.push(cog);
+            }
+        }
+
+        Ok(covering)
+    }
+
+    /// Find the longest line of sight at the given coordinate. Different COGs can contain the same
+    /// point, so we choose the longest line out of all of them.
+    pub fn longest_line(&self, coordinate: LonLatCoord) -> Result<Option<LongestLine>> {
+        let cogs = self.covering(coordinate)?;
+        if cogs.is_empty() {
+            tracing::debug!("{coordinate:?} is not within the radius of any longest lines COGs");
+            return Ok(None);
+        }
+
+        let mut longest: Option<LongestLine> = None;
+        for cog in cogs {
+            let Some(candidate) = self.search_cog(cog, coordinate)? else {
+                continue;
+            };
+
+            if longest.is_none_or(|current| candidate.distance > current.distance) {
+                longest = Some(candidate);
+            }
+        }
+
+        Ok(longest)
+    }
+
+    /// Search for the longest line in a small window around the coordinate in a single COG.
+    #[expect(
+        clippy::as_conversions,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss,
+        clippy::cast_possible_wrap,
+        reason = "Raster coordinates are always small, positive and checked against the raster size"
+    )]
+    fn search_cog(&self, cog: &IndexedCog, coordinate: LonLatCoord) -> Result<Option<LongestLine>> {
+        let path = self.directory.join(&cog.filename);
+        tracing::trace!("Searching for longest line in: {path:?}");
+
+        let dataset = gdal::Dataset::open(&path)?;
+        let transform = dataset.geo_transform()?;
+        let band = dataset.rasterband(1)?;
+        let (width, height) = band.size();
+
+        let projector = crate::projector::Convert {
+            base: cog.tile.centre,
+        };
+        let metric = projector.to_meters(coordinate)?;
+        let column = ((metric.x - transform[0]) / transform[1]).floor();
+        let row = ((metric.y - transform[3]) / transform[5]).floor();
+        if column < 0.0 || row < 0.0 || column >= width as f64 || row >= height as f64 {
+            tracing::debug!("{coordinate:?} is out of bounds of {path:?}");
+            return Ok(None);
+        }
+
+        let left = (column as usize).saturating_sub(SEARCH_AROUND);
+        let top = (row as usize).saturating_sub(SEARCH_AROUND);
+        let right = (column as usize + SEARCH_AROUND + 1).min(width);
+        let bottom = (row as usize + SEARCH_AROUND + 1).min(height);
+        let window_size = (right - left, bottom - top);
+
+        let buffer: gdal::raster::Buffer<f32> = band.read_as(
+            (left as isize, top as isize),
+            window_size,
+            window_size,
+            None,
+        )?;
+
+        let mut longest: Option<(usize, super::packed::LineOfSight)> = None;
+        for (index, &value) in buffer.data().iter().enumerate() {
+            let line = super::packed::LineOfSight(value);
+            if longest.is_none_or(|(_, current)| line.distance() > current.distance()) {
+                longest = Some((index, line));
+            }
+        }
+
+        let Some((index, line)) = longest else {
+            return Ok(None);
+        };
+        let angle = line.angle()?;
+        if line.distance() == 0 && angle == 0 {
+            tracing::debug!("No longest line found around {coordinate:?} in {path:?}");
+            return Ok(None);
+        }
+
+        let x = (left + index.rem_euclid(window_size.0)) as f64;
+        let y = (top + index.div_euclid(window_size.0)) as f64;
+        let from = projector.to_degrees(geo::coord! {
+            x: (x + 0.5).mul_add(transform[1], transform[0]),
+            y: (y + 0.5).mul_add(transform[5], transform[3]),
+        })?;
+
+        Ok(Some(LongestLine {
+            distance: line.distance(),
+            angle,
+            from,
+            to: Self::line_end(from, line.distance(), angle)?,
+        }))
+    }
+
+    /// Find where a line of sight ends. Lines of sight are straight in the AEQD projection anchored
+    /// to their point of view.
+    fn line_end(from: LonLatCoord, distance: u32, angle: u16) -> Result<LonLatCoord> {
+        let radians = (f64::from(angle) + ANGLE_SHIFT).to_radians();
+        let distance_f64 = f64::from(distance);
+        let projector = crate::projector::Convert { base: from };
+
+        projector.to_degrees(geo::coord! {
+            x: distance_f64 * radians.cos(),
+            y: distance_f64 * radians.sin(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_index() {
+        let contents = "-2.5_51.4.tiff 36661018.75\n\n10_-20.tiff 3600000\n";
+        let cogs = Index::parse(contents).unwrap();
+        assert_eq!(cogs.len(), 2);
+        assert_eq!(cogs[0].filename, "-2.5_51.4.tiff");
+        assert_eq!(
+            cogs[0].tile.centre,
+            LonLatCoord(geo::coord! { x: -2.5, y: 51.4 })
+        );
+        assert!((cogs[0].tile.width - 366_610.2).abs() < 0.1);
+        assert!((cogs[1].tile.width - 36_000.0).abs() < 0.1);
+    }
+}
@@ -7,7 +7,10 @@
 
 use std::sync::Arc;
 
-use color_eyre::{Result, eyre::ContextCompat as _};
+use color_eyre::Result;
+
+/// The H3 resolution of the grid. Each cell is around 65kmx65km.
+pub const H3_RESOLUTION: h3o::Resolution = h3o::Resolution::Four;
 
 /// A longest in a single H3 grid.
 #[derive(Debug, Clone, Copy)]
@@ -43,7 +46,7 @@ struct Tile {
 impl Tile {
     /// Entrypoint.
     fn process(path: &std::path::Path) -> Result<HashMap> {
-        let centre = Self::parse_centre_coords(path)?;
+        let centre = crate::tile::Tile::centre_from_cog_filename(path)?;
         let buffer = Self::load(path)?;
         let width = buffer.width();
         #[expect(
@@ -64,32 +67,6 @@ impl Tile {
         Ok(local)
     }
 
-    /// Get the lon/lat coordinates representing the centre of the longest lines COG.
-    fn parse_centre_coords(path: &std::path::Path) -> Result<crate::projector::LonLatCoord> {
-        let filestem = path
-            .file_stem()
-            .context("Couldn't get file stem of longest lines tile")?
-            .display()
-            .to_string();
-        let parts: Vec<&str> = filestem.split('_').collect();
-
-        #[expect(
-            clippy::get_first,
-            reason = "Better to have the consistent access method."
-        )]
-        let lon: f64 = parts
-            .get(0)
-            .context("Longitude not present in path parts")?
-            .parse()?;
-        let lat: f64 = parts
-            .get(1)
-            .context("Latitude not present in path parts")?
-            .parse()?;
-        let coord = crate::projector::LonLatCoord(geo::coord! {x: lon, y: lat});
-
-        Ok(coord)
-    }
-
     /// Load a longest lines COG file.
     fn load(path: &std::path::Path) -> Result<gdal::raster::Buffer<f32>> {
         tracing::trace!("Loading {path:?}");
@@ -112,7 +89,7 @@ impl Tile {
         for (index, &value) in self.buffer.data().iter().enumerate() {
             let coord = self.index_to_coord(index);
             let lonlat = self.coord_to_lonlat(coord)?;
-            let cell = h3o::LatLng::new(lonlat.0.y, lonlat.0.x)?.to_cell(h3o::Resolution::Four);
+            let cell = h3o::LatLng::new(lonlat.0.y, lonlat.0.x)?.to_cell(H3_RESOLUTION);
 
             let current = super::packed::LineOfSight(value);
             let longest_line = LongestLine {
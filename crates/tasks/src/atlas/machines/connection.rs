@@ -141,6 +141,19 @@ impl Connection {
         self.command(command).await
     }
 
+    /// Copy a file from one place in our S3 bucket to another.
+    pub async fn copy_s3_file(&self, from: &str, to: &str) -> Result<()> {
+        tracing::info!("Copying file {} to {} on {:?}", from, to, self.provider);
+
+        let command = Command {
+            executable: "./ctl.sh".into(),
+            args: vec!["s3", "cp", &from, &to],
+            ..Default::default()
+        };
+
+        self.command(command).await
+    }
+
     /// Sync a file from our S3 bucket.
     pub async fn sync_file_from_s3(&self, from: &str, to: &str) -> Result<()> {
         tracing::info!("Syncing file {} from {} on {:?}", from, to, self.provider);
@@ -0,0 +1,96 @@
+//! Reuse the outputs of a previous run for the tiles that haven't changed since it.
+//!
+//! After a repack most tiles are the same as they were, so there's no need to compute them again.
+//! With `--only-changed-since`, only the added and resized tiles get jobs. The unchanged tiles have
+//! their outputs copied from the previous run into this one instead. Tiles count as unchanged when
+//! their centres are within a tolerance, so the outputs are copied to the new tile's canonical
+//! filename, which can be slightly different to the old one's.
+
+use color_eyre::Result;
+
+/// A tile whose outputs from the previous run are still valid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reused {
+    /// The tile in the old master tiles, that the outputs were made for.
+    pub old: crate::tile::Tile,
+    /// The same tile in the current master tiles.
+    pub new: crate::tile::Tile,
+}
+
+/// The differences since the old master tiles, when only the changed tiles are processed.
+pub fn diff(config: &crate::config::Atlas) -> Result<Option<crate::tiles::diff::Diff>> {
+    let Some(old_master) = &config.only_changed_since else {
+        return Ok(None);
+    };
+
+    let master = crate::tiles::File::load(&config.master)?;
+    let master_tiles: Vec<crate::tile::Tile> =
+        master.tiles.iter().map(|packed| packed.tile).collect();
+    Ok(Some(crate::tiles::diff::Diff::new(
+        &crate::tiles::load(old_master)?,
+        &master_tiles,
+        crate::tiles::diff::DEFAULT_TOLERANCE,
+        master.metadata.earth(),
+    )))
+}
+
+/// The tiles whose outputs are reused from the previous run.
+pub fn reused_tiles(config: &crate::config::Atlas) -> Result<Vec<Reused>> {
+    Ok(diff(config)?
+        .map(|changes| unchanged(&changes))
+        .unwrap_or_default())
+}
+
+/// The unchanged tiles in a diff.
+pub fn unchanged(changes: &crate::tiles::diff::Diff) -> Vec<Reused> {
+    changes
+        .unchanged_tiles()
+        .into_iter()
+        .map(|(old, new)| Reused { old, new })
+        .collect()
+}
+
+/// Copy the outputs of all the reused tiles from the previous run into this run.
+pub async fn copy_outputs(config: &crate::config::Atlas, reused: &[Reused]) -> Result<()> {
+    tracing::info!(
+        "Reusing the outputs of {} unchanged tiles from the previous run",
+        reused.len()
+    );
+
+    if config.is_local_run() {
+        let directory = crate::atlas::longest_lines::index::directory(config);
+        for tile in reused {
+            let from = directory.join(tile.old.cog_filename());
+            let to = directory.join(tile.new.cog_filename());
+            if from != to {
+                std::fs::copy(&from, &to).map_err(|error| {
+                    color_eyre::eyre::eyre!("Couldn't copy {from:?} to {to:?}: {error}")
+                })?;
+            }
+        }
+        return Ok(());
+    }
+
+    let Some(previous_run_id) = &config.previous_run_id else {
+        color_eyre::eyre::bail!(
+            "`--only-changed-since` needs `--previous-run-id` to reuse the outputs of a cloud run"
+        );
+    };
+    let connection = crate::atlas::machines::local::Machine::connection();
+    for tile in reused {
+        for folder in ["raw", "longest_lines_cogs"] {
+            let from = format!(
+                "s3://viewview/runs/{previous_run_id}/{folder}/{}",
+                tile.old.cog_filename()
+            );
+            let to = format!(
+                "s3://viewview/runs/{}/{folder}/{}",
+                config.run_id,
+                tile.new.cog_filename()
+            );
+            connection.copy_s3_file(&from, &to).await?;
+        }
+    }
+
+    Ok(())
+}
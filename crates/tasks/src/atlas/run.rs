@@ -9,7 +9,7 @@
 //! These can and probably should be run manually after a bunch of tiles have been processed.
 //! 4. Create the new `world.pmtile`: `./ctl.sh make_pmtiles latest website/public/world.pmtiles`
 
-use color_eyre::{Result, eyre::ContextCompat as _};
+use color_eyre::Result;
 
 use apalis::prelude::{Task, TaskSink as _};
 use apalis_sqlite::SqlContext;
@@ -25,7 +25,7 @@ impl Atlas {
     /// Instantiate.
     pub fn new(config: &crate::config::Atlas) -> Result<Self> {
         let mut tiles = rstar::RTree::new();
-        let master_tiles = Self::load_master_tiles(&config.master)?;
+        let master_tiles = crate::tiles::load(&config.master)?;
         for master_tile in master_tiles {
             tiles.insert(crate::packer::TileRstar::new(
                 master_tile.centre,
@@ -37,34 +37,6 @@ impl Atlas {
         Ok(atlas)
     }
 
-    /// Load the master tile file created by `Packer`. This is what we iterate through to get all
-    /// the tiles for the whole world.
-    pub fn load_master_tiles(master_path: &std::path::PathBuf) -> Result<Vec<crate::tile::Tile>> {
-        let mut tiles = Vec::new();
-        let master_file = std::fs::read_to_string(master_path)?;
-
-        for line in master_file.lines() {
-            let mut parts = line.split(',');
-            let lon = parts
-                .next()
-                .context("No longitude in tile.csv line")?
-                .parse::<f64>()?;
-            let lat = parts
-                .next()
-                .context("No latitude in tile.csv line")?
-                .parse::<f64>()?;
-            let width = parts
-                .next()
-                .context("No width in tile.csv line")?
-                .parse::<f32>()?;
-            let centre = crate::projector::LonLatCoord(geo::Coord { x: lon, y: lat });
-
-            tiles.push(crate::tile::Tile { centre, width });
-        }
-
-        Ok(tiles)
-    }
-
     /// Process all the tiles.
     pub async fn run_all(config: &crate::config::Atlas) -> Result<()> {
         #[expect(clippy::collapsible_if, reason = "I prefer it like this")]
@@ -94,6 +66,20 @@ impl Atlas {
         let atlas = Self::new(config)?;
         let mut tile_store = super::db::atlas_worker_store().await?;
 
+        let changed_tiles = match super::reuse::diff(config)? {
+            Some(diff) => {
+                tracing::info!("Only adding changed tiles. {}", diff.summary());
+                super::reuse::copy_outputs(config, &super::reuse::unchanged(&diff)).await?;
+                Some(
+                    diff.changed_tiles()
+                        .iter()
+                        .map(crate::tile::Tile::cog_filename)
+                        .collect::<std::collections::HashSet<String>>(),
+                )
+            }
+            None => None,
+        };
+
         tracing::debug!("Adding tile jobs to worker...");
         let start_from = crate::projector::LonLatCoord(config.centre.into());
         let amount_of_tiles_to_add = config.amount.unwrap_or_else(|| atlas.tiles.size());
@@ -102,6 +88,11 @@ impl Atlas {
             .tiles
             .nearest_neighbor_iter(&start_from)
             .filter(|tile| tile.data.width < config.max_tile_width.unwrap_or(f32::MAX))
+            .filter(|tile| {
+                changed_tiles
+                    .as_ref()
+                    .is_none_or(|changed| changed.contains(&tile.data.cog_filename()))
+            })
             .skip(config.skip.unwrap_or(0))
         {
             if crate::atlas::db::is_tile_added(master_tile.data).await? {
@@ -17,7 +17,7 @@ pub struct StitchJob {
 
 /// Entrypoint.
 pub async fn run(config: &crate::config::StitchAll) -> Result<()> {
-    let master_tiles = super::run::Atlas::load_master_tiles(&config.master)?;
+    let master_tiles = crate::tiles::load(&config.master)?;
     let mut stitch_store = crate::atlas::db::worker_store::<StitchJob>(STITCH_ALL_DB_PATH).await?;
 
     for master_tile in master_tiles {
@@ -27,6 +27,7 @@ pub struct TileJob {
     pub tile: crate::tile::Tile,
 }
 
+/// Runs all the steps needed to process a single tile.
 pub struct TileRunner<'mutex> {
     /// `mutex` is a borrowed mutex from the `Worker`. It makes sure that
     /// only a single `TileRunner` is running an L.o.S calculation.
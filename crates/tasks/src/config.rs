@@ -26,12 +26,55 @@ pub enum Commands {
     MaxSubTiles(MaxSubTiles),
     /// Create tiles identifited by the packer.
     Stitch(Stitch),
+    /// Output the elevation profile between two points as JSON.
+    Profile(Profile),
+    /// Output the lon/lat extents of a processed tile, split at the antimeridian.
+    TileExtents(TileExtents),
+
+    #[command(subcommand)]
+    /// Tools for the tiles files made by the packer.
+    Tiles(TilesCommands),
 
     #[command(subcommand)]
     /// Run and manage all the tasks for processing the entire planet.
     Atlas(AtlasCommands),
 }
 
+/// `tiles` subcommands.
+#[derive(clap::Subcommand, Debug)]
+pub enum TilesCommands {
+    /// Compare 2 tiles files and report which tiles were added, removed or resized.
+    Diff(TilesDiff),
+}
+
+/// `cargo run tiles diff` arguments.
+#[derive(clap::Parser, Debug, Clone)]
+pub struct TilesDiff {
+    /// The older tiles.
+    #[arg(value_name = "Path to old tiles")]
+    pub old: std::path::PathBuf,
+
+    /// The newer tiles.
+    #[arg(value_name = "Path to new tiles")]
+    pub new: std::path::PathBuf,
+
+    /// How many meters apart 2 tiles' centres can be and still be the same tile.
+    #[arg(
+        long,
+        value_name = "Meters",
+        default_value_t = crate::tiles::diff::DEFAULT_TOLERANCE
+    )]
+    pub tolerance: f64,
+
+    /// Where to save the changed tiles as `GeoJSON`.
+    #[arg(
+        long,
+        value_name = "Path to GeoJSON",
+        default_value = "output/tiles_diff.geojson"
+    )]
+    pub output: std::path::PathBuf,
+}
+
 /// `atlas` subcommands.
 #[derive(clap::Subcommand, Debug)]
 pub enum AtlasCommands {
@@ -49,11 +92,18 @@ pub enum AtlasCommands {
     CurrentRunConfig(CurrentRunConfig),
     /// Stitch the entire world's `.bt` files and save them to S3.
     StitchAll(StitchAll),
+    /// Collect all of a run's outputs into a single directory that can be served offline.
+    Bundle(Bundle),
 }
 
 /// `cargo run packer` arguments.
 #[derive(clap::Parser, Debug, Clone)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct Packer {
+    #[command(subcommand)]
+    /// Tools for the packer's output. Without one the packer itself is run.
+    pub command: Option<PackerCommands>,
+
     /// Just run for one step
     #[arg(
         long,
@@ -75,11 +125,280 @@ pub struct Packer {
     /// How many window steps to take Useful for debugging.
     #[arg(long, value_name = "Number of steps")]
     pub steps: Option<u32>,
+
+    /// Where to periodically save the state of the run, so that it can be resumed.
+    #[arg(
+        long,
+        value_name = "Checkpoint directory",
+        default_value = "output/packer_checkpoint"
+    )]
+    pub checkpoint: std::path::PathBuf,
+
+    /// How many window steps to take between each checkpoint.
+    #[arg(
+        long,
+        value_name = "Number of steps",
+        default_value_t = 10,
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub checkpoint_every: u32,
+
+    /// Pack this many bands of latitudes in parallel. Defaults to packing the whole globe in one
+    /// go. Packing in bands doesn't save checkpoints, so it can't be resumed, and it can't be
+    /// stopped after a number of steps.
+    #[arg(
+        long,
+        value_name = "Number of bands",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with_all = ["one", "start", "resume", "steps", "checkpoint", "checkpoint_every"]
+    )]
+    pub bands: u32,
+
+    /// Continue a previous run from where its checkpoint was saved.
+    #[arg(long, value_name = "Checkpoint directory", conflicts_with_all = ["one", "start"])]
+    pub resume: Option<std::path::PathBuf>,
+
+    /// Only repack a region of an existing packing. Either a bounding box, eg:
+    /// `west,south,east,north`, or the path to a `GeoJSON` file of polygons.
+    #[arg(
+        long,
+        allow_hyphen_values(true),
+        value_parser = parse_region,
+        value_name = "Bounding box or GeoJSON",
+        requires_all = ["base", "output"],
+        conflicts_with_all = ["one", "start", "resume", "bands"]
+    )]
+    pub region: Option<Region>,
+
+    /// The existing tiles to repack a region of.
+    #[arg(long, value_name = "Path to tiles", requires = "region")]
+    pub base: Option<std::path::PathBuf>,
+
+    /// Where to save the tiles. Defaults to `output/tiles.csv`, apart from when repacking a
+    /// region, which has to say where to save them so that it can't overwrite a whole packing by
+    /// accident.
+    #[arg(long, value_name = "Path to tiles")]
+    pub output: Option<std::path::PathBuf>,
+
+    /// Where to save the differences between the base tiles and the repacked tiles, as `GeoJSON`.
+    /// Defaults to next to the repacked tiles, eg: `output/region_diff.geojson`.
+    #[arg(long, value_name = "Path to GeoJSON", requires = "region")]
+    pub diff_output: Option<std::path::PathBuf>,
+
+    /// How to decide which tiles are cheaper when cleaning up overlapping tiles.
+    #[arg(long, value_enum, value_name = "Cost model", default_value_t)]
+    pub cost_model: crate::packer::cost::Model,
+
+    /// How far lines of sight can reach, which decides how big tiles need to be.
+    #[command(flatten)]
+    pub line_of_sight: LineOfSight,
+
+    /// The shape of the earth for all the tile geometry. It's recorded in the tiles file, so
+    /// everything that loads the tiles remakes their geometry on the same earth.
+    #[arg(long, value_enum, value_name = "Earth model", default_value_t)]
+    pub earth: crate::projector::Earth,
+
+    /// Load a legacy max subtiles file, from before they had a header.
+    #[arg(long)]
+    pub legacy_subtiles: bool,
+}
+
+/// How lines of sight are modelled. Used for sizing tiles and for checking whether a line of sight
+/// is plausible.
+#[derive(clap::Args, Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LineOfSight {
+    /// How much the atmosphere bends lines of sight back down towards the earth. 0 is no
+    /// refraction at all.
+    #[arg(
+        long,
+        value_name = "Refraction coefficient",
+        default_value_t = crate::horizon::DEFAULT_REFRACTION,
+        value_parser = parse_refraction
+    )]
+    pub refraction: f64,
+
+    /// The height of the observer above the ground in meters.
+    #[arg(long, value_name = "Observer height", default_value_t = 0.0)]
+    pub observer_height: f32,
+
+    /// The height of the target above the ground in meters.
+    #[arg(long, value_name = "Target height", default_value_t = 0.0)]
+    pub target_height: f32,
+}
+
+/// A region of the globe.
+#[derive(Debug, Clone)]
+pub enum Region {
+    /// A bounding box of lon/lat degrees. It can't cross the antimeridian, regions that do need a
+    /// `GeoJSON` file with a polygon on each side of it.
+    BoundingBox {
+        /// The most westerly longitude.
+        west: f64,
+        /// The most southerly latitude.
+        south: f64,
+        /// The most easterly longitude.
+        east: f64,
+        /// The most northerly latitude.
+        north: f64,
+    },
+    /// A `GeoJSON` file of polygons.
+    GeoJson(std::path::PathBuf),
+}
+
+/// `packer` subcommands.
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum PackerCommands {
+    /// Check that a tiles file covers every max subtile with a big enough tile.
+    Verify(Verify),
+    /// Estimate how much compute a tiles file needs.
+    Stats(Stats),
+    /// Draw a tiles file as an image or `GeoJSON`.
+    Render(Render),
+}
+
+/// `cargo run packer verify` arguments.
+#[derive(clap::Parser, Debug, Clone)]
+pub struct Verify {
+    /// The max subtiles made by `max-sub-tiles`.
+    #[arg(
+        long,
+        value_name = "Path to max subtiles",
+        default_value = "max_subtiles.bin"
+    )]
+    pub subtiles: std::path::PathBuf,
+
+    /// The tiles to verify.
+    #[arg(long, value_name = "Path to tiles", default_value = "output/tiles.csv")]
+    pub tiles: std::path::PathBuf,
+
+    /// Where to save any subtiles that aren't covered by a big enough tile, as `GeoJSON`.
+    #[arg(
+        long,
+        value_name = "Path to GeoJSON",
+        default_value = "output/violations.geojson"
+    )]
+    pub output: std::path::PathBuf,
+
+    /// How far lines of sight can reach, which decides how big tiles need to be. Only used for
+    /// tiles files that don't record the line of sight they were packed for.
+    #[command(flatten)]
+    pub line_of_sight: LineOfSight,
+
+    /// Load a legacy max subtiles file, from before they had a header.
+    #[arg(long)]
+    pub legacy_subtiles: bool,
+}
+
+/// `cargo run packer stats` arguments.
+#[derive(clap::Parser, Debug, Clone)]
+pub struct Stats {
+    /// The tiles to analyse.
+    #[arg(long, value_name = "Path to tiles", default_value = "output/tiles.csv")]
+    pub tiles: std::path::PathBuf,
+
+    /// How many cores the machines that compute the tiles have.
+    #[arg(long, value_name = "Number of cores", default_value_t = 48)]
+    pub cores: u32,
+
+    /// How much a core costs per hour, in dollars.
+    #[arg(long, value_name = "Dollars", default_value_t = 0.04)]
+    pub cost_per_core_hour: f32,
+}
+
+/// `cargo run packer render` arguments.
+#[derive(clap::Parser, Debug, Clone)]
+pub struct Render {
+    /// The tiles to draw.
+    #[arg(long, value_name = "Path to tiles", default_value = "output/tiles.csv")]
+    pub tiles: std::path::PathBuf,
+
+    /// The max subtiles to draw the elevations from.
+    #[arg(
+        long,
+        value_name = "Path to max subtiles",
+        default_value = "max_subtiles.bin"
+    )]
+    pub subtiles: std::path::PathBuf,
+
+    /// Load a legacy max subtiles file, from before they had a header.
+    #[arg(long)]
+    pub legacy_subtiles: bool,
+
+    /// Where to save the render. Its extension decides the format: `.png`, `.svg` or `.geojson`.
+    #[arg(
+        long,
+        value_name = "Path to render",
+        default_value = "output/tiles.png"
+    )]
+    pub output: std::path::PathBuf,
+
+    /// The map projection of images. `GeoJSON` is always in lon/lat.
+    #[arg(long, value_enum, value_name = "Projection", default_value_t)]
+    pub projection: crate::packer::render::Projection,
+
+    /// The width of images in pixels.
+    #[arg(
+        long,
+        value_name = "Pixels",
+        default_value_t = 4096,
+        value_parser = clap::value_parser!(u32).range(360..=32_768)
+    )]
+    pub width: u32,
 }
 
 /// `cargo run max-sub-tiles` arguments.
 #[derive(clap::Parser, Debug, Clone)]
-pub struct MaxSubTiles;
+pub struct MaxSubTiles {
+    /// The DEMs to make subtiles from. Either a folder of `.hgt`, `.tif` or `.vrt` files, or a
+    /// glob like `"dems/Copernicus_DSM_*.tif"`. They must be in lon/lat coordinates.
+    #[arg(
+        long,
+        value_name = "Folder or glob of DEMs",
+        default_value = "/publicish/dems"
+    )]
+    pub dems: String,
+
+    /// How many subtiles each degree is split into, along both longitude and latitude.
+    #[arg(
+        long,
+        value_name = "Subtiles per degree",
+        default_value_t = 10,
+        value_parser = clap::value_parser!(u32).range(1..=3600)
+    )]
+    pub factor: u32,
+
+    /// Where to save the max subtiles.
+    #[arg(
+        long,
+        value_name = "Path to max subtiles",
+        default_value = "max_subtiles.bin"
+    )]
+    pub output: std::path::PathBuf,
+
+    /// Where to save the statistics of every subtile, like where exactly its highest point is
+    /// and how much of it is void.
+    #[arg(
+        long,
+        value_name = "Path to subtile statistics",
+        default_value = "max_subtile_stats.bin"
+    )]
+    pub stats: std::path::PathBuf,
+
+    /// Also save a PNG map of how much of every subtile is void, with one pixel per subtile. It's
+    /// never more than 10 pixels per degree though, so finer subtiles share pixels.
+    #[arg(long, value_name = "Path to PNG")]
+    pub void_map: Option<std::path::PathBuf>,
+
+    /// How many DEMs to make subtiles for at the same time. Defaults to the number of cores.
+    #[arg(long, value_name = "Thread count", value_parser = clap::value_parser!(u64).range(1..))]
+    pub threads: Option<u64>,
+
+    /// Carry on from an unfinished run, skipping the DEMs already recorded in the output's
+    /// `.index` sidecar file.
+    #[arg(long)]
+    pub resume: bool,
+}
 
 /// `cargo run stitch` arguments.
 #[derive(clap::Parser, Debug, Clone)]
@@ -102,6 +421,56 @@ pub struct Stitch {
     pub width: f32,
 }
 
+/// `cargo run tile-extents` arguments.
+#[derive(clap::Parser, Debug, Clone)]
+pub struct TileExtents {
+    /// A GeoTiff tile made by `ctl.sh prepare_for_cloud`. Its filename is the tile's centre.
+    #[arg(value_name = "Path to tile")]
+    pub tiff: std::path::PathBuf,
+}
+
+/// `cargo run profile` arguments.
+#[derive(clap::Parser, Debug, Clone)]
+pub struct Profile {
+    /// Source of all the DEM files.
+    #[arg(long, value_name = "Path to DEMs folder")]
+    pub dems: std::path::PathBuf,
+
+    /// The lon/lat coord of the observer.
+    #[arg(
+        long,
+        allow_hyphen_values(true),
+        value_parser = parse_coord,
+        value_name = "Observer coordinate")
+    ]
+    pub from: (f64, f64),
+
+    /// The lon/lat coord of the target.
+    #[arg(
+        long,
+        allow_hyphen_values(true),
+        value_parser = parse_coord,
+        value_name = "Target coordinate")
+    ]
+    pub to: (f64, f64),
+
+    /// The refraction and the heights of the observer and target.
+    #[command(flatten)]
+    pub line_of_sight: LineOfSight,
+
+    /// The shape of the earth that the line of sight is drawn over.
+    #[arg(long, value_enum, value_name = "Earth model", default_value_t)]
+    pub earth: crate::projector::Earth,
+
+    /// The distance between samples in meters.
+    #[arg(
+        long,
+        value_name = "Sample spacing",
+        default_value_t = crate::profile::DEFAULT_SPACING
+    )]
+    pub spacing: f64,
+}
+
 /// `cargo run atlas stitch-all` arguments.
 #[derive(clap::Parser, Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct StitchAll {
@@ -134,6 +503,20 @@ pub struct Atlas {
     #[arg(long, value_name = "Path to master tiles list")]
     pub master: std::path::PathBuf,
 
+    /// Only process the tiles that were added or resized since this older master tile list. The
+    /// outputs of all the other tiles are still valid, so they're reused.
+    #[arg(long, value_name = "Path to old master tiles list")]
+    pub only_changed_since: Option<std::path::PathBuf>,
+
+    /// The ID of the run that processed the old master tile list given to
+    /// `--only-changed-since`. The outputs of its unchanged tiles are copied into this run.
+    #[arg(
+        long,
+        value_name = "ID of previous run",
+        requires = "only_changed_since"
+    )]
+    pub previous_run_id: Option<String>,
+
     /// The lon/lat coord from which to start processing
     #[arg(
         long,
@@ -251,6 +634,54 @@ pub struct LongestLinesOverviews {
     pub run_id: String,
 }
 
+/// `cargo run atlas bundle` arguments.
+#[derive(clap::Parser, Debug, Clone)]
+pub struct Bundle {
+    /// The ID of the run to bundle.
+    #[arg(long, value_name = "Versioned ID for run")]
+    pub run_id: String,
+
+    /// Where to create the bundle.
+    #[arg(long, value_name = "Bundle directory")]
+    pub output: std::path::PathBuf,
+
+    /// The world heatmap archive.
+    #[arg(
+        long,
+        value_name = "Path to PMTiles",
+        default_value = "work/world.pmtiles"
+    )]
+    pub pmtiles: std::path::PathBuf,
+
+    /// The longest lines COGs and their `index.txt`.
+    #[arg(
+        long,
+        value_name = "Longest lines COGs directory",
+        default_value = "work/longest_lines"
+    )]
+    pub longest_lines_cogs: std::path::PathBuf,
+
+    /// The longest lines grid made by `atlas longest-lines-overviews`.
+    #[arg(
+        long,
+        value_name = "Path to longest lines grid",
+        default_value = "output/longest_lines_grided.bin"
+    )]
+    pub grided: std::path::PathBuf,
+
+    /// Master tile list produced by the Packer.
+    #[arg(
+        long,
+        value_name = "Path to master tiles list",
+        default_value = "website/public/tiles.csv"
+    )]
+    pub tiles: std::path::PathBuf,
+
+    /// A built copy of the website to add to the root of the bundle.
+    #[arg(long, value_name = "Built website directory")]
+    pub website: Option<std::path::PathBuf>,
+}
+
 /// Get the current run's config.
 #[derive(clap::Parser, Debug, Clone)]
 pub struct CurrentRunConfig;
@@ -274,6 +705,55 @@ fn parse_coord(string: &str) -> Result<(f64, f64)> {
     Ok((coordinates[0], coordinates[1]))
 }
 
+/// Parse a region, either a bounding box or the path to a `GeoJSON` file.
+fn parse_region(string: &str) -> Result<Region> {
+    let Ok(coordinates) = string
+        .split(',')
+        .map(str::parse::<f64>)
+        .collect::<Result<Vec<f64>, _>>()
+    else {
+        return Ok(Region::GeoJson(string.into()));
+    };
+
+    let [west, south, east, north] = coordinates[..] else {
+        color_eyre::eyre::bail!("Bounding box must be 4 numbers: west,south,east,north");
+    };
+    let longitudes = -180.0f64..=180.0f64;
+    let latitudes = -90.0f64..=90.0f64;
+    if !longitudes.contains(&west) || !longitudes.contains(&east) {
+        color_eyre::eyre::bail!("Bounding box's longitudes must be from -180 to 180");
+    }
+    if !latitudes.contains(&south) || !latitudes.contains(&north) {
+        color_eyre::eyre::bail!("Bounding box's latitudes must be from -90 to 90");
+    }
+    if west >= east {
+        color_eyre::eyre::bail!(
+            "Bounding box's West must be West of its East. For a region across the antimeridian, \
+            use a GeoJSON file with a polygon on each side of it."
+        );
+    }
+    if south >= north {
+        color_eyre::eyre::bail!("Bounding box's South must be South of its North");
+    }
+
+    Ok(Region::BoundingBox {
+        west,
+        south,
+        east,
+        north,
+    })
+}
+
+/// Parse a refraction coefficient. At 1 the pretend earth would be infinitely big.
+fn parse_refraction(string: &str) -> Result<f64> {
+    let refraction = string.parse::<f64>()?;
+    if !refraction.is_finite() || refraction >= 1.0f64 {
+        color_eyre::eyre::bail!("Refraction coefficient must be less than 1");
+    }
+
+    Ok(refraction)
+}
+
 /// Get the number of CPUs on the machine.
 pub fn number_of_cpus_on_machine() -> usize {
     std::fs::read_to_string("/proc/cpuinfo")
@@ -288,3 +768,28 @@ pub fn number_of_cpus_on_machine() -> usize {
         .unwrap_or(1)
 }
 
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reject_invalid_bounding_boxes() {
+        let error = |string: &str| parse_region(string).unwrap_err().to_string();
+
+        assert!(matches!(
+            parse_region("86,27,88,29").unwrap(),
+            Region::BoundingBox { .. }
+        ));
+        assert!(error("86,27,188,29").contains("longitudes"));
+        assert!(error("86,-91,88,29").contains("latitudes"));
+        assert!(error("86,27,NaN,29").contains("longitudes"));
+        assert!(error("170,-20,-170,-10").contains("West must be West"));
+        assert!(error("86,27,86,29").contains("West must be West"));
+        assert!(error("86,29,88,27").contains("South must be South"));
+        assert!(matches!(
+            parse_region("region.geojson").unwrap(),
+            Region::GeoJson(_)
+        ));
+    }
+}
+
@@ -0,0 +1,100 @@
+//! How far it's theoretically possible to see.
+//!
+//! Over a perfectly smooth earth the furthest you can see is the horizon, where a ray from the
+//! observer just grazes the surface. But the atmosphere bends rays back down towards the earth, so
+//! in reality you can see a bit further. The usual way to model refraction is to pretend that rays
+//! are straight over a bigger earth, with a radius of `R / (1 - k)`, where `k` is the refraction
+//! coefficient.
+
+/// The standard refraction coefficient used by surveyors. It varies a lot with the weather, it
+/// can even be negative over hot ground, but this is a good average.
+pub const DEFAULT_REFRACTION: f64 = 0.13;
+
+/// The radius of the earth in meters.
+fn earth_radius_meters() -> f64 {
+    f64::from(crate::projector::EARTH_RADIUS) * 1000.0
+}
+
+/// The radius of the pretend earth over which refracted rays are straight.
+pub fn effective_radius(refraction: f64) -> f64 {
+    earth_radius_meters() / (1.0 - refraction)
+}
+
+/// The distance in meters to the horizon from a height above a perfectly smooth earth.
+pub fn distance_to(height: f64, refraction: f64) -> f64 {
+    let above_ground = height.max(0.0);
+    (2.0 * effective_radius(refraction))
+        .mul_add(above_ground, above_ground.powi(2))
+        .sqrt()
+}
+
+/// The longest theoretical line of sight from a point at the given elevation.
+///
+/// It's the distance to the observer's horizon, plus the distance beyond the horizon at which a
+/// target can still just peek over it.
+pub fn longest_line_of_sight(elevation: f64, line_of_sight: &crate::config::LineOfSight) -> f64 {
+    longest_line_between(elevation, 0.0, line_of_sight)
+}
+
+/// Whether a line of sight between an observer and a target could possibly be as long as
+/// `distance`. Any line of sight that goes beyond both their horizons must be an error.
+pub fn is_plausible(
+    observer_elevation: f64,
+    target_elevation: f64,
+    distance: f64,
+    line_of_sight: &crate::config::LineOfSight,
+) -> bool {
+    distance <= longest_line_between(observer_elevation, target_elevation, line_of_sight)
+}
+
+/// The longest possible line of sight between an observer and a target at the given elevations.
+fn longest_line_between(
+    observer_elevation: f64,
+    target_elevation: f64,
+    line_of_sight: &crate::config::LineOfSight,
+) -> f64 {
+    let observer = observer_elevation + f64::from(line_of_sight.observer_height);
+    let target = target_elevation + f64::from(line_of_sight.target_height);
+
+    distance_to(observer, line_of_sight.refraction) + distance_to(target, line_of_sight.refraction)
+}
+
+/// How far a point at the given distance drops below the observer's horizontal plane because of
+/// the curvature of the earth, less how much refraction lifts it back up.
+pub fn curvature_drop(distance: f64, refraction: f64) -> f64 {
+    distance.powi(2) / (2.0 * effective_radius(refraction))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn curvature() {
+        // The classic rule of thumb is that the earth drops about 8 inches per mile squared.
+        assert!((curvature_drop(1_609.34, 0.0) - 0.203f64).abs() < 0.001f64);
+        assert!((curvature_drop(100_000.0, 0.0) - 784.8f64).abs() < 0.1f64);
+        assert!((curvature_drop(100_000.0, DEFAULT_REFRACTION) - 682.8f64).abs() < 0.1f64);
+    }
+
+    #[test]
+    fn refraction_sees_further() {
+        let geometric = crate::config::LineOfSight {
+            refraction: 0.0,
+            observer_height: 0.0,
+            target_height: 0.0,
+        };
+        let refracted = crate::config::LineOfSight {
+            refraction: DEFAULT_REFRACTION,
+            ..geometric
+        };
+
+        let everest = longest_line_of_sight(8848.0, &geometric);
+        assert!((everest - 335_886.0f64).abs() < 1.0f64);
+        let everest_refracted = longest_line_of_sight(8848.0, &refracted);
+        assert!((everest_refracted - 360_091.5f64).abs() < 1.0f64);
+
+        assert!(is_plausible(8848.0, 0.0, 350_000.0, &refracted));
+        assert!(!is_plausible(8848.0, 0.0, 350_000.0, &geometric));
+    }
+}
@@ -0,0 +1,59 @@
+//! All the supporting code used to find and display the longest line of sight on the planet.
+//!
+//! This is mostly used by the `tasks` CLI, but it's also a library so that the `server` can share
+//! things like the longest lines lookups.
+#![expect(
+    clippy::panic_in_result_fn,
+    reason = "This is just code for short tasks, so panicking is better"
+)]
+#![cfg_attr(
+    test,
+    expect(
+        clippy::indexing_slicing,
+        clippy::as_conversions,
+        clippy::unreadable_literal,
+        reason = "Tests aren't so strict"
+    )
+)]
+
+/// The code for processing the entire world.
+pub mod atlas {
+    pub mod bundle;
+    pub mod daemon;
+    pub mod db;
+    pub mod reuse;
+    pub mod run;
+    pub mod stitch_all;
+    pub mod tile_job;
+
+    /// All the code for handling longest lines.
+    pub mod longest_lines {
+        pub mod grided;
+        pub mod index;
+        pub mod lookup;
+        pub mod overview;
+        pub mod packed;
+    }
+    /// Providers of compute resources.
+    pub mod machines {
+        pub mod cli;
+        pub mod connection;
+        pub mod digital_ocean;
+        pub mod google_cloud;
+        pub mod local;
+        pub mod machine;
+        pub mod new_machine_job;
+        pub mod vultr;
+        pub mod worker;
+    }
+}
+
+pub mod config;
+pub mod horizon;
+pub mod max_subtile;
+pub mod packer;
+pub mod profile;
+pub mod projector;
+pub mod stitch;
+pub mod tile;
+pub mod tiles;
@@ -1,59 +1,11 @@
 //! Entrypoint
-#![expect(
-    clippy::panic_in_result_fn,
-    reason = "This is just code for short tasks, so panicking is better"
-)]
-#![cfg_attr(
-    test,
-    expect(
-        clippy::indexing_slicing,
-        clippy::as_conversions,
-        clippy::unreadable_literal,
-        reason = "Tests aren't so strict"
-    )
-)]
-
-/// The code for processing the entire world.
-mod atlas {
-    pub mod daemon;
-    pub mod db;
-    pub mod run;
-    pub mod stitch_all;
-    pub mod tile_job;
-
-    /// All the code for handling longest lines.
-    pub mod longest_lines {
-        pub mod grided;
-        pub mod index;
-        pub mod overview;
-        pub mod packed;
-    }
-    /// Providers of compute resources.
-    pub mod machines {
-        pub mod cli;
-        pub mod connection;
-        pub mod digital_ocean;
-        pub mod google_cloud;
-        pub mod local;
-        pub mod machine;
-        pub mod new_machine_job;
-        pub mod vultr;
-        pub mod worker;
-    }
-}
-
-mod config;
-mod max_subtile;
-mod packer;
-mod projector;
-mod stitch;
-mod tile;
-
 use clap::Parser as _;
 use color_eyre::Result;
 use tracing_subscriber::{Layer as _, layer::SubscriberExt as _, util::SubscriberInitExt as _};
 
-use crate::projector::LonLatCoord;
+use tasks::{
+    atlas, config, max_subtile, packer, profile, projector::LonLatCoord, stitch, tile, tiles,
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -65,28 +17,50 @@ async fn main() -> Result<()> {
         std::env::set_var("GDAL_NUM_THREADS", "ALL_CPUS");
     };
 
-    let config = crate::config::Config::parse();
+    let config = config::Config::parse();
     tracing::info!("Initialising with config: {config:?}",);
 
     match &config.command {
-        config::Commands::Packer(packer_config) => {
-            let mut packer = packer::Packer::new(packer_config.clone())?;
-            match packer_config.one {
-                Some(coordinate) => packer.run_one(LonLatCoord(geo::coord! {
-                    x: coordinate.0,
-                    y: coordinate.1
-                }))?,
-                None => packer.run_all()?,
+        config::Commands::Packer(packer_config) => match &packer_config.command {
+            Some(config::PackerCommands::Verify(verify_config)) => {
+                packer::verify::run(verify_config)?;
+            }
+            Some(config::PackerCommands::Stats(stats_config)) => {
+                packer::stats::run(stats_config)?;
             }
+            Some(config::PackerCommands::Render(render_config)) => {
+                packer::render::run(render_config)?;
+            }
+            None => {
+                let mut packer = packer::Packer::new(packer_config.clone())?;
+                match packer_config.one {
+                    Some(coordinate) => packer.run_one(LonLatCoord(geo::coord! {
+                        x: coordinate.0,
+                        y: coordinate.1
+                    }))?,
+                    None => packer.run_all()?,
+                }
+            }
+        },
+        config::Commands::MaxSubTiles(max_subtiles_config) => {
+            max_subtile::run(max_subtiles_config)?
         }
-        config::Commands::MaxSubTiles(_) => max_subtile::run()?,
         config::Commands::Stitch(stitch_config) => {
             stitch::make_tile(
-                &crate::atlas::machines::local::Machine::connection().into(),
+                &atlas::machines::local::Machine::connection().into(),
                 stitch_config,
             )
             .await?;
         }
+        config::Commands::Profile(profile_config) => {
+            profile::run(profile_config).await?;
+        }
+        config::Commands::TileExtents(extents_config) => {
+            tile::print_extents(extents_config)?;
+        }
+        config::Commands::Tiles(tiles_config) => match tiles_config {
+            config::TilesCommands::Diff(diff_config) => tiles::diff::run(diff_config)?,
+        },
         config::Commands::Atlas(atlas_config) => match atlas_config {
             config::AtlasCommands::Worker(worker_config) => {
                 atlas::daemon::start_all(worker_config, broadcaster).await?;
@@ -109,6 +83,9 @@ async fn main() -> Result<()> {
             config::AtlasCommands::StitchAll(stitch_all_config) => {
                 atlas::stitch_all::run(stitch_all_config).await?;
             }
+            config::AtlasCommands::Bundle(bundle_config) => {
+                atlas::bundle::run(bundle_config)?;
+            }
         },
     }
 
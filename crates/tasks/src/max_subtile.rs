@@ -1,21 +1,42 @@
 //! These subtiles are a kind of acceleration structure for being able to more quickly find the
 //! highest points on the planet.
 //!
-//! We take a normal DEM tile, split it into a certain grid, say 10x10, and find the maximum
-//! elevation in each tile of that 10x10 grid. We then record the lon/lat centre of each grid tile.
+//! We split the world into a grid of subtiles, say 10x10 for every degree, and find the maximum
+//! elevation in each of them. We then record the lon/lat centre of each subtile.
 //!
-//! This should be run on a large number of DEM tiles, typically in fact the whole world.
-
-use std::io::Write as _;
+//! This should be run on a large number of DEM tiles, typically in fact the whole world. Any
+//! raster that GDAL can read works, like SRTM `.hgt`s, Copernicus GLO-30 `GeoTIFF`s or a VRT of
+//! them, as long as it's in lon/lat coordinates. The subtile grid is always aligned to whole
+//! degrees, so that DEMs of different resolutions make subtiles at exactly the same places and
+//! their packings can be compared.
+//!
+//! Each DEM is made into subtiles on its own, so they're spread across all the cores. But the
+//! subtiles are always saved in the order of the DEMs' paths, so the same DEMs always make exactly
+//! the same file. Every DEM that's saved is recorded in a sidecar index, see `index.rs`, so that an
+//! unfinished run can be carried on with `--resume`. While the run is going the subtiles are saved
+//! without a header to a `.partial` file, and once every DEM is done they're saved with a header
+//! describing them, see `file.rs`.
+//!
+//! The same pass also makes richer statistics about every subtile, like where exactly its highest
+//! point is and how much of it is void, see `statistics.rs`.
 
 use color_eyre::Result;
 
-/// The file name for the max subtiles data.
-const SUBTILES_FILE: &str = "max_subtiles.bin";
+pub mod file;
+mod index;
+pub mod pyramid;
+pub mod statistics;
 
 /// The number of arc seconds in a degree.
 pub const ARCSEC_PER_DEG: f32 = 3600.0;
 
+/// The extensions of the rasters that are read from a directory of DEMs.
+const DEM_EXTENSIONS: [&str; 4] = ["hgt", "tif", "tiff", "vrt"];
+
+/// How close, in pixels, the edge of a subtile has to be to a pixel centre to be considered on
+/// it. Pixel centres often fall exactly on subtile edges, but floating point doesn't agree.
+const PIXEL_EPSILON: f64 = 0.000_001;
+
 /// A `MaxSubTile` is sub tile of a DEM tile, that contains nothing other than the maximum height
 /// of the subtile.
 #[repr(C)]
@@ -29,202 +50,621 @@ pub struct MaxSubTile {
     pub max_height: i32,
 }
 
+/// A block of elevations from a DEM, and where it is in the world.
+pub struct Elevations {
+    /// The elevations, row by row from the North West corner.
+    pub data: Vec<f32>,
+    /// The number of pixels in each row.
+    pub width: usize,
+    /// The lon/lat of the North West corner of the North West pixel.
+    pub origin: (f64, f64),
+    /// The size of each pixel in degrees. Like GDAL's geotransforms, the latitude size is negative
+    /// because rows go South.
+    pub pixel_size: (f64, f64),
+    /// The value that marks missing data.
+    pub nodata: Option<f64>,
+}
+
+impl Elevations {
+    /// The number of rows of pixels.
+    pub const fn height(&self) -> usize {
+        match self.data.len().checked_div(self.width) {
+            Some(height) => height,
+            None => 0,
+        }
+    }
+}
+
+/// Creates the max subtiles for a collection of DEM tiles.
 pub struct Subtiler {
     /// Keep track of _all_ the subtiles on the planet.
     subtiles: Vec<MaxSubTile>,
-    /// The width of the underlying DEM tile.
-    tile_width: usize,
-    /// The width of each subtile. Though note that the final subtiles of each row and colum may be
-    /// less than this.
-    subtile_width: f32,
+    /// The statistics of all the subtiles, including the ones that are entirely void.
+    stats: Vec<statistics::SubtileStats>,
+    /// How many subtiles each degree is split into, along both longitude and latitude.
+    factor: u32,
+    /// The finest resolution of the DEMs, in arc seconds.
+    pub resolution: Option<f32>,
     /// Keep track of how many invalid subtiles were found.
     pub invalid_count: u64,
 }
 
 impl Subtiler {
     /// Instantitate.
-    pub fn new(factor: u32, width: usize) -> Self {
-        #[expect(
-            clippy::as_conversions,
-            clippy::cast_precision_loss,
-            reason = "Is there another way?"
-        )]
-        let subtile_width = width as f32 / factor as f32;
+    pub const fn new(factor: u32) -> Self {
         Self {
             subtiles: Vec::new(),
-            tile_width: width,
-            subtile_width,
+            stats: Vec::new(),
+            factor,
+            resolution: None,
             invalid_count: 0,
         }
     }
 
-    /// Make all the subtiles for a DEM tile.
-    pub fn make_all_subtiles(&mut self, tile: &srtm_reader::Tile) {
-        let mut x_offset = 0.0;
-        let mut y_offset = 0.0;
+    /// The subtiles made so far.
+    pub fn subtiles(&self) -> &[MaxSubTile] {
+        &self.subtiles
+    }
+
+    /// The statistics of the subtiles made so far.
+    pub fn stats(&self) -> &[statistics::SubtileStats] {
+        &self.stats
+    }
+
+    /// Make all the subtiles for a single DEM raster.
+    ///
+    /// The raster is read a degree at a time, so that even a VRT of the whole world doesn't need
+    /// to fit in memory.
+    pub fn add_dem(&mut self, path: &std::path::Path) -> Result<()> {
+        let dataset = gdal::Dataset::open(path)?;
+        if !dataset.spatial_ref()?.is_geographic() {
+            color_eyre::eyre::bail!(
+                "{path:?} isn't in lon/lat coordinates, reproject it first with: \
+                `gdalwarp -t_srs EPSG:4326`"
+            );
+        }
+        let transform = dataset.geo_transform()?;
+        let [
+            west,
+            pixel_width,
+            row_rotation,
+            north,
+            column_rotation,
+            pixel_height,
+        ] = transform;
+        if row_rotation != 0.0f64 || column_rotation != 0.0f64 || pixel_height >= 0.0f64 {
+            color_eyre::eyre::bail!("{path:?} isn't North up, its geotransform is: {transform:?}");
+        }
+        #[expect(
+            clippy::as_conversions,
+            clippy::cast_possible_truncation,
+            reason = "Resolutions are only recorded for reference"
+        )]
+        let resolution = (pixel_width.min(-pixel_height) * f64::from(ARCSEC_PER_DEG)) as f32;
+        self.resolution = Some(
+            self.resolution
+                .map_or(resolution, |finest| finest.min(resolution)),
+        );
+
+        let band = dataset.rasterband(1)?;
+        let nodata = band.no_data_value();
+        let (width, height) = dataset.raster_size();
         #[expect(
             clippy::as_conversions,
             clippy::cast_precision_loss,
-            reason = "Is there another way?"
+            reason = "Rasters are much smaller than the types' limits"
         )]
-        let tile_width = self.tile_width as f32;
-        let last_subtile_start = tile_width - self.subtile_width;
+        let (east, south) = (
+            (width as f64).mul_add(pixel_width, west),
+            (height as f64).mul_add(pixel_height, north),
+        );
 
         #[expect(
-            clippy::while_float,
-            reason = "There aren't enough iterations for the errors to build up."
+            clippy::as_conversions,
+            clippy::cast_possible_truncation,
+            reason = "Degrees are much smaller than the types' limits"
         )]
-        while y_offset < last_subtile_start {
-            if let Some(subtile) = self.make_subtile(tile, x_offset, y_offset) {
-                self.subtiles.push(subtile);
-            }
+        for latitude in ((south.floor() as i32)..(north.ceil() as i32)).rev() {
+            for longitude in (west.floor() as i32)..(east.ceil() as i32) {
+                let columns = clamp_pixels(
+                    &pixels_between(
+                        west,
+                        pixel_width,
+                        (f64::from(longitude), f64::from(longitude + 1i32)),
+                    ),
+                    width,
+                );
+                let rows = clamp_pixels(
+                    &pixels_between(
+                        north,
+                        pixel_height,
+                        (f64::from(latitude), f64::from(latitude + 1i32)),
+                    ),
+                    height,
+                );
+                if columns.is_empty() || rows.is_empty() {
+                    continue;
+                }
 
-            x_offset += self.subtile_width;
-            if x_offset > last_subtile_start {
-                x_offset = 0.0;
-                y_offset += self.subtile_width;
+                let window_size = (columns.len(), rows.len());
+                #[expect(
+                    clippy::as_conversions,
+                    clippy::cast_possible_wrap,
+                    reason = "Rasters are much smaller than the types' limits"
+                )]
+                let buffer: gdal::raster::Buffer<f32> = band.read_as(
+                    (columns.start as isize, rows.start as isize),
+                    window_size,
+                    window_size,
+                    None,
+                )?;
+                #[expect(
+                    clippy::as_conversions,
+                    clippy::cast_precision_loss,
+                    reason = "Rasters are much smaller than the types' limits"
+                )]
+                let origin = (
+                    (columns.start as f64).mul_add(pixel_width, west),
+                    (rows.start as f64).mul_add(pixel_height, north),
+                );
+                let (_, data) = buffer.into_shape_and_vec();
+                self.make_all_subtiles(&Elevations {
+                    data,
+                    width: window_size.0,
+                    origin,
+                    pixel_size: (pixel_width, pixel_height),
+                    nodata,
+                });
             }
         }
+
+        Ok(())
     }
 
-    /// Make a single subtile.
-    fn make_subtile(
-        &mut self,
-        tile: &srtm_reader::Tile,
-        x_offset: f32,
-        y_offset: f32,
-    ) -> Option<MaxSubTile> {
-        let mut max_height = i16::MIN;
-        let mut is_valid = false;
-        let subtile_width = self.subtile_width;
-        let x_end = x_offset + subtile_width;
-        let y_end = y_offset + subtile_width;
-
-        let mut sub_y = y_offset;
-        #[expect(
-            clippy::while_float,
-            reason = "There aren't enough iterations for the errors to build up."
-        )]
-        while sub_y < y_end - 1.0 {
-            let mut sub_x = x_offset;
-            while sub_x < x_end - 1.0 {
-                let elevation = Self::get_elevation(tile, sub_x, sub_y);
-                if elevation > max_height {
-                    is_valid = true;
-                    max_height = elevation;
+    /// Make all the subtiles that are completely covered by a block of elevations. Subtiles on
+    /// the edges that are only partly covered are made from the neighbouring blocks.
+    #[expect(
+        clippy::as_conversions,
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        reason = "Blocks of elevations are much smaller than the types' limits"
+    )]
+    pub fn make_all_subtiles(&mut self, elevations: &Elevations) {
+        let factor = f64::from(self.factor);
+        let (west, north) = elevations.origin;
+        let east = (elevations.width as f64).mul_add(elevations.pixel_size.0, west);
+        let south = (elevations.height() as f64).mul_add(elevations.pixel_size.1, north);
+
+        let first_column = (west * factor).floor() as i64;
+        let end_column = (east * factor).ceil() as i64;
+        let first_row = (south * factor).floor() as i64;
+        let end_row = (north * factor).ceil() as i64;
+
+        for row in (first_row..end_row).rev() {
+            for column in first_column..end_column {
+                let bounds = (
+                    (column as f64 / factor, (column + 1) as f64 / factor),
+                    (row as f64 / factor, (row + 1) as f64 / factor),
+                );
+                let Some(subtile_stats) = Self::make_subtile(elevations, bounds) else {
+                    continue;
+                };
+                if let Some(subtile) = subtile_stats.max_subtile() {
+                    self.subtiles.push(subtile);
+                } else {
+                    self.invalid_count += 1;
+                    tracing::error!(
+                        "No max value found in subtile at: {},{}",
+                        subtile_stats.lon,
+                        subtile_stats.lat
+                    );
                 }
-                sub_x += 1.0;
+                self.stats.push(subtile_stats);
             }
-
-            sub_y += 1.0;
         }
+    }
 
-        if !is_valid {
-            self.invalid_count += 1;
-            tracing::error!(
-                "No max value found in: {},{}/{},{}",
-                tile.longitude,
-                tile.latitude,
-                x_offset,
-                y_offset
-            );
+    /// Make the statistics of a single subtile from all the pixels whose centres are inside its
+    /// bounds. They're only made if the elevations completely cover the subtile.
+    #[expect(
+        clippy::as_conversions,
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        reason = "Subtiles are stored as `f32`s and `i32`s"
+    )]
+    fn make_subtile(
+        elevations: &Elevations,
+        (longitudes, latitudes): ((f64, f64), (f64, f64)),
+    ) -> Option<statistics::SubtileStats> {
+        let (west, north) = elevations.origin;
+        let column_span = pixels_between(west, elevations.pixel_size.0, longitudes);
+        let row_span = pixels_between(north, elevations.pixel_size.1, latitudes);
+        let is_inside = |pixels: &std::ops::Range<i64>, count: usize| {
+            pixels.start >= 0i64 && usize::try_from(pixels.end).is_ok_and(|end| end <= count)
+        };
+        let is_covered =
+            is_inside(&column_span, elevations.width) && is_inside(&row_span, elevations.height());
+        if !is_covered || column_span.is_empty() || row_span.is_empty() {
             return None;
         }
+        let columns = clamp_pixels(&column_span, elevations.width);
+        let rows = clamp_pixels(&row_span, elevations.height());
+
+        let mut max_height = f32::MIN;
+        let mut min_height = f32::MAX;
+        let mut peak = (0, 0);
+        let mut total = 0.0f64;
+        let mut voids = 0u32;
+        for row in rows.clone() {
+            let start = row * elevations.width;
+            let pixels = elevations
+                .data
+                .get((start + columns.start)..(start + columns.end))?;
+            for (column, elevation) in columns.clone().zip(pixels) {
+                let is_nodata = elevations
+                    .nodata
+                    .is_some_and(|nodata| (f64::from(*elevation) - nodata).abs() < f64::EPSILON);
+                if is_nodata || !elevation.is_finite() {
+                    voids += 1;
+                    continue;
+                }
+                if *elevation > max_height {
+                    max_height = *elevation;
+                    peak = (column, row);
+                }
+                min_height = min_height.min(*elevation);
+                total += f64::from(*elevation);
+            }
+        }
 
-        let centre_xy = (
-            f32::midpoint(x_offset, x_end),
-            f32::midpoint(y_offset, y_end),
-        );
-        let centre_lonlat = Self::xy_to_lonlat(tile, centre_xy);
+        let lon = f64::midpoint(longitudes.0, longitudes.1) as f32;
+        let lat = f64::midpoint(latitudes.0, latitudes.1) as f32;
+        let pixels = u32::try_from(columns.len() * rows.len()).ok()?;
+        if voids == pixels {
+            return Some(statistics::SubtileStats::void(lon, lat, pixels));
+        }
 
-        let subtile = MaxSubTile {
-            lon: centre_lonlat.0,
-            lat: centre_lonlat.1,
-            max_height: i32::from(max_height),
-        };
+        Some(statistics::SubtileStats {
+            lon,
+            lat,
+            peak_lon: (peak.0 as f64 + 0.5f64).mul_add(elevations.pixel_size.0, west) as f32,
+            peak_lat: (peak.1 as f64 + 0.5f64).mul_add(elevations.pixel_size.1, north) as f32,
+            max_height: max_height.round() as i32,
+            min_height: min_height.round() as i32,
+            mean_height: (total / f64::from(pixels - voids)) as f32,
+            pixels,
+            voids,
+        })
+    }
 
-        Some(subtile)
+    /// Save the subtiles to disk, with a header describing them.
+    pub fn save(&self, path: &std::path::Path, dems: &str) -> Result<()> {
+        file::File {
+            metadata: file::Metadata::new(self.factor, dems, self.resolution, &self.subtiles)?,
+            subtiles: self.subtiles.clone(),
+        }
+        .save(path)
     }
 
-    /// Get the elevation at the DEM-relative point coordinates of the main tile.
-    #[expect(clippy::panic, reason = "This would be a serious bug")]
-    fn get_elevation(tile: &srtm_reader::Tile, x: f32, y: f32) -> i16 {
-        #[expect(
-            clippy::as_conversions,
-            clippy::cast_possible_truncation,
-            clippy::cast_sign_loss,
-            reason = "Is there another way?"
-        )]
-        let index = y.round() as usize * tile.resolution.extent() + x.round() as usize;
-        *tile
-            .data
-            .get(index)
-            .unwrap_or_else(|| panic!("Tried to get elevation for point outside of DEM: {x}x{y}"))
+    /// Load and validate subtiles from disk. Legacy headerless files are only loaded when
+    /// `is_legacy` is set.
+    pub fn load(path: &std::path::Path, is_legacy: bool) -> Result<file::File> {
+        file::File::load(path, is_legacy)
     }
+}
+
+/// The pixels whose centres are in `[low, high)`.
+///
+/// Pixels are `size` wide starting from `start`. The size is negative for rows of pixels, which
+/// go from North to South. The pixels aren't limited to the raster, so they can be negative or
+/// past its end.
+#[expect(
+    clippy::as_conversions,
+    clippy::cast_possible_truncation,
+    reason = "Rasters are much smaller than the types' limits"
+)]
+fn pixels_between(start: f64, size: f64, (low, high): (f64, f64)) -> std::ops::Range<i64> {
+    let at_low = (low - start) / size - 0.5f64;
+    let at_high = (high - start) / size - 0.5f64;
+    let (first, end) = if size > 0.0f64 {
+        (
+            (at_low - PIXEL_EPSILON).ceil(),
+            (at_high - PIXEL_EPSILON).ceil(),
+        )
+    } else {
+        (
+            (at_high + PIXEL_EPSILON).floor() + 1.0f64,
+            (at_low + PIXEL_EPSILON).floor() + 1.0f64,
+        )
+    };
+
+    (first as i64)..(end as i64)
+}
 
-    /// Convert a DEM-relative x/y coordinate to lon/lat.
-    fn xy_to_lonlat(tile: &srtm_reader::Tile, (x, y): (f32, f32)) -> (f32, f32) {
-        let constant = ARCSEC_PER_DEG / 3.0;
+/// The pixels that are both in `pixels` and in a raster `count` pixels wide.
+fn clamp_pixels(pixels: &std::ops::Range<i64>, count: usize) -> std::ops::Range<usize> {
+    let limit = |pixel: i64| usize::try_from(pixel).unwrap_or(0).min(count);
+    limit(pixels.start)..limit(pixels.end)
+}
 
-        let lon = f32::from(tile.longitude) + x / constant;
-        let lat = f32::from(tile.latitude) + 1.0 - y / constant;
-        (lon, lat)
+/// All the DEM rasters in a directory, or that match a glob, in a consistent order.
+fn find_dems(source: &str) -> Result<Vec<std::path::PathBuf>> {
+    let mut paths = Vec::new();
+    let directory = std::path::Path::new(source);
+    if directory.is_dir() {
+        for entry in std::fs::read_dir(directory)? {
+            let path = entry?.path();
+            let is_dem = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| {
+                    DEM_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+                });
+            if is_dem {
+                paths.push(path);
+            }
+        }
+    } else {
+        for path in glob::glob(source)? {
+            paths.push(path?);
+        }
     }
 
-    /// Save the subtiles to disk.
-    pub fn save(&self, path: &str) -> Result<()> {
-        let bytes: &[u8] = bytemuck::cast_slice(&self.subtiles);
-        let mut file = std::fs::File::create(path)?;
-        file.write_all(bytes)?;
-        Ok(())
+    paths.sort();
+    Ok(paths)
+}
+
+/// How many items per thread the workers can get ahead of the next item to save. Every result
+/// that's waiting to be saved is held in memory, and a DEM's subtiler can be big.
+const ITEMS_AHEAD_PER_THREAD: usize = 2;
+
+/// How far saving has got, shared with the workers so that they don't get too far ahead.
+#[derive(Default)]
+struct Saving {
+    /// The next item to save. Everything before it has been saved.
+    next_to_save: usize,
+    /// Whether saving has stopped, either because it's finished or because it failed.
+    is_stopped: bool,
+}
+
+/// Do some work on every item across a number of threads, and save the results in the same order
+/// as the items.
+///
+/// Results that finish early wait until all the results before them are saved. Saving happens on
+/// the calling thread, so it doesn't need to be `Send`. Workers don't start an item that's more
+/// than `ITEMS_AHEAD_PER_THREAD` items per thread ahead of the next item to save, so that there
+/// are never too many results waiting to be saved.
+fn in_parallel<T: Sync, R: Send>(
+    items: &[T],
+    threads: usize,
+    work: impl Fn(&T) -> Result<R> + Sync,
+    mut save: impl FnMut(&T, R) -> Result<()>,
+) -> Result<()> {
+    let next = std::sync::atomic::AtomicUsize::new(0);
+    let saving = (
+        std::sync::Mutex::new(Saving::default()),
+        std::sync::Condvar::new(),
+    );
+    let items_ahead = threads.max(1) * ITEMS_AHEAD_PER_THREAD;
+    let (sender, receiver) = std::sync::mpsc::sync_channel(threads);
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            let worker_sender = sender.clone();
+            let (next_ref, work_ref, saving_ref) = (&next, &work, &saving);
+            scope.spawn(move || {
+                loop {
+                    let index = next_ref.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    let Some(item) = items.get(index) else {
+                        break;
+                    };
+                    if !wait_to_start(saving_ref, index, items_ahead) {
+                        break;
+                    }
+                    // Sending only fails once saving has failed, so there's no point carrying on.
+                    if worker_sender.send((index, work_ref(item))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let save_in_order = || {
+            let mut finished = std::collections::BTreeMap::new();
+            let mut next_to_save = 0;
+            for (index, received) in receiver {
+                finished.insert(index, received);
+                while let Some(result) = finished.remove(&next_to_save) {
+                    let Some(item) = items.get(next_to_save) else {
+                        color_eyre::eyre::bail!(
+                            "Result for an item that doesn't exist: {next_to_save}"
+                        );
+                    };
+                    save(item, result?)?;
+                    next_to_save += 1;
+                    update_saving(&saving, |state| state.next_to_save = next_to_save);
+                }
+            }
+
+            Ok(())
+        };
+        let saved = save_in_order();
+        update_saving(&saving, |state| state.is_stopped = true);
+
+        saved
+    })
+}
+
+/// Wait until an item is close enough to the next item to save to start working on it. Returns
+/// false when saving has stopped, so there's no point working on it at all.
+fn wait_to_start(
+    saving: &(std::sync::Mutex<Saving>, std::sync::Condvar),
+    index: usize,
+    items_ahead: usize,
+) -> bool {
+    let (mutex, condvar) = saving;
+    let mut state = mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    while !state.is_stopped && index >= state.next_to_save + items_ahead {
+        state = condvar
+            .wait(state)
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
     }
 
-    /// Load subtiles from disk.
-    pub fn load(path: &str) -> Result<Vec<MaxSubTile>> {
-        let bytes = std::fs::read(path)?;
+    !state.is_stopped
+}
+
+/// Change how far saving has got, and wake up the workers that are waiting for it.
+fn update_saving(
+    saving: &(std::sync::Mutex<Saving>, std::sync::Condvar),
+    update: impl FnOnce(&mut Saving),
+) {
+    let (mutex, condvar) = saving;
+    update(
+        &mut mutex
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner),
+    );
+    condvar.notify_all();
+}
 
-        let slice: &[MaxSubTile] = bytemuck::try_cast_slice(&bytes).map_err(|error| {
-            std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("Error parsing saved file: {error:?}"),
-            )
-        })?;
+/// Logs how far through the DEMs a run is.
+struct Progress {
+    /// When the run started.
+    started: std::time::Instant,
+    /// How many DEMs have been saved.
+    done: usize,
+    /// How many DEMs there are to save.
+    total: usize,
+}
 
-        Ok(slice.to_vec())
+impl Progress {
+    /// Log the progress after another DEM is saved.
+    #[expect(
+        clippy::as_conversions,
+        clippy::cast_precision_loss,
+        reason = "Just for logging"
+    )]
+    fn saved(&mut self, dem: &std::path::Path, subtiles: usize) {
+        self.done += 1;
+        let fraction = self.done as f32 / self.total as f32;
+        let elapsed = self.started.elapsed().as_secs_f32();
+        let remaining = elapsed / fraction - elapsed;
+        tracing::info!(
+            "{}/{} DEMs ({:.1}%), about {:.0}s left. Saved {subtiles} subtiles from {dem:?}",
+            self.done,
+            self.total,
+            fraction * 100.0,
+            remaining
+        );
     }
 }
 
 /// Create the "max subtiles" acceleration structure file.
-pub fn run() -> Result<()> {
-    let mut subtiler = Subtiler::new(10, srtm_reader::Resolution::SRTM3.extent());
-    let mut count = 0u32;
-    let dir = std::path::Path::new("/publicish/dems");
-    let entries = std::fs::read_dir(dir)?;
-    let total = std::fs::read_dir(dir)?.count();
-    for entry in entries {
-        let path = entry?.path();
-        if path.extension().and_then(|string| string.to_str()) == Some("hgt") {
-            tracing::debug!("Loading {path:?}");
-            let tile = srtm_reader::Tile::from_file(&path).map_err(|error| {
-                color_eyre::eyre::eyre!("Couldn't load SRTM tile ({path:?}): {error:?}")
-            })?;
-            subtiler.make_all_subtiles(&tile);
-            count += 1;
-            #[expect(
-                clippy::as_conversions,
-                clippy::cast_precision_loss,
-                reason = "Just for logging"
-            )]
-            let percentage = (count as f32 / total as f32) * 100.0;
-            tracing::info!("{}%", percentage);
-        }
+pub fn run(config: &crate::config::MaxSubTiles) -> Result<()> {
+    let dems = find_dems(&config.dems)?;
+    if dems.is_empty() {
+        color_eyre::eyre::bail!("No DEMs found in: {}", config.dems);
     }
 
-    if subtiler.invalid_count > 0 {
-        tracing::warn!("{} invalid subtiles", subtiler.invalid_count);
+    let source = index::Source {
+        factor: config.factor,
+        dems: config.dems.clone(),
+    };
+    let (mut index, saved) = if config.resume {
+        index::Index::resume(&config.output, &source)?
+    } else {
+        (index::Index::create(&config.output, &source)?, Vec::new())
+    };
+    let saved_dems: std::collections::HashSet<&std::path::Path> =
+        saved.iter().map(|entry| entry.dem.as_path()).collect();
+    let remaining: Vec<std::path::PathBuf> = dems
+        .into_iter()
+        .filter(|dem| !saved_dems.contains(dem.as_path()))
+        .collect();
+    tracing::info!(
+        "Making max subtiles for {} DEMs, {} already saved in {:?}",
+        remaining.len(),
+        saved.len(),
+        index::Index::partial(&config.output)
+    );
+
+    let threads = match config.threads {
+        Some(threads) => usize::try_from(threads)?,
+        None => std::thread::available_parallelism()?.get(),
+    };
+    let mut progress = Progress {
+        started: std::time::Instant::now(),
+        done: 0,
+        total: remaining.len(),
+    };
+    let mut invalid_count = 0;
+    let mut resolution = saved
+        .iter()
+        .filter_map(|entry| entry.resolution)
+        .reduce(f32::min);
+    in_parallel(
+        &remaining,
+        threads,
+        |dem| {
+            tracing::debug!("Loading {dem:?}");
+            let mut subtiler = Subtiler::new(config.factor);
+            subtiler.add_dem(dem)?;
+            Ok(subtiler)
+        },
+        |dem, subtiler| {
+            index.save(
+                dem,
+                subtiler.resolution,
+                subtiler.subtiles(),
+                subtiler.stats(),
+            )?;
+            invalid_count += subtiler.invalid_count;
+            resolution = [resolution, subtiler.resolution]
+                .into_iter()
+                .flatten()
+                .reduce(f32::min);
+            progress.saved(dem, subtiler.subtiles().len());
+            Ok(())
+        },
+    )?;
+
+    if invalid_count > 0 {
+        tracing::warn!("{invalid_count} invalid subtiles");
     }
 
-    subtiler.save(SUBTILES_FILE)?;
+    let subtiles: Vec<MaxSubTile> =
+        file::parse_subtiles(&std::fs::read(index::Index::partial(&config.output))?)?;
+    let max_subtiles = file::File {
+        metadata: file::Metadata::new(config.factor, &config.dems, resolution, &subtiles)?,
+        subtiles,
+    };
+    max_subtiles.save(&config.output)?;
+    tracing::info!(
+        "Saved {} max subtiles to: {:?}",
+        max_subtiles.metadata.subtiles,
+        config.output
+    );
+
+    let all_stats: Vec<statistics::SubtileStats> =
+        file::parse_subtiles(&std::fs::read(index::Index::partial_stats(&config.output))?)?;
+    if let Some(void_map) = &config.void_map {
+        statistics::save_void_map(&all_stats, config.factor, void_map)?;
+    }
+    let subtile_stats = file::File {
+        metadata: file::Metadata::new(config.factor, &config.dems, resolution, &all_stats)?,
+        subtiles: all_stats,
+    };
+    subtile_stats.save(&config.stats)?;
+    tracing::info!(
+        "Saved {} subtile statistics to: {:?}",
+        subtile_stats.metadata.subtiles,
+        config.stats
+    );
+
+    index.remove(&config.output)?;
 
     Ok(())
 }
@@ -234,32 +674,36 @@ mod test {
     use super::*;
 
     const SUBTILE_FACTOR: u32 = 10;
-    const WIDTH: usize = srtm_reader::Resolution::SRTM3.extent();
+    /// The width of an SRTM3 `.hgt`, whose edges overlap its neighbours by one pixel.
+    const WIDTH: usize = 1201;
     const TOTAL_TILES: usize = (SUBTILE_FACTOR).pow(2) as usize;
 
-    fn make_hgt() -> srtm_reader::Tile {
+    fn make_hgt() -> Elevations {
         let total = WIDTH.pow(2);
-        let mut data = vec![0; total];
-        data[WIDTH + 2] = 42;
-        data[total - 1] = 69;
-        srtm_reader::Tile {
-            longitude: -2,
-            latitude: 54,
-            resolution: srtm_reader::Resolution::SRTM3,
+        let mut data = vec![0.0; total];
+        data[WIDTH + 2] = 42.0;
+        data[(WIDTH - 2) * WIDTH + WIDTH - 2] = 69.0;
+        // The last column belongs to the subtiles of the tile to the East.
+        data[total - 1] = 1000.0;
+        let pixel = 1.0f64 / 1200.0f64;
+        Elevations {
             data,
+            width: WIDTH,
+            origin: (-2.0 - pixel / 2.0, 55.0 + pixel / 2.0),
+            pixel_size: (pixel, -pixel),
+            nodata: Some(-32768.0f64),
         }
     }
 
     #[test]
     fn max_subtile_top_left() {
-        let tile = make_hgt();
-        let mut subtiler = Subtiler::new(SUBTILE_FACTOR, tile.resolution.extent());
-        subtiler.make_all_subtiles(&tile);
+        let mut subtiler = Subtiler::new(SUBTILE_FACTOR);
+        subtiler.make_all_subtiles(&make_hgt());
         assert_eq!(
             subtiler.subtiles[0],
             MaxSubTile {
-                lon: -1.9499583,
-                lat: 54.94996,
+                lon: -1.95,
+                lat: 54.95,
                 max_height: 42,
             }
         );
@@ -267,29 +711,98 @@ mod test {
 
     #[test]
     fn max_subtile_bottom_right() {
-        let tile = make_hgt();
-        let mut subtiler = Subtiler::new(SUBTILE_FACTOR, tile.resolution.extent());
-        subtiler.make_all_subtiles(&tile);
+        let mut subtiler = Subtiler::new(SUBTILE_FACTOR);
+        subtiler.make_all_subtiles(&make_hgt());
         assert_eq!(subtiler.subtiles.len(), TOTAL_TILES);
         assert_eq!(
             subtiler.subtiles[TOTAL_TILES - 1],
             MaxSubTile {
-                lon: -1.0492084,
-                lat: 54.04921,
+                lon: -1.05,
+                lat: 54.05,
                 max_height: 69
             }
         );
     }
 
+    #[test]
+    fn subtile_statistics() {
+        let mut hgt = make_hgt();
+        hgt.data[WIDTH] = -32768.0;
+        hgt.data[WIDTH + 1] = -5.0;
+        let mut subtiler = Subtiler::new(SUBTILE_FACTOR);
+        subtiler.make_all_subtiles(&hgt);
+
+        let pixel = 1.0f32 / 1200.0f32;
+        let stats = subtiler.stats[0];
+        assert_eq!(stats.max_height, 42i32);
+        assert_eq!(stats.min_height, -5i32);
+        assert!((stats.peak_lon - 2.0f32.mul_add(pixel, -2.0)).abs() < 0.000_01);
+        assert!((stats.peak_lat - (55.0 - pixel)).abs() < 0.000_01);
+        assert!((stats.mean_height - 37.0 / 14_399.0).abs() < 0.000_01);
+        assert_eq!((stats.pixels, stats.voids), (14_400, 1));
+        assert_eq!(stats.max_subtile(), Some(subtiler.subtiles[0]));
+    }
+
+    #[test]
+    fn any_resolution_makes_the_same_subtiles() {
+        // Like a Copernicus GLO-30 tile, where pixels don't overlap the neighbouring tiles.
+        let width = 360;
+        let pixel = 1.0f64 / 360.0f64;
+        let mut data = vec![-32768.0; width * width];
+        data[width + 2] = 42.0;
+        let copernicus = Elevations {
+            data,
+            width,
+            origin: (-2.0f64, 55.0f64),
+            pixel_size: (pixel, -pixel),
+            nodata: Some(-32768.0f64),
+        };
+
+        let mut subtiler = Subtiler::new(SUBTILE_FACTOR);
+        subtiler.make_all_subtiles(&copernicus);
+        assert_eq!(subtiler.subtiles.len(), 1);
+        assert_eq!(subtiler.invalid_count, 99);
+        assert_eq!(subtiler.stats.len(), 100);
+
+        let mut srtm = Subtiler::new(SUBTILE_FACTOR);
+        srtm.make_all_subtiles(&make_hgt());
+        assert_eq!(subtiler.subtiles[0], srtm.subtiles[0]);
+    }
+
+    #[test]
+    fn parallel_results_are_saved_in_order() {
+        let items: Vec<u64> = (0..20).collect();
+        let mut saved = Vec::new();
+        let saved_count = std::sync::atomic::AtomicU64::new(0);
+        in_parallel(
+            &items,
+            4,
+            |item| {
+                let ahead = item - saved_count.load(std::sync::atomic::Ordering::SeqCst);
+                assert!(ahead < 4 * 2, "Item {item} started {ahead} items ahead");
+                // Make the earlier items finish last.
+                std::thread::sleep(std::time::Duration::from_millis(20 - item));
+                Ok(item * 2)
+            },
+            |item, result| {
+                saved.push((*item, result));
+                saved_count.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        let expected: Vec<(u64, u64)> = items.iter().map(|item| (*item, item * 2)).collect();
+        assert_eq!(saved, expected);
+    }
+
     #[test]
     fn save_and_load() {
         let file = tempfile::NamedTempFile::new().unwrap();
-        let path = file.path().to_str().unwrap();
-        let tile = make_hgt();
-        let mut subtiler = Subtiler::new(SUBTILE_FACTOR, tile.resolution.extent());
-        subtiler.make_all_subtiles(&tile);
-        subtiler.save(path).unwrap();
-        let all_subtiles = Subtiler::load(path).unwrap();
+        let mut subtiler = Subtiler::new(SUBTILE_FACTOR);
+        subtiler.make_all_subtiles(&make_hgt());
+        subtiler.save(file.path(), "dems").unwrap();
+        let all_subtiles = Subtiler::load(file.path(), false).unwrap().subtiles;
         assert_eq!(all_subtiles.len(), TOTAL_TILES);
         assert_eq!(subtiler.subtiles[TOTAL_TILES - 1].max_height, 69i32);
     }
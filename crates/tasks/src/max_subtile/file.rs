@@ -0,0 +1,291 @@
+//! The max subtiles file format.
+//!
+//! A max subtiles file starts with a header that describes how it was made, then has all the
+//! subtiles as raw `MaxSubTile` structs:
+//!
+//! ```text
+//! MAXSUBTL                       8 bytes of magic
+//! 312                            the length of the metadata as a little endian `u32`
+//! {"version":1,"factor":10,...}  the metadata as JSON
+//! ...                            the subtiles, 12 bytes each
+//! ```
+//!
+//! The metadata has a checksum of the subtiles, so a truncated or corrupted file gets noticed
+//! when it's loaded.
+//!
+//! Older files are just the raw subtiles without a header. They can still be loaded, but only when
+//! asked for explicitly with `--legacy-subtiles`, as there's no way to check them.
+//!
+//! The subtile statistics, see `statistics.rs`, are saved in exactly the same way, just with their own
+//! magic so that the two kinds of file can't be mixed up.
+
+use std::io::Write as _;
+
+use color_eyre::Result;
+
+/// The current version of the max subtiles file format.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Something that's saved one after another in the body of a file, like a `MaxSubTile`.
+pub trait Record: bytemuck::Pod {
+    /// The start of every file of these records that has a header.
+    const MAGIC: &'static [u8; 8];
+}
+
+impl Record for crate::max_subtile::MaxSubTile {
+    const MAGIC: &'static [u8; 8] = b"MAXSUBTL";
+}
+
+/// The subtiles per degree of legacy files, which were all made from SRTM3 `.hgt`s.
+const LEGACY_FACTOR: u32 = 10;
+
+/// The resolution, in arc seconds, of the SRTM3 DEMs that legacy files were made from.
+const LEGACY_RESOLUTION: f32 = 3.0;
+
+/// The order of the bytes in the subtiles' numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ByteOrder {
+    /// Least significant byte first, like x86 and ARM.
+    Little,
+    /// Most significant byte first.
+    Big,
+}
+
+impl ByteOrder {
+    /// The byte order of this machine.
+    pub const fn native() -> Self {
+        if cfg!(target_endian = "big") {
+            Self::Big
+        } else {
+            Self::Little
+        }
+    }
+}
+
+/// Metadata about a whole max subtiles file.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Metadata {
+    /// The version of the file format. Legacy headerless files are version 0.
+    pub version: u32,
+    /// How many subtiles each degree is split into, along both longitude and latitude.
+    pub factor: u32,
+    /// Where the DEMs came from, the folder or glob given to `max-sub-tiles`.
+    pub dems: String,
+    /// The finest resolution of the DEMs, in arc seconds.
+    pub resolution: Option<f32>,
+    /// The order of the bytes in the subtiles' numbers.
+    pub byte_order: ByteOrder,
+    /// How many subtiles there are.
+    pub subtiles: u64,
+    /// A checksum of all the subtiles. It's the same as the tiles files' `input_hash`.
+    pub checksum: String,
+}
+
+impl Metadata {
+    /// Describe some subtiles made on this machine.
+    pub fn new<R: Record>(
+        factor: u32,
+        dems: &str,
+        resolution: Option<f32>,
+        subtiles: &[R],
+    ) -> Result<Self> {
+        Ok(Self {
+            version: FORMAT_VERSION,
+            factor,
+            dems: dems.to_owned(),
+            resolution,
+            byte_order: ByteOrder::native(),
+            subtiles: u64::try_from(subtiles.len())?,
+            checksum: checksum(subtiles),
+        })
+    }
+}
+
+/// A whole max subtiles file, or a file of any other records about the subtiles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct File<R: Record = crate::max_subtile::MaxSubTile> {
+    /// Metadata about the whole file.
+    pub metadata: Metadata,
+    /// All the subtiles.
+    pub subtiles: Vec<R>,
+}
+
+impl<R: Record> File<R> {
+    /// Save the subtiles with their header.
+    pub fn save(&self, path: &std::path::Path) -> Result<()> {
+        let metadata = serde_json::to_string(&self.metadata)?;
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        file.write_all(R::MAGIC)?;
+        #[expect(
+            clippy::little_endian_bytes,
+            reason = "The header is the same on every machine"
+        )]
+        file.write_all(&u32::try_from(metadata.len())?.to_le_bytes())?;
+        file.write_all(metadata.as_bytes())?;
+        file.write_all(bytemuck::cast_slice(&self.subtiles))?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Load and validate a max subtiles file. Legacy headerless files are only loaded when
+    /// `is_legacy` is set.
+    pub fn load(path: &std::path::Path, is_legacy: bool) -> Result<Self> {
+        let bytes = std::fs::read(path)
+            .map_err(|error| color_eyre::eyre::eyre!("Couldn't read {path:?}: {error}"))?;
+        let file = Self::parse(&bytes, is_legacy).map_err(|error| {
+            color_eyre::eyre::eyre!("Invalid max subtiles file {path:?}: {error}")
+        })?;
+        if file.metadata.version == 0 {
+            tracing::warn!(
+                "Loaded legacy headerless max subtiles {path:?}, consider remaking them"
+            );
+        }
+
+        Ok(file)
+    }
+
+    /// Parse and validate the contents of a max subtiles file.
+    fn parse(bytes: &[u8], is_legacy: bool) -> Result<Self> {
+        let Some(after_magic) = bytes.strip_prefix(R::MAGIC) else {
+            if !is_legacy {
+                color_eyre::eyre::bail!(
+                    "It doesn't have a header. If it's from before max subtiles files had \
+                    headers, load it with `--legacy-subtiles`"
+                );
+            }
+            let subtiles = parse_subtiles(bytes)?;
+            return Ok(Self {
+                metadata: Metadata {
+                    version: 0,
+                    ..Metadata::new(LEGACY_FACTOR, "", Some(LEGACY_RESOLUTION), &subtiles)?
+                },
+                subtiles,
+            });
+        };
+
+        let Some((length_bytes, after_length)) = after_magic.split_first_chunk::<4>() else {
+            color_eyre::eyre::bail!("The header is cut short");
+        };
+        #[expect(
+            clippy::little_endian_bytes,
+            reason = "The header is the same on every machine"
+        )]
+        let length = usize::try_from(u32::from_le_bytes(*length_bytes))?;
+        let Some((json, body)) = after_length.split_at_checked(length) else {
+            color_eyre::eyre::bail!("The metadata is cut short");
+        };
+        let metadata: Metadata = serde_json::from_slice(json)?;
+
+        if metadata.version > FORMAT_VERSION {
+            color_eyre::eyre::bail!(
+                "Version {} is newer than the supported version {FORMAT_VERSION}",
+                metadata.version
+            );
+        }
+        if metadata.byte_order != ByteOrder::native() {
+            color_eyre::eyre::bail!(
+                "It was made on a machine with a different byte order: {:?}",
+                metadata.byte_order
+            );
+        }
+
+        let subtiles = parse_subtiles(body)?;
+        if u64::try_from(subtiles.len())? != metadata.subtiles {
+            color_eyre::eyre::bail!(
+                "It should have {} subtiles, but it has {}",
+                metadata.subtiles,
+                subtiles.len()
+            );
+        }
+        let actual = checksum(&subtiles);
+        if actual != metadata.checksum {
+            color_eyre::eyre::bail!(
+                "The subtiles' checksum is {actual}, but it should be {}",
+                metadata.checksum
+            );
+        }
+
+        Ok(Self { metadata, subtiles })
+    }
+}
+
+/// A checksum of some subtiles. For max subtiles it's the same as the tiles files' `input_hash`.
+pub fn checksum<R: Record>(subtiles: &[R]) -> String {
+    let bytes: &[u8] = bytemuck::cast_slice(subtiles);
+    format!("crc32:{:08x}", crc32fast::hash(bytes))
+}
+
+/// Parse raw subtiles, like the body of a max subtiles file.
+pub fn parse_subtiles<R: Record>(bytes: &[u8]) -> Result<Vec<R>> {
+    if !bytes.len().is_multiple_of(size_of::<R>()) {
+        color_eyre::eyre::bail!(
+            "It's {} bytes of subtiles, which isn't a whole number of subtiles",
+            bytes.len()
+        );
+    }
+
+    Ok(bytes
+        .chunks_exact(size_of::<R>())
+        .map(bytemuck::pod_read_unaligned)
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn file() -> File {
+        let subtiles = vec![
+            crate::max_subtile::MaxSubTile {
+                lon: -1.95,
+                lat: 54.95,
+                max_height: 42,
+            },
+            crate::max_subtile::MaxSubTile {
+                lon: -1.05,
+                lat: 54.05,
+                max_height: 69,
+            },
+        ];
+        File {
+            metadata: Metadata::new(10, "dems", Some(1.0), &subtiles).unwrap(),
+            subtiles,
+        }
+    }
+
+    #[test]
+    fn save_and_load() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("max_subtiles.bin");
+        file().save(&path).unwrap();
+        assert_eq!(File::load(&path, false).unwrap(), file());
+    }
+
+    #[test]
+    fn corrupted_subtiles_are_refused() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("max_subtiles.bin");
+        file().save(&path).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        if let Some(last) = bytes.last_mut() {
+            *last ^= 1;
+        }
+
+        let error = <File>::parse(&bytes, false).unwrap_err();
+        assert!(error.to_string().starts_with("The subtiles' checksum is"));
+    }
+
+    #[test]
+    fn legacy_files_need_the_legacy_flag() {
+        let bytes = bytemuck::cast_slice(&file().subtiles).to_vec();
+
+        let error = <File>::parse(&bytes, false).unwrap_err();
+        assert!(error.to_string().starts_with("It doesn't have a header"));
+
+        let legacy = <File>::parse(&bytes, true).unwrap();
+        assert_eq!(legacy.metadata.version, 0);
+        assert_eq!(legacy.metadata.factor, LEGACY_FACTOR);
+        assert_eq!(legacy.subtiles, file().subtiles);
+    }
+}
@@ -0,0 +1,277 @@
+//! The sidecar index of a max subtiles file, so that making the subtiles for the whole world can
+//! be resumed when it stops for whatever reason.
+//!
+//! While a run is going, the subtiles are saved without a header to a partial file next to the
+//! final one, eg `max_subtiles.bin.partial`, and their statistics to another, eg
+//! `max_subtiles.bin.stats.partial`. The index sits next to them, eg `max_subtiles.bin.index`. Its
+//! first line is `{factor}\t{DEMs}`, what the subtiles are made from, so that a run can't be
+//! resumed with different arguments. Then it records every DEM whose subtiles have been saved, one
+//! per line as `{number of subtiles}\t{number of statistics}\t{resolution}\t{path to DEM}`. A DEM
+//! is only recorded after its subtiles are safely on disk, so any subtiles past the last recorded
+//! DEM are from a DEM that didn't finish and get thrown away when resuming. Once the run has
+//! finished, all the files are removed.
+
+use std::io::Write as _;
+
+use color_eyre::Result;
+
+/// A DEM whose subtiles have been saved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// How many subtiles the DEM made.
+    pub subtiles: u64,
+    /// How many subtile statistics the DEM made, including entirely void subtiles.
+    pub stats: u64,
+    /// The DEM's resolution in arc seconds.
+    pub resolution: Option<f32>,
+    /// The DEM file.
+    pub dem: std::path::PathBuf,
+}
+
+/// What the subtiles in an index are made from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    /// How many subtiles each degree is split into.
+    pub factor: u32,
+    /// The folder or glob of the DEMs.
+    pub dems: String,
+}
+
+/// The index of the DEMs that have been saved to a max subtiles file.
+pub struct Index {
+    /// The open index file.
+    file: std::fs::File,
+    /// The open partial subtiles.
+    subtiles: std::fs::File,
+    /// The open partial subtile statistics.
+    stats: std::fs::File,
+}
+
+impl Index {
+    /// The path of the index for a max subtiles file.
+    pub fn path(output: &std::path::Path) -> std::path::PathBuf {
+        sidecar(output, ".index")
+    }
+
+    /// The path of the partial subtiles for a max subtiles file.
+    pub fn partial(output: &std::path::Path) -> std::path::PathBuf {
+        sidecar(output, ".partial")
+    }
+
+    /// The path of the partial subtile statistics for a max subtiles file.
+    pub fn partial_stats(output: &std::path::Path) -> std::path::PathBuf {
+        sidecar(output, ".stats.partial")
+    }
+
+    /// Start new partial max subtiles and statistics files, and an index with just their source.
+    pub fn create(output: &std::path::Path, source: &Source) -> Result<Self> {
+        let mut file = std::fs::File::create(Self::path(output))?;
+        writeln!(file, "{}\t{}", source.factor, source.dems)?;
+        file.sync_data()?;
+
+        Ok(Self {
+            subtiles: std::fs::File::create(Self::partial(output))?,
+            stats: std::fs::File::create(Self::partial_stats(output))?,
+            file,
+        })
+    }
+
+    /// Carry on with existing partial max subtiles and statistics files. Any subtiles that aren't
+    /// in the index are removed from the end of the files. The index must be for the same source.
+    pub fn resume(output: &std::path::Path, source: &Source) -> Result<(Self, Vec<Entry>)> {
+        let path = Self::path(output);
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|error| color_eyre::eyre::eyre!("Couldn't read {path:?}: {error}"))?;
+
+        let mut lines = contents.lines();
+        let first_line = lines.next().unwrap_or_default();
+        let Some((factor, dems)) = first_line.split_once('\t') else {
+            color_eyre::eyre::bail!("{path:?} doesn't start with the factor and the DEMs");
+        };
+        let indexed = Source {
+            factor: factor.parse()?,
+            dems: dems.to_owned(),
+        };
+        if &indexed != source {
+            color_eyre::eyre::bail!(
+                "Can't resume {path:?}, it was started with `--factor {}` and `--dems {}`. Run \
+                with the same arguments, or start again without `--resume`",
+                indexed.factor,
+                indexed.dems
+            );
+        }
+
+        let mut entries = Vec::new();
+        for line in lines {
+            let mut columns = line.splitn(4, '\t');
+            let (Some(subtiles), Some(stats), Some(resolution), Some(dem)) = (
+                columns.next(),
+                columns.next(),
+                columns.next(),
+                columns.next(),
+            ) else {
+                color_eyre::eyre::bail!("Badly formatted line in {path:?}: {line}");
+            };
+            entries.push(Entry {
+                subtiles: subtiles.parse()?,
+                stats: stats.parse()?,
+                resolution: match resolution {
+                    "" => None,
+                    arcseconds => Some(arcseconds.parse()?),
+                },
+                dem: std::path::PathBuf::from(dem),
+            });
+        }
+
+        let subtiles = truncate::<crate::max_subtile::MaxSubTile>(
+            &Self::partial(output),
+            entries.iter().map(|entry| entry.subtiles).sum(),
+        )?;
+        let stats = truncate::<crate::max_subtile::statistics::SubtileStats>(
+            &Self::partial_stats(output),
+            entries.iter().map(|entry| entry.stats).sum(),
+        )?;
+
+        let file = std::fs::OpenOptions::new().append(true).open(&path)?;
+        Ok((
+            Self {
+                file,
+                subtiles,
+                stats,
+            },
+            entries,
+        ))
+    }
+
+    /// Save a DEM's subtiles and their statistics, then record that they've been saved.
+    pub fn save(
+        &mut self,
+        dem: &std::path::Path,
+        resolution: Option<f32>,
+        subtiles: &[crate::max_subtile::MaxSubTile],
+        stats: &[crate::max_subtile::statistics::SubtileStats],
+    ) -> Result<()> {
+        self.subtiles.write_all(bytemuck::cast_slice(subtiles))?;
+        self.subtiles.sync_data()?;
+        self.stats.write_all(bytemuck::cast_slice(stats))?;
+        self.stats.sync_data()?;
+        self.record(&Entry {
+            subtiles: u64::try_from(subtiles.len())?,
+            stats: u64::try_from(stats.len())?,
+            resolution,
+            dem: dem.to_path_buf(),
+        })
+    }
+
+    /// Record that a DEM's subtiles have been saved. The subtiles must already be synced to disk.
+    fn record(&mut self, entry: &Entry) -> Result<()> {
+        let resolution = entry
+            .resolution
+            .map(|arcseconds| arcseconds.to_string())
+            .unwrap_or_default();
+        writeln!(
+            self.file,
+            "{}\t{}\t{resolution}\t{}",
+            entry.subtiles,
+            entry.stats,
+            entry.dem.display()
+        )?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    /// Remove the index and the partial files once the final files are saved.
+    pub fn remove(self, output: &std::path::Path) -> Result<()> {
+        drop(self);
+        std::fs::remove_file(Self::path(output))?;
+        std::fs::remove_file(Self::partial(output))?;
+        std::fs::remove_file(Self::partial_stats(output))?;
+        Ok(())
+    }
+}
+
+/// Open a partial file to carry on saving to it, removing anything past the `recorded` records
+/// from the end.
+fn truncate<R: crate::max_subtile::file::Record>(
+    partial: &std::path::Path,
+    recorded: u64,
+) -> Result<std::fs::File> {
+    let size_of_record = u64::try_from(size_of::<R>())?;
+    let expected_size = recorded * size_of_record;
+    let file = std::fs::OpenOptions::new().append(true).open(partial)?;
+    let actual_size = file.metadata()?.len();
+    if actual_size < expected_size {
+        color_eyre::eyre::bail!(
+            "{partial:?} is smaller than its index says, it should be at least \
+            {expected_size} bytes but it's {actual_size}"
+        );
+    }
+    if actual_size > expected_size {
+        tracing::warn!(
+            "Removing {} records from an unfinished DEM at the end of {partial:?}",
+            (actual_size - expected_size).div_euclid(size_of_record)
+        );
+        file.set_len(expected_size)?;
+    }
+
+    Ok(file)
+}
+
+/// A file next to the max subtiles file, with an extra extension.
+fn sidecar(output: &std::path::Path, extension: &str) -> std::path::PathBuf {
+    let mut name = output.as_os_str().to_owned();
+    name.push(extension);
+    std::path::PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resuming_drops_unrecorded_subtiles() {
+        let directory = tempfile::tempdir().unwrap();
+        let output = directory.path().join("max_subtiles.bin");
+        let subtile = crate::max_subtile::MaxSubTile {
+            lon: 1.0,
+            lat: 2.0,
+            max_height: 3,
+        };
+        let stats = crate::max_subtile::statistics::SubtileStats::void(1.0, 2.0, 4);
+        let dem = std::path::PathBuf::from("dems/N02E001.hgt");
+
+        let source = Source {
+            factor: 10,
+            dems: "dems".to_owned(),
+        };
+        let mut index = Index::create(&output, &source).unwrap();
+        index
+            .save(&dem, Some(1.0), &[subtile; 2], &[stats; 3])
+            .unwrap();
+        // A DEM that stopped halfway through being saved.
+        index
+            .subtiles
+            .write_all(bytemuck::bytes_of(&subtile))
+            .unwrap();
+        drop(index);
+
+        let different = Source {
+            factor: 20,
+            ..source.clone()
+        };
+        assert!(Index::resume(&output, &different).is_err());
+
+        let (_, entries) = Index::resume(&output, &source).unwrap();
+        assert_eq!(
+            entries,
+            vec![Entry {
+                subtiles: 2,
+                stats: 3,
+                resolution: Some(1.0),
+                dem,
+            }]
+        );
+        let saved = std::fs::read(Index::partial(&output)).unwrap();
+        assert_eq!(saved, bytemuck::cast_slice::<_, u8>(&[subtile; 2]));
+    }
+}
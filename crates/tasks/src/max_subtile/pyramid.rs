@@ -0,0 +1,399 @@
+//! A pyramid of max subtiles, for quickly finding the highest subtile in an area.
+//!
+//! The bottom level of the pyramid is a grid of cells the size of a subtile, each holding the
+//! subtiles inside it. Every level above merges 2x2 cells of the level below, and each cell
+//! remembers the highest subtile anywhere inside it. So the whole world is summarised by the 4
+//! cells at the top.
+//!
+//! Finding the highest subtile in an area starts at the top and always looks inside the highest
+//! cell that's still a candidate next. Cells that don't touch the area's bounding boxes are
+//! skipped. As soon as a subtile inside the area comes up, there can't be anything higher, so only
+//! the cells along the way down to it are ever opened, rather than every subtile in the area.
+
+use geo::Intersects as _;
+
+/// How much bigger cells are made when checking if they touch an area, so that subtiles that are
+/// exactly on the edge of a cell aren't missed because of floating point errors.
+const CELL_MARGIN: f64 = 0.000_000_001;
+
+/// A cell's position in its level of the pyramid.
+type Cell = (i64, i64);
+
+/// What's inside a cell of the pyramid.
+#[derive(Debug, Clone, Copy)]
+struct Summary {
+    /// The highest subtile in the cell.
+    highest: crate::max_subtile::MaxSubTile,
+    /// Whether any of the cell's subtiles isn't at sea level.
+    has_non_zero: bool,
+}
+
+impl Summary {
+    /// Summarise a single subtile.
+    const fn of(subtile: crate::max_subtile::MaxSubTile) -> Self {
+        Self {
+            highest: subtile,
+            has_non_zero: subtile.max_height != 0i32,
+        }
+    }
+
+    /// Add another cell's summary to this one.
+    fn merge(&mut self, other: &Self) {
+        if compare(&other.highest, &self.highest).is_gt() {
+            self.highest = other.highest;
+        }
+        self.has_non_zero |= other.has_non_zero;
+    }
+}
+
+/// Something that might be the highest subtile in an area.
+#[derive(Debug)]
+enum Candidate {
+    /// A cell of the pyramid, that still needs opening.
+    Cell {
+        /// The level of the pyramid, 0 is the bottom.
+        level: usize,
+        /// The cell in its level.
+        cell: Cell,
+        /// The highest subtile anywhere in the cell.
+        highest: crate::max_subtile::MaxSubTile,
+    },
+    /// A single subtile.
+    Subtile(crate::max_subtile::MaxSubTile),
+}
+
+impl Candidate {
+    /// The highest subtile that this candidate could be.
+    const fn highest(&self) -> &crate::max_subtile::MaxSubTile {
+        match self {
+            Self::Cell { highest, .. } | Self::Subtile(highest) => highest,
+        }
+    }
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        compare(self.highest(), other.highest())
+    }
+}
+
+/// The order of subtiles by height. Subtiles of the same height are ordered by their position, so
+/// that the highest subtile is always the same one whatever order the subtiles are searched in.
+fn compare(
+    left: &crate::max_subtile::MaxSubTile,
+    right: &crate::max_subtile::MaxSubTile,
+) -> std::cmp::Ordering {
+    left.max_height
+        .cmp(&right.max_height)
+        .then(left.lon.total_cmp(&right.lon))
+        .then(left.lat.total_cmp(&right.lat))
+}
+
+/// A pyramid of max subtiles.
+pub struct Pyramid {
+    /// How many cells of the bottom level there are per degree.
+    factor: f64,
+    /// The subtiles in each cell of the bottom level.
+    subtiles: std::collections::HashMap<Cell, Vec<crate::max_subtile::MaxSubTile>>,
+    /// The summaries of the cells of every level, from the bottom up.
+    levels: Vec<std::collections::HashMap<Cell, Summary>>,
+}
+
+impl Pyramid {
+    /// Build the pyramid for subtiles that are `factor` per degree.
+    pub fn new(subtiles: &[crate::max_subtile::MaxSubTile], factor: u32) -> Self {
+        let mut pyramid = Self {
+            factor: f64::from(factor),
+            subtiles: std::collections::HashMap::new(),
+            levels: Vec::new(),
+        };
+
+        let mut bottom = std::collections::HashMap::new();
+        for subtile in subtiles {
+            let cell = pyramid.cell_of(subtile);
+            pyramid.subtiles.entry(cell).or_default().push(*subtile);
+            bottom
+                .entry(cell)
+                .and_modify(|summary: &mut Summary| summary.merge(&Summary::of(*subtile)))
+                .or_insert_with(|| Summary::of(*subtile));
+        }
+        pyramid.levels.push(bottom);
+
+        // Merging cells always ends up at -1 or 0, either side of the equator and prime meridian,
+        // so there are at most 4 cells at the top.
+        let is_top =
+            |cell: &Cell| (-1i64..=0i64).contains(&cell.0) && (-1i64..=0i64).contains(&cell.1);
+        while let Some(below) = pyramid.levels.last()
+            && !below.keys().all(is_top)
+        {
+            let mut level: std::collections::HashMap<Cell, Summary> =
+                std::collections::HashMap::new();
+            #[expect(
+                clippy::iter_over_hash_type,
+                reason = "Cells are merged the same whatever order they come in"
+            )]
+            for ((x, y), summary) in below {
+                level
+                    .entry((x.div_euclid(2), y.div_euclid(2)))
+                    .and_modify(|parent| parent.merge(summary))
+                    .or_insert(*summary);
+            }
+            pyramid.levels.push(level);
+        }
+
+        tracing::debug!(
+            "Built a max subtile pyramid with {} levels",
+            pyramid.levels.len()
+        );
+        pyramid
+    }
+
+    /// The highest subtile inside an area. Subtiles on the area's edge count as inside.
+    pub fn highest_in(
+        &self,
+        area: &geo::MultiPolygon<f64>,
+    ) -> Option<crate::max_subtile::MaxSubTile> {
+        let bounds = Self::bounds_of(area);
+        let mut candidates = std::collections::BinaryHeap::new();
+        candidates.extend(
+            self.top_cells()
+                .map(|(level, cell, summary)| Candidate::Cell {
+                    level,
+                    cell,
+                    highest: summary.highest,
+                }),
+        );
+
+        while let Some(candidate) = candidates.pop() {
+            match candidate {
+                Candidate::Subtile(subtile) => {
+                    if Self::is_inside(area, &subtile) {
+                        return Some(subtile);
+                    }
+                }
+                Candidate::Cell { level, cell, .. } => {
+                    if !self.touches(level, cell, &bounds) {
+                        continue;
+                    }
+                    if level == 0 {
+                        let subtiles = self.subtiles.get(&cell).into_iter().flatten();
+                        candidates.extend(subtiles.copied().map(Candidate::Subtile));
+                    } else {
+                        candidates.extend(self.children(level, cell).map(|(child, summary)| {
+                            Candidate::Cell {
+                                level: level - 1,
+                                cell: child,
+                                highest: summary.highest,
+                            }
+                        }));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Whether any subtile inside an area isn't at sea level.
+    pub fn has_non_zero_in(&self, area: &geo::MultiPolygon<f64>) -> bool {
+        let bounds = Self::bounds_of(area);
+        let mut cells: Vec<(usize, Cell)> = self
+            .top_cells()
+            .filter(|(_, _, summary)| summary.has_non_zero)
+            .map(|(level, cell, _)| (level, cell))
+            .collect();
+
+        while let Some((level, cell)) = cells.pop() {
+            if !self.touches(level, cell, &bounds) {
+                continue;
+            }
+            if level == 0 {
+                let mut subtiles = self.subtiles.get(&cell).into_iter().flatten();
+                if subtiles
+                    .any(|subtile| subtile.max_height != 0i32 && Self::is_inside(area, subtile))
+                {
+                    return true;
+                }
+            } else {
+                cells.extend(
+                    self.children(level, cell)
+                        .filter(|(_, summary)| summary.has_non_zero)
+                        .map(|(child, _)| (level - 1, child)),
+                );
+            }
+        }
+
+        false
+    }
+
+    /// The cells of the top level of the pyramid.
+    fn top_cells(&self) -> impl Iterator<Item = (usize, Cell, &Summary)> {
+        let top = self.levels.len().saturating_sub(1);
+        self.levels
+            .last()
+            .into_iter()
+            .flatten()
+            .map(move |(cell, summary)| (top, *cell, summary))
+    }
+
+    /// The cells of the level below that are inside a cell.
+    fn children(&self, level: usize, (x, y): Cell) -> impl Iterator<Item = (Cell, &Summary)> {
+        let below = self.levels.get(level - 1);
+        [(0i64, 0i64), (1, 0), (0, 1), (1, 1)]
+            .into_iter()
+            .filter_map(move |(dx, dy)| {
+                let child = (x * 2i64 + dx, y * 2i64 + dy);
+                below?.get(&child).map(|summary| (child, summary))
+            })
+    }
+
+    /// The cell of the bottom level that a subtile is in.
+    #[expect(
+        clippy::as_conversions,
+        clippy::cast_possible_truncation,
+        reason = "Cells of real coordinates are much smaller than the types' limits"
+    )]
+    fn cell_of(&self, subtile: &crate::max_subtile::MaxSubTile) -> Cell {
+        (
+            (f64::from(subtile.lon) * self.factor).floor() as i64,
+            (f64::from(subtile.lat) * self.factor).floor() as i64,
+        )
+    }
+
+    /// Whether a cell touches any of an area's bounding boxes.
+    #[expect(
+        clippy::as_conversions,
+        clippy::cast_precision_loss,
+        reason = "Cells of real coordinates are much smaller than the types' limits"
+    )]
+    fn touches(&self, level: usize, (x, y): Cell, bounds: &[geo::Rect<f64>]) -> bool {
+        let size = f64::from(1u32 << level) / self.factor;
+        let cell = geo::Rect::new(
+            geo::coord! {
+                x: (x as f64).mul_add(size, -CELL_MARGIN),
+                y: (y as f64).mul_add(size, -CELL_MARGIN),
+            },
+            geo::coord! {
+                x: ((x + 1i64) as f64).mul_add(size, CELL_MARGIN),
+                y: ((y + 1i64) as f64).mul_add(size, CELL_MARGIN),
+            },
+        );
+        bounds.iter().any(|bound| bound.intersects(&cell))
+    }
+
+    /// The bounding boxes of each part of an area, like the parts either side of the
+    /// antimeridian.
+    fn bounds_of(area: &geo::MultiPolygon<f64>) -> Vec<geo::Rect<f64>> {
+        area.iter()
+            .filter_map(geo::BoundingRect::bounding_rect)
+            .collect()
+    }
+
+    /// Whether a subtile is inside an area, including on its edge.
+    fn is_inside(area: &geo::MultiPolygon<f64>, subtile: &crate::max_subtile::MaxSubTile) -> bool {
+        area.intersects(&geo::coord! {
+            x: f64::from(subtile.lon),
+            y: f64::from(subtile.lat),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A 4°x4° world of subtiles across the antimeridian, with bumpy heights and a sea in its
+    /// North.
+    fn world() -> Vec<crate::max_subtile::MaxSubTile> {
+        let mut subtiles = Vec::new();
+        for row in 0i16..40 {
+            for column in 0i16..40 {
+                let bumps =
+                    (i32::from(row) * 7919i32 + i32::from(column) * 104_729i32).rem_euclid(997);
+                let lon = 178.05 + f32::from(column) / 10.0;
+                subtiles.push(crate::max_subtile::MaxSubTile {
+                    lon: if lon > 180.0 { lon - 360.0 } else { lon },
+                    lat: -2.05 + f32::from(row) / 10.0,
+                    max_height: if row > 30 { 0 } else { bumps },
+                });
+            }
+        }
+        subtiles
+    }
+
+    fn square(west: f64, south: f64, east: f64, north: f64) -> geo::MultiPolygon<f64> {
+        geo::Rect::new(
+            geo::coord! { x: west, y: south },
+            geo::coord! { x: east, y: north },
+        )
+        .to_polygon()
+        .into()
+    }
+
+    /// The area of a tile that crosses the antimeridian, so it's split either side of it.
+    fn tile_across_the_antimeridian(lon: f64, lat: f64, width: f32) -> geo::MultiPolygon<f64> {
+        let tile = crate::tile::Tile {
+            centre: crate::projector::LonLatCoord(geo::coord! { x: lon, y: lat }),
+            width,
+        };
+        let area = tile.to_polygon_lonlat(crate::projector::Earth::Wgs84);
+        assert_eq!(area.0.len(), 2);
+        area
+    }
+
+    fn slowest_highest_in(
+        subtiles: &[crate::max_subtile::MaxSubTile],
+        area: &geo::MultiPolygon<f64>,
+    ) -> Option<crate::max_subtile::MaxSubTile> {
+        subtiles
+            .iter()
+            .filter(|subtile| Pyramid::is_inside(area, subtile))
+            .max_by(|left, right| compare(left, right))
+            .copied()
+    }
+
+    #[test]
+    fn finds_the_same_highest_as_searching_everything() {
+        let subtiles = world();
+        let pyramid = Pyramid::new(&subtiles, 10);
+        let areas = [
+            tile_across_the_antimeridian(180.0, 0.0, 400_000.0),
+            tile_across_the_antimeridian(179.9, -0.6, 60_000.0),
+            tile_across_the_antimeridian(-179.8, 1.2, 90_000.0),
+            square(179.23, -1.1, 179.51, -0.45),
+            square(-178.29, 1.05, -178.02, 1.95),
+        ];
+
+        for area in &areas {
+            assert!(slowest_highest_in(&subtiles, area).is_some(), "{area:?}");
+            assert_eq!(
+                pyramid.highest_in(area),
+                slowest_highest_in(&subtiles, area),
+                "{area:?}"
+            );
+        }
+        assert_eq!(pyramid.highest_in(&square(170.0, 10.0, 171.0, 11.0)), None);
+    }
+
+    #[test]
+    fn finds_whether_there_is_any_land() {
+        let pyramid = Pyramid::new(&world(), 10);
+        assert!(pyramid.has_non_zero_in(&tile_across_the_antimeridian(180.0, 0.0, 400_000.0)));
+        assert!(!pyramid.has_non_zero_in(&tile_across_the_antimeridian(180.0, 1.6, 50_000.0)));
+        assert!(!pyramid.has_non_zero_in(&square(170.0, 10.0, 171.0, 11.0)));
+    }
+}
@@ -0,0 +1,198 @@
+//! Statistics about every subtile, made in the same pass over the DEMs as the max subtiles.
+//!
+//! A `MaxSubTile` only has the highest elevation, and it's placed at the centre of the subtile.
+//! The statistics also have exactly where the highest pixel is, the lowest and mean elevations and
+//! how many of the pixels are voids. They're saved to their own file, eg `max_subtile_stats.bin`,
+//! so that the packer's input stays small.
+//!
+//! Subtiles that are entirely void don't have a max subtile, but they do still have statistics.
+//! So the statistics can be drawn as a map of where the DEMs are missing data, see
+//! `save_void_map()`.
+
+use color_eyre::Result;
+
+/// Statistics about the pixels of a single subtile.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct SubtileStats {
+    /// The longitude of the centre of the subtile.
+    pub lon: f32,
+    /// The latitude of the centre of the subtile.
+    pub lat: f32,
+    /// The longitude of the centre of the highest pixel.
+    pub peak_lon: f32,
+    /// The latitude of the centre of the highest pixel.
+    pub peak_lat: f32,
+    /// The highest elevation.
+    pub max_height: i32,
+    /// The lowest elevation.
+    pub min_height: i32,
+    /// The mean elevation of the pixels that aren't voids.
+    pub mean_height: f32,
+    /// How many pixels are in the subtile.
+    pub pixels: u32,
+    /// How many of the pixels are voids, either marked as nodata or not a number.
+    pub voids: u32,
+}
+
+impl crate::max_subtile::file::Record for SubtileStats {
+    const MAGIC: &'static [u8; 8] = b"SUBSTATS";
+}
+
+impl SubtileStats {
+    /// The statistics of a subtile that's entirely void. The peak is at the centre and all the
+    /// elevations are 0.
+    pub const fn void(lon: f32, lat: f32, pixels: u32) -> Self {
+        Self {
+            lon,
+            lat,
+            peak_lon: lon,
+            peak_lat: lat,
+            max_height: 0,
+            min_height: 0,
+            mean_height: 0.0,
+            pixels,
+            voids: pixels,
+        }
+    }
+
+    /// The fraction of the subtile's pixels that are voids, from 0 to 1.
+    #[expect(
+        clippy::as_conversions,
+        clippy::cast_possible_truncation,
+        reason = "It's only a fraction"
+    )]
+    pub fn void_fraction(&self) -> f32 {
+        if self.pixels == 0 {
+            return 1.0;
+        }
+        (f64::from(self.voids) / f64::from(self.pixels)) as f32
+    }
+
+    /// The max subtile, unless the subtile is entirely void.
+    pub const fn max_subtile(&self) -> Option<crate::max_subtile::MaxSubTile> {
+        if self.voids >= self.pixels {
+            return None;
+        }
+        Some(crate::max_subtile::MaxSubTile {
+            lon: self.lon,
+            lat: self.lat,
+            max_height: self.max_height,
+        })
+    }
+}
+
+/// The most pixels per degree of the void map. At the highest `--factor` of 3600 a pixel per
+/// subtile would be about 1.3M by 650k pixels, so finer subtiles are downsampled to this.
+pub const MAXIMUM_VOID_MAP_PIXELS_PER_DEGREE: u32 = 10;
+
+/// Draw how much of every subtile has data, as a PNG of the whole world.
+///
+/// There's one pixel per subtile in an equirectangular projection, or per group of subtiles when
+/// there are more than `MAXIMUM_VOID_MAP_PIXELS_PER_DEGREE` subtiles per degree. The pixels are
+/// greyscale, from white where the subtiles have no voids to black where they're entirely void,
+/// and are transparent where there isn't any DEM.
+pub fn save_void_map(stats: &[SubtileStats], factor: u32, path: &std::path::Path) -> Result<()> {
+    let pixels_per_degree = factor.min(MAXIMUM_VOID_MAP_PIXELS_PER_DEGREE);
+    let (width, height) = (360 * pixels_per_degree, 180 * pixels_per_degree);
+    let pixels = draw_void_map(stats, pixels_per_degree)?;
+
+    let writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::GrayscaleAlpha);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()?
+        .write_image_data(pixels.as_flattened())?;
+
+    tracing::info!("Saved {width}x{height} void map to: {path:?}");
+    Ok(())
+}
+
+/// The grey and alpha of every pixel of the void map, row by row from the North West corner. When
+/// a pixel covers more than one subtile, its grey is from all of their voids together.
+#[expect(
+    clippy::as_conversions,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss,
+    reason = "Subtiles are always within the world, and the grey is always from 0 to 255"
+)]
+fn draw_void_map(stats: &[SubtileStats], pixels_per_degree: u32) -> Result<Vec<[u8; 2]>> {
+    let width = usize::try_from(360 * pixels_per_degree)?;
+    let height = usize::try_from(180 * pixels_per_degree)?;
+    // The voids and pixels of all the subtiles in each pixel of the map.
+    let mut counts: Vec<Option<(u64, u64)>> = vec![None; width * height];
+
+    for subtile in stats {
+        let scale = f64::from(pixels_per_degree);
+        let column = ((f64::from(subtile.lon) + 180.0f64) * scale).floor() as i64;
+        let row = ((90.0f64 - f64::from(subtile.lat)) * scale).floor() as i64;
+        // DEMs sometimes go a little past the antimeridian.
+        let Ok(wrapped) = usize::try_from(column.rem_euclid(i64::try_from(width)?)) else {
+            continue;
+        };
+        let Some(count) = usize::try_from(row)
+            .ok()
+            .filter(|row_index| *row_index < height)
+            .and_then(|row_index| counts.get_mut(row_index * width + wrapped))
+        else {
+            continue;
+        };
+        let (voids, pixels) = count.get_or_insert((0, 0));
+        *voids += u64::from(subtile.voids.min(subtile.pixels));
+        *pixels += u64::from(subtile.pixels);
+    }
+
+    Ok(counts
+        .into_iter()
+        .map(|count| match count {
+            None => [0, 0],
+            Some((_, 0)) => [0, u8::MAX],
+            Some((voids, pixels)) => {
+                let data = 1.0f64 - voids as f64 / pixels as f64;
+                [(data * f64::from(u8::MAX)).round() as u8, u8::MAX]
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn void_map_shades_subtiles_by_their_voids() {
+        let stats = [
+            SubtileStats::void(-179.95, 89.95, 100),
+            SubtileStats {
+                voids: 25,
+                max_height: 42,
+                ..SubtileStats::void(179.95, -89.95, 100)
+            },
+        ];
+        let pixels = draw_void_map(&stats, 10).unwrap();
+
+        assert_eq!(pixels.len(), 3600 * 1800);
+        assert_eq!(pixels[0], [0, 255]);
+        assert_eq!(pixels[3600 * 1800 - 1], [191, 255]);
+        assert_eq!(pixels[1], [0, 0]);
+        assert_eq!(stats[0].max_subtile(), None);
+        assert_eq!(stats[1].max_subtile().unwrap().max_height, 42i32);
+    }
+
+    #[test]
+    fn void_map_downsamples_fine_subtiles() {
+        let stats = [
+            SubtileStats {
+                voids: 0,
+                ..SubtileStats::void(0.0125, 0.0125, 100)
+            },
+            SubtileStats::void(0.0375, 0.0125, 100),
+        ];
+        let pixels = draw_void_map(&stats, MAXIMUM_VOID_MAP_PIXELS_PER_DEGREE).unwrap();
+
+        assert_eq!(pixels.len(), 3600 * 1800);
+        assert_eq!(pixels[899 * 3600 + 1800], [128, 255]);
+    }
+}
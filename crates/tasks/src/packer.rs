@@ -6,17 +6,34 @@
 //!   3. That each tile is big enough to contain the theoretically longest line of sight of its
 //!      highest point.
 
+pub mod checkpoint;
+pub mod cost;
+pub mod region;
+pub mod render;
+pub mod stats;
+pub mod verify;
+
+#[cfg(test)]
+mod scenarios;
+
 use std::{collections::VecDeque, panic};
 
 use color_eyre::{Result, eyre::ContextCompat as _};
-use geo::{Area as _, BooleanOps as _, Contains as _, Intersects as _, Within as _};
+use geo::{Area as _, BooleanOps as _, Contains as _, Intersects as _};
 use rstar::PointDistance as _;
 
 use crate::projector::LonLatCoord;
 
-/// Where to save the final packed tiles output.
+/// Where to save the final packed tiles output when there's no `--output`.
 const TILES_OUTPUT: &str = "output/tiles.csv";
 
+/// The max subtiles of the whole world, made by `max_subtile.rs`.
+const MAX_SUBTILES: &str = "max_subtiles.bin";
+
+/// How many max subtiles there are per degree. The packer only works with subtiles of this
+/// resolution, see `nudge_extend_tile()`.
+const SUBTILES_PER_DEGREE: u32 = 10;
+
 /// The minimum elevation inside a tile to start considering larger tiles from. Another way of
 /// looking at this is rather the minimum _width_ of tile, as tile widths are primarilly dictated
 /// by the maximum point of elevation inside them. We don't use 0m because in fact the lowest
@@ -36,12 +53,37 @@ const WINDOW_RADIUS: f64 = 1_800_000.0;
 /// The distance that each window moves.
 const WINDOW_STEP: f64 = WINDOW_RADIUS / 2.0f64;
 
+/// The biggest a tile can ever be. It's a little bigger than the longest theoretical line of sight
+/// from Everest, even with a fair amount of refraction. Everest needs a 672km tile without any
+/// refraction, and a 720km tile with the default refraction of 0.13. So this leaves room for
+/// refraction coefficients up to about 0.29, above that the packer refuses to run.
+const MAXIMUM_TILE_WIDTH: f32 = 800_000.0;
+
+/// The height of Everest, the highest point on earth.
+const HIGHEST_ELEVATION: i32 = 8848;
+
 /// A single point of elevation that represents the highest elevation within the resolution range of
 /// the point. The resolution is defined by another process, `max_subtile.rs`.
 type PointRstar = rstar::primitives::GeomWithData<LonLatCoord, i32>;
 /// How we store tiles for fast lookups.
 pub type TileRstar = rstar::primitives::GeomWithData<LonLatCoord, crate::tile::Tile>;
 
+/// A band of latitudes that can be packed independently of the rest of the globe.
+#[derive(Debug, Clone, Copy)]
+struct Band {
+    /// The latitude of the band's first, most northerly, row of windows.
+    north: f64,
+    /// The latitude of the band's last, most southerly, row of windows.
+    south: f64,
+}
+
+impl Band {
+    /// Whether a row of windows is in the band.
+    fn contains(self, latitude: f64) -> bool {
+        (self.south..=self.north).contains(&latitude)
+    }
+}
+
 /// A packer of tiles.
 pub struct Packer {
     /// Config from the CLI.
@@ -54,8 +96,12 @@ pub struct Packer {
     ///      constructing the tile's actual corners. The simple act of adding a distance to a point
     ///      must be done in as local as possible projection.
     projector: crate::projector::Convert,
-    /// An unchanging canonical reference of all the world's maximum elevation points.
-    canonical: rstar::RTree<PointRstar>,
+    /// An unchanging canonical reference of all the world's maximum elevation points. It's shared
+    /// between the packers of each band when packing in parallel.
+    canonical: std::sync::Arc<rstar::RTree<PointRstar>>,
+    /// The same points as `canonical`, but for quickly finding the highest point in a tile. It's
+    /// also shared between the packers of each band.
+    pyramid: std::sync::Arc<crate::max_subtile::pyramid::Pyramid>,
     /// A stack of points, each of which must at some point be proven to fall within a tile.
     stack: VecDeque<PointRstar>,
     /// Keep track of all the points that we've contained by tiles. Useful for not repeating points
@@ -63,48 +109,155 @@ pub struct Packer {
     stack_history: rstar::RTree<PointRstar>,
     /// All the currently found tiles for the world.
     tiles: rstar::RTree<TileRstar>,
+    /// Tiles that must be kept exactly as they are, like the tiles around a region being repacked.
+    frozen: rstar::RTree<TileRstar>,
+    /// The only band of the globe that this packer packs, when packing in parallel.
+    band: Option<Band>,
+    /// A hash of the max subtiles, saved with the tiles so we know what they were packed from.
+    input_hash: String,
+    /// Where to save the tiles. Nothing is saved when packing in memory, like in tests.
+    output: Option<std::path::PathBuf>,
 }
 
 impl Packer {
-    /// Instantiate.
+    /// Instantiate with the max subtiles of the whole world.
     pub fn new(config: crate::config::Packer) -> Result<Self> {
+        let max_subtiles = crate::max_subtile::Subtiler::load(
+            std::path::Path::new(MAX_SUBTILES),
+            config.legacy_subtiles,
+        )?;
+        if max_subtiles.metadata.factor != SUBTILES_PER_DEGREE {
+            color_eyre::eyre::bail!(
+                "{MAX_SUBTILES} has {} subtiles per degree, but the packer only works with \
+                {SUBTILES_PER_DEGREE}. Remake them with `max-sub-tiles --factor {SUBTILES_PER_DEGREE}`",
+                max_subtiles.metadata.factor
+            );
+        }
+        tracing::info!(
+            "Loaded {} max subtiles made from {:?}.",
+            max_subtiles.subtiles.len(),
+            max_subtiles.metadata.dems
+        );
+
+        let output = config
+            .output
+            .clone()
+            .unwrap_or_else(|| std::path::PathBuf::from(TILES_OUTPUT));
+        let mut packer = Self::from_subtiles(config, &max_subtiles.subtiles)?;
+        packer.output = Some(output);
+        Ok(packer)
+    }
+
+    /// Instantiate with any max subtiles, without saving anything. Useful for packing small
+    /// synthetic worlds.
+    pub fn from_subtiles(
+        config: crate::config::Packer,
+        subtiles: &[crate::max_subtile::MaxSubTile],
+    ) -> Result<Self> {
+        let everest_width =
+            Self::minimum_tile_size_for_elevation(HIGHEST_ELEVATION, &config.line_of_sight);
+        if everest_width > MAXIMUM_TILE_WIDTH {
+            color_eyre::eyre::bail!(
+                "Everest would need a {everest_width}m tile, which is bigger than the biggest \
+                allowed tile of {MAXIMUM_TILE_WIDTH}m. Try less refraction or lower heights."
+            );
+        }
+
         Ok(Self {
             config,
-            canonical: Self::build_canonical_rtree()?,
+            canonical: std::sync::Arc::new(Self::build_canonical_rtree(subtiles)),
+            pyramid: std::sync::Arc::new(crate::max_subtile::pyramid::Pyramid::new(
+                subtiles,
+                SUBTILES_PER_DEGREE,
+            )),
             // window: rstar::RTree::new(),
             stack_history: rstar::RTree::new(),
             stack: VecDeque::new(),
             // tiles: rstar::RTree::new(),
             tiles: rstar::RTree::new(),
+            frozen: rstar::RTree::new(),
             projector: crate::projector::Convert {
                 base: crate::projector::LonLatCoord(geo::Coord::zero()),
             },
+            band: None,
+            input_hash: crate::tiles::hash_subtiles(subtiles),
+            output: None,
         })
     }
 
+    /// Make a packer for just one band of the globe, sharing this packer's canonical points.
+    fn for_band(&self, band: Band) -> Self {
+        Self {
+            config: self.config.clone(),
+            canonical: std::sync::Arc::clone(&self.canonical),
+            pyramid: std::sync::Arc::clone(&self.pyramid),
+            stack_history: rstar::RTree::new(),
+            stack: VecDeque::new(),
+            tiles: rstar::RTree::new(),
+            frozen: rstar::RTree::new(),
+            projector: crate::projector::Convert {
+                base: crate::projector::LonLatCoord(geo::Coord::zero()),
+            },
+            band: Some(band),
+            input_hash: self.input_hash.clone(),
+            output: self.output.clone(),
+        }
+    }
+
     /// Run for the entire globe.
     pub fn run_all(&mut self) -> Result<()> {
-        // We don't go all the way to ±90 because the current (October 2025) implementation doesn't
-        // work well when a tile crosses the North or South poles. 85°N is roughly the
-        // northern-most part of Greenland, beyond which there isn't really any more land. And we
-        // assume that beyond 85°S, which is solely in Antartica, doesn't have the longest line of
-        // sight on the planet.
-        let max_latitude = 85.0f64;
+        if self.config.bands > 1 && self.band.is_none() {
+            return self.run_bands();
+        }
+        if let (Some(region), Some(base)) = (self.config.region.clone(), self.config.base.clone()) {
+            return self.run_region(&region, &base);
+        }
+
+        let max_latitude = 90.0f64;
 
         let max_longitude = 180.0f64;
         let default_start = (-max_longitude, max_latitude);
-        let mut centre = LonLatCoord(self.config.start.unwrap_or(default_start).into());
+        let band_start = self.band.map(|band| (-max_longitude, band.north));
+        let mut centre = LonLatCoord(
+            band_start
+                .or(self.config.start)
+                .unwrap_or(default_start)
+                .into(),
+        );
 
         let mut steps = 0u32;
-        while (-max_latitude..=max_latitude).contains(&centre.0.y) {
+        let mut is_last_longitude = false;
+        if let Some(path) = &self.config.resume {
+            let resumed = checkpoint::Checkpoint::load(path)?;
+            if resumed.state.earth != self.config.earth {
+                color_eyre::eyre::bail!(
+                    "The checkpoint in {path:?} was packed on {:?}, but this run is on {:?}",
+                    resumed.state.earth,
+                    self.config.earth
+                );
+            }
+            centre = resumed.state.centre;
+            steps = resumed.state.steps;
+            is_last_longitude = resumed.state.is_last_longitude;
+            self.restore(resumed);
+        }
+
+        while (-max_latitude..=max_latitude).contains(&centre.0.y) && self.is_in_band(centre.0.y) {
             if let Some(stop_at_step) = self.config.steps
                 && steps >= stop_at_step
             {
                 break;
             }
 
-            let longtitude_step = Self::calculate_longtitude_step(centre.0.y);
-            let mut is_last_longitude = false;
+            // Near the poles a single window centred on the pole covers the whole row.
+            let is_polar_row = Self::is_polar_latitude(centre.0.y, self.config.earth);
+            if is_polar_row {
+                centre =
+                    LonLatCoord(geo::coord! { x: 0.0f64, y: max_latitude.copysign(centre.0.y) });
+                is_last_longitude = true;
+            }
+
+            let longtitude_step = Self::calculate_longtitude_step(centre.0.y, self.config.earth);
             while (-max_longitude..=max_longitude).contains(&centre.0.x) {
                 if let Some(stop_at_step) = self.config.steps
                     && steps >= stop_at_step
@@ -117,7 +270,9 @@ impl Packer {
                 self.run_one(centre)?;
                 self.nudge_extend_window_tiles()?;
                 self.remove_inefficient_tiles()?;
-                self.save_tiles_as_csv()?;
+                if self.band.is_none() {
+                    self.save_tiles_as_csv(false)?;
+                }
                 if is_last_longitude {
                     break;
                 }
@@ -133,22 +288,356 @@ impl Packer {
                 }
 
                 steps += 1;
+                if self.band.is_none()
+                    && self.output.is_some()
+                    && steps.is_multiple_of(self.config.checkpoint_every)
+                {
+                    self.save_checkpoint(centre, is_last_longitude, steps)?;
+                }
             }
             let Some(updated_centre) = Self::start_new_latitude(centre)? else {
                 break;
             };
             centre = updated_centre;
+            is_last_longitude = false;
+        }
+
+        if self.band.is_none() {
+            self.save_tiles_as_csv(true)?;
         }
 
         Ok(())
     }
 
+    /// Pack the globe in parallel, with a thread for each band of latitudes.
+    ///
+    /// Each band has its own tiles and stack history, so the bands can't interact. That's only
+    /// safe because the bands are far enough apart that none of their tiles can touch, see
+    /// `seam_rows()`. Once all the bands are packed, their tiles and stack histories are merged,
+    /// and then the rows of windows in the seams between the bands are packed, just like any
+    /// other row.
+    fn run_bands(&mut self) -> Result<()> {
+        let bands = Self::bands(self.config.bands, self.config.earth)?;
+        tracing::info!("Packing {} bands in parallel: {bands:?}", bands.len());
+
+        let packers: Vec<Self> = bands.iter().map(|band| self.for_band(*band)).collect();
+        let results: Vec<Result<Self>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = packers
+                .into_iter()
+                .enumerate()
+                .map(|(index, mut packer)| {
+                    scope.spawn(move || {
+                        let _span = tracing::info_span!("band", index).entered();
+                        packer.run_all()?;
+                        Ok(packer)
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|error| panic::resume_unwind(error))
+                })
+                .collect()
+        });
+
+        let mut tiles = Vec::new();
+        let mut stack_history = Vec::new();
+        for result in results {
+            let band = result?;
+            tiles.extend(band.tiles);
+            stack_history.extend(band.stack_history);
+        }
+        self.tiles = rstar::RTree::bulk_load(tiles);
+        self.stack_history = rstar::RTree::bulk_load(stack_history);
+
+        let seams = Self::rows(self.config.earth)?
+            .into_iter()
+            .filter(|latitude| !bands.iter().any(|band| band.contains(*latitude)));
+        for latitude in seams {
+            tracing::info!("Packing the seam between bands at latitude: {latitude}");
+            for centre in Self::row_windows(latitude, self.config.earth) {
+                self.run_one(centre)?;
+                self.nudge_extend_window_tiles()?;
+                self.remove_inefficient_tiles()?;
+            }
+        }
+
+        self.save_tiles_as_csv(true)
+    }
+
+    /// Repack just a region of an existing packing.
+    ///
+    /// All the tiles that touch the region are removed, and all the points they covered are packed
+    /// again. Every other point is treated as already packed. Tiles that don't even touch the
+    /// repacked area are frozen, so they're never nudged, grown or removed, and the seams with
+    /// them stay valid.
+    fn run_region(&mut self, region: &crate::config::Region, base: &std::path::Path) -> Result<()> {
+        let region_polygon = region::to_polygon(region)?;
+        let base_file = crate::tiles::File::load(base)?;
+        if base_file.metadata.earth() != self.config.earth {
+            color_eyre::eyre::bail!(
+                "The base tiles in {base:?} were packed on {:?}, but this repack is on {:?}",
+                base_file.metadata.earth(),
+                self.config.earth
+            );
+        }
+        let base_tiles: Vec<crate::tile::Tile> =
+            base_file.tiles.iter().map(|packed| packed.tile).collect();
+
+        let (repack_area, kept) =
+            region::repack_area(&region_polygon, &base_tiles, self.config.earth);
+        let frozen = kept
+            .iter()
+            .filter(|tile| {
+                !tile
+                    .to_polygon_lonlat(self.config.earth)
+                    .intersects(&repack_area)
+            })
+            .map(|tile| TileRstar::new(tile.centre, *tile))
+            .collect();
+        tracing::info!(
+            "Repacking {} of {} tiles in region: {region:?}",
+            base_tiles.len() - kept.len(),
+            base_tiles.len()
+        );
+        self.tiles = rstar::RTree::bulk_load(
+            kept.iter()
+                .map(|tile| TileRstar::new(tile.centre, *tile))
+                .collect(),
+        );
+        self.frozen = rstar::RTree::bulk_load(frozen);
+
+        let bounds = geo::BoundingRect::bounding_rect(&repack_area)
+            .context("Region doesn't have any area")?;
+        let packed = self
+            .canonical
+            .iter()
+            .filter(|point| {
+                !(bounds.intersects(&point.geom().0) && repack_area.intersects(&point.geom().0))
+            })
+            .copied()
+            .collect();
+        self.stack_history = rstar::RTree::bulk_load(packed);
+
+        #[expect(
+            clippy::as_conversions,
+            clippy::cast_possible_truncation,
+            reason = "The window is much smaller than `f32::MAX`"
+        )]
+        let window_width = (WINDOW_STEP * 2.0f64) as f32;
+        for latitude in Self::rows(self.config.earth)? {
+            for centre in Self::row_windows(latitude, self.config.earth) {
+                let window = crate::tile::Tile {
+                    centre,
+                    width: window_width,
+                };
+                if !window
+                    .to_polygon_lonlat(self.config.earth)
+                    .intersects(&repack_area)
+                {
+                    continue;
+                }
+
+                tracing::debug!("Repacking window centred at: {centre:?}");
+                self.run_one(centre)?;
+                self.nudge_extend_window_tiles()?;
+                self.remove_inefficient_tiles()?;
+            }
+        }
+
+        self.save_tiles_as_csv(true)?;
+        let tiles: Vec<crate::tile::Tile> = self.tiles.iter().map(|tile| tile.data).collect();
+        let diff = crate::tiles::diff::Diff::new(
+            &base_tiles,
+            &tiles,
+            crate::tiles::diff::DEFAULT_TOLERANCE,
+            self.config.earth,
+        );
+        tracing::info!("Repacked region. {}", diff.summary());
+        let diff_output = self
+            .config
+            .diff_output
+            .clone()
+            .or_else(|| self.output.as_deref().map(region::default_diff_output));
+        if let Some(path) = diff_output {
+            diff.save(&path)?;
+        }
+
+        Ok(())
+    }
+
+    /// The latitudes of all the rows of windows that cover the globe, from North to South.
+    fn rows(earth: crate::projector::Earth) -> Result<Vec<f64>> {
+        let mut rows = Vec::new();
+        let mut centre = LonLatCoord(geo::coord! { x: -180.0f64, y: 90.0f64 });
+        loop {
+            if Self::is_polar_latitude(centre.0.y, earth) {
+                centre.0.y = 90.0f64.copysign(centre.0.y);
+            }
+            rows.push(centre.0.y);
+            let Some(updated_centre) = Self::start_new_latitude(centre)? else {
+                break;
+            };
+            centre = updated_centre;
+        }
+
+        Ok(rows)
+    }
+
+    /// Split the rows of windows that cover the globe into bands of neighbouring rows, with
+    /// `seam_rows()` rows left out between each band.
+    fn bands(count: u32, earth: crate::projector::Earth) -> Result<Vec<Band>> {
+        let rows = Self::rows(earth)?;
+        let band_count = usize::try_from(count)?;
+        let seam_rows = Self::seam_rows();
+        let Some(band_rows) = rows
+            .len()
+            .checked_sub(band_count.saturating_sub(1) * seam_rows)
+            .filter(|band_rows| *band_rows >= band_count)
+        else {
+            color_eyre::eyre::bail!(
+                "There are only {} rows of windows, which isn't enough for {count} bands with \
+                {seam_rows} rows between each of them",
+                rows.len()
+            );
+        };
+
+        let mut bands = Vec::new();
+        for index in 0..band_count {
+            let offset = index * seam_rows;
+            let first = (index * band_rows).div_euclid(band_count) + offset;
+            let last = ((index + 1) * band_rows).div_euclid(band_count) + offset - 1;
+            if let (Some(north), Some(south)) = (rows.get(first), rows.get(last)) {
+                bands.push(Band {
+                    north: *north,
+                    south: *south,
+                });
+            }
+        }
+
+        Ok(bands)
+    }
+
+    /// How many rows of windows to leave between bands, so that the bands can't interact.
+    ///
+    /// The points that a band packs are all within `WINDOW_STEP` of its rows, and its tiles are
+    /// centred on those points. So a band's tiles reach at most `WINDOW_STEP` plus half of
+    /// `MAXIMUM_TILE_WIDTH` past its first and last rows. Therefore the tiles of 2 bands can't
+    /// touch as long as their rows are at least `WINDOW_RADIUS + MAXIMUM_TILE_WIDTH` apart. Rows
+    /// are `WINDOW_STEP` apart, or further near the poles.
+    fn seam_rows() -> usize {
+        let buffer = WINDOW_RADIUS + f64::from(MAXIMUM_TILE_WIDTH);
+
+        #[expect(
+            clippy::as_conversions,
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            reason = "It's only a handful of rows"
+        )]
+        let seam_rows = ((buffer / WINDOW_STEP).ceil() as usize).saturating_sub(1);
+
+        seam_rows
+    }
+
+    /// Whether a row of windows belongs to this packer. Always true when not packing in bands.
+    fn is_in_band(&self, latitude: f64) -> bool {
+        self.band.is_none_or(|band| latitude >= band.south)
+    }
+
+    /// The centres of all the windows in a row, from West to East.
+    fn row_windows(latitude: f64, earth: crate::projector::Earth) -> Vec<LonLatCoord> {
+        if Self::is_polar_latitude(latitude, earth) {
+            return vec![LonLatCoord(
+                geo::coord! { x: 0.0f64, y: 90.0f64.copysign(latitude) },
+            )];
+        }
+
+        let step = Self::calculate_longtitude_step(latitude, earth);
+        let mut windows: Vec<LonLatCoord> =
+            std::iter::successors(Some(-180.0f64), |longitude| Some(longitude + step))
+                .take_while(|longitude| *longitude < 180.0f64)
+                .map(|longitude| LonLatCoord(geo::coord! { x: longitude, y: latitude }))
+                .collect();
+        windows.push(LonLatCoord(geo::coord! { x: 180.0f64, y: latitude }));
+
+        windows
+    }
+
+    /// Save everything needed to resume the run from the given window.
+    fn save_checkpoint(
+        &self,
+        centre: LonLatCoord,
+        is_last_longitude: bool,
+        steps: u32,
+    ) -> Result<()> {
+        let mut stack_history = Vec::with_capacity(self.stack_history.size());
+        for point in &self.stack_history {
+            #[expect(
+                clippy::as_conversions,
+                clippy::cast_possible_truncation,
+                reason = "The points were originally `f32`s from the max subtiles file"
+            )]
+            stack_history.push(crate::max_subtile::MaxSubTile {
+                lon: point.geom().0.x as f32,
+                lat: point.geom().0.y as f32,
+                max_height: point.data,
+            });
+        }
+
+        let checkpoint = checkpoint::Checkpoint {
+            state: checkpoint::State {
+                centre,
+                is_last_longitude,
+                steps,
+                tiles: self.tiles.iter().map(|tile| tile.data).collect(),
+                earth: self.config.earth,
+            },
+            stack_history,
+        };
+        checkpoint.save(&self.config.checkpoint)
+    }
+
+    /// Restore the tiles and stack history of a previous run.
+    fn restore(&mut self, checkpoint: checkpoint::Checkpoint) {
+        let tiles = checkpoint
+            .state
+            .tiles
+            .into_iter()
+            .map(|tile| TileRstar::new(tile.centre, tile))
+            .collect();
+        self.tiles = rstar::RTree::bulk_load(tiles);
+
+        let points = checkpoint
+            .stack_history
+            .iter()
+            .map(Self::subtile_to_point)
+            .collect();
+        self.stack_history = rstar::RTree::bulk_load(points);
+    }
+
     /// Calculate the number of degrees to go Eastward to reach the next window.
-    fn calculate_longtitude_step(latitude: f64) -> f64 {
-        let degrees_per_meter = 1.0 / crate::projector::Convert::meters_per_degree(latitude);
+    fn calculate_longtitude_step(latitude: f64, earth: crate::projector::Earth) -> f64 {
+        let degrees_per_meter = 1.0 / crate::projector::Convert::meters_per_degree(earth, latitude);
         f64::from(degrees_per_meter) * WINDOW_STEP
     }
 
+    /// Whether a row of windows at the given latitude is close enough to a pole that it can be
+    /// covered by a single window centred on the pole.
+    ///
+    /// It's only half a step, because the row after the polar row is a whole step from the pole,
+    /// and degrees of latitude near the poles are longer than at the equator. So with a whole step
+    /// that next row would also count as polar, and we'd never leave the pole.
+    fn is_polar_latitude(latitude: f64, earth: crate::projector::Earth) -> bool {
+        let degrees_to_pole = 90.0f64 - latitude.abs();
+        let meters_per_degree_of_latitude =
+            f64::from(crate::projector::Convert::meters_per_degree(earth, 0.0));
+        degrees_to_pole * meters_per_degree_of_latitude < WINDOW_STEP / 2.0f64
+    }
+
     /// Do a sort of "carriage return" to the next latitude South.
     fn start_new_latitude(current: LonLatCoord) -> Result<Option<LonLatCoord>> {
         let projector = crate::projector::Convert { base: current };
@@ -171,7 +660,7 @@ impl Packer {
         if self.config.one.is_some() {
             self.nudge_extend_window_tiles()?;
             self.remove_inefficient_tiles()?;
-            self.save_tiles_as_csv()?;
+            self.save_tiles_as_csv(true)?;
         }
 
         Ok(())
@@ -183,7 +672,9 @@ impl Packer {
         self.projector = crate::projector::Convert { base: centre };
 
         tracing::debug!("Ordering around coordinate: {centre:?}");
-        for canonical_point in self.canonical.nearest_neighbor_iter(&centre) {
+        for canonical_point in
+            Self::nearest_first(&self.canonical, centre, WINDOW_RADIUS, self.config.earth)?
+        {
             let projected = self.projector.to_meters(*canonical_point.geom())?;
             let is_new_point = !self.stack_history.contains(canonical_point);
             let distance = projected.distance_2(&geo::Coord::zero()).sqrt();
@@ -202,9 +693,12 @@ impl Packer {
     /// Get just the tiles in the current window.
     fn tiles_in_window(&self) -> Result<Vec<TileRstar>> {
         let mut tiles = Vec::new();
-        let iterator = self
-            .tiles
-            .nearest_neighbor_iter(&self.current_window_centre());
+        let iterator = Self::nearest_first(
+            &self.tiles,
+            self.current_window_centre(),
+            WINDOW_RADIUS,
+            self.config.earth,
+        )?;
 
         for tile in iterator {
             let distance = self
@@ -221,33 +715,88 @@ impl Packer {
         Ok(tiles)
     }
 
+    /// Iterate over everything in a tree of lon/lat points, nearest first, out to at least `radius`
+    /// meters from `centre`.
+    ///
+    /// `rstar`'s nearest neighbour search measures distances in degrees. That's a good enough
+    /// approximation until the search area reaches a pole or the antimeridian, where points can be
+    /// close together but have longitudes up to 360° apart. So there we search the bounding boxes
+    /// of the area and sort by real distances instead.
+    fn nearest_first<'tree, T>(
+        tree: &'tree rstar::RTree<rstar::primitives::GeomWithData<LonLatCoord, T>>,
+        centre: LonLatCoord,
+        radius: f64,
+        earth: crate::projector::Earth,
+    ) -> Result<
+        Box<dyn Iterator<Item = &'tree rstar::primitives::GeomWithData<LonLatCoord, T>> + 'tree>,
+    > {
+        #[expect(
+            clippy::as_conversions,
+            clippy::cast_possible_truncation,
+            reason = "Tile widths are `f32`"
+        )]
+        let area = crate::tile::Tile {
+            centre,
+            width: (radius * 2.0) as f32,
+        };
+        let bounds = area.rough_bounds_lonlat(earth);
+        let is_wrapped = bounds.min().x >= -180.0f64 && bounds.max().x <= 180.0f64;
+        if area.enclosed_pole(earth).is_none() && is_wrapped {
+            return Ok(Box::new(tree.nearest_neighbor_iter(&centre)));
+        }
+
+        let mut nearby = Vec::new();
+        for aabb in area.to_aabbs_lonlat(earth)? {
+            nearby.extend(tree.locate_in_envelope_intersecting(&aabb));
+        }
+        nearby.sort_by(|left, right| {
+            earth
+                .distance(centre, *left.geom())
+                .total_cmp(&earth.distance(centre, *right.geom()))
+        });
+
+        Ok(Box::new(nearby.into_iter()))
+    }
+
+    /// Iterate over the tiles near to a tile, nearest first. Far enough out to find every tile that
+    /// could overlap it.
+    fn neighbours<'tree>(
+        tiles: &'tree rstar::RTree<TileRstar>,
+        tile: &crate::tile::Tile,
+        earth: crate::projector::Earth,
+    ) -> Result<Box<dyn Iterator<Item = &'tree TileRstar> + 'tree>> {
+        Self::nearest_first(
+            tiles,
+            tile.centre,
+            f64::from(tile.width + MAXIMUM_TILE_WIDTH),
+            earth,
+        )
+    }
+
     /// The centre of the current window.
     const fn current_window_centre(&self) -> LonLatCoord {
         self.projector.base
     }
 
-    /// Load the acceleration structire of maximum elevation subtiles for the whole world.
-    fn build_canonical_rtree() -> Result<rstar::RTree<PointRstar>> {
-        let mut points: Vec<PointRstar> = Vec::new();
-        // TODO: make the file name configurable.
-        let subtiles = crate::max_subtile::Subtiler::load("max_subtiles.bin")?;
-        let total_points = subtiles.len();
-
-        tracing::info!("Loading {} max subtiles.", total_points);
-        for subtile in subtiles {
-            points.push(PointRstar::new(
-                LonLatCoord(geo::coord! { x: subtile.lon.into(), y: subtile.lat.into()}),
-                subtile.max_height,
-            ));
-        }
+    /// Build the acceleration structure of maximum elevation subtiles.
+    fn build_canonical_rtree(
+        subtiles: &[crate::max_subtile::MaxSubTile],
+    ) -> rstar::RTree<PointRstar> {
+        let points: Vec<PointRstar> = subtiles.iter().map(Self::subtile_to_point).collect();
 
         tracing::info!(
             "Creating canonical RTree from {} max elevation points",
-            total_points
+            points.len()
         );
-        let tree: rstar::RTree<_> = rstar::RTree::bulk_load(points);
+        rstar::RTree::bulk_load(points)
+    }
 
-        Ok(tree)
+    /// Convert a max subtile to a point in the `rstar` trees.
+    fn subtile_to_point(subtile: &crate::max_subtile::MaxSubTile) -> PointRstar {
+        PointRstar::new(
+            LonLatCoord(geo::coord! { x: subtile.lon.into(), y: subtile.lat.into()}),
+            subtile.max_height,
+        )
     }
 
     /// Loop over all the points in a window.
@@ -277,8 +826,8 @@ impl Packer {
         tracing::debug!("Finding non-overlapping tile for point: {centre:?}");
         let mut highest = centre;
         loop {
-            let tile = Self::find_minimum_tile_for_point(centre.geom(), highest.data);
-            let Some((candidate, covered_points)) = self.find_highest_point_in_tile(&tile)? else {
+            let tile = self.find_minimum_tile_for_point(centre.geom(), highest.data);
+            let Some(candidate) = self.find_highest_point_in_tile(&tile) else {
                 // Ignore tiles with just zeroes in them.
                 return Ok(());
             };
@@ -291,8 +840,9 @@ impl Packer {
                 );
                 highest = candidate;
             } else {
-                let overlap_amount = self.calculate_overlap_amount(&tile);
+                let overlap_amount = self.calculate_overlap_amount(&tile)?;
                 if overlap_amount == 0.0 {
+                    let covered_points = self.find_points_in_tile(&tile)?;
                     self.add_tile(tile, &covered_points)?;
                     return Ok(());
                 }
@@ -312,8 +862,8 @@ impl Packer {
         tracing::debug!("Finding any tile for point: {point:?}");
         let mut highest = start_with_highest.unwrap_or(point.data);
         loop {
-            let tile = Self::find_minimum_tile_for_point(point.geom(), highest);
-            let Some((candidate, covered_points)) = self.find_highest_point_in_tile(&tile)? else {
+            let tile = self.find_minimum_tile_for_point(point.geom(), highest);
+            let Some(candidate) = self.find_highest_point_in_tile(&tile) else {
                 // Ignore tiles with just zeroes in them.
                 return Ok(None);
             };
@@ -326,6 +876,7 @@ impl Packer {
                 );
                 highest = candidate.data;
             } else {
+                let covered_points = self.find_points_in_tile(&tile)?;
                 return Ok(Some((tile, covered_points)));
             }
         }
@@ -333,30 +884,31 @@ impl Packer {
 
     /// Find all the points in a tile.
     fn find_points_in_tile(&self, tile: &crate::tile::Tile) -> Result<Vec<PointRstar>> {
-        let tile_polygon = tile.to_polygon_lonlat();
-        let mut covered_points: Vec<PointRstar> = self
-            .canonical
-            .locate_in_envelope_intersecting(&tile.to_aabb_lonlat()?)
-            .copied()
-            .collect();
-        covered_points.retain(|point| point.geom().0.is_within(&tile_polygon));
+        let tile_polygon = tile.to_polygon_lonlat(self.config.earth);
+        let mut covered_points: Vec<PointRstar> = Vec::new();
+        for aabb in crate::tile::Tile::polygon_aabbs_lonlat(&tile_polygon)? {
+            covered_points.extend(self.canonical.locate_in_envelope_intersecting(&aabb));
+        }
+        covered_points.retain(|point| tile_polygon.intersects(&point.geom().0));
         Ok(covered_points)
     }
 
     /// Calculate how much a tile overlaps with its neighbours.
-    fn calculate_overlap_amount(&self, tile: &crate::tile::Tile) -> f64 {
-        let candidate_polygon = tile.to_polygon_lonlat();
+    fn calculate_overlap_amount(&self, tile: &crate::tile::Tile) -> Result<f64> {
+        let candidate_polygon = tile.to_polygon_lonlat(self.config.earth);
         let area = candidate_polygon.unsigned_area();
         let mut searched = 0usize;
         let mut super_tile = geo::MultiPolygon::empty();
-        let iterator = self.tiles.nearest_neighbor_iter(&tile.centre);
+        let iterator = Self::neighbours(&self.tiles, tile, self.config.earth)?;
         for neighbour in iterator {
             if &neighbour.data == tile {
                 continue;
             }
-            let neighbour_polygon = neighbour.data.to_polygon_lonlat();
-            if neighbour_polygon.intersects(&candidate_polygon) {
-                super_tile = super_tile.union(&neighbour_polygon);
+            if tile.might_overlap(neighbour.data, self.config.earth) {
+                let neighbour_polygon = neighbour.data.to_polygon_lonlat(self.config.earth);
+                if neighbour_polygon.intersects(&candidate_polygon) {
+                    super_tile = super_tile.union(&neighbour_polygon);
+                }
             }
             searched += 1;
             if searched > 50 {
@@ -365,7 +917,7 @@ impl Packer {
         }
 
         let overlap = candidate_polygon.intersection(&super_tile).unsigned_area();
-        overlap / area
+        Ok(overlap / area)
     }
 
     /// Find points that have not yet been associated with a tile.
@@ -397,16 +949,16 @@ impl Packer {
     ///   1. A tile for the point, even though it will overlap another tile.
     ///   2. Find the nearest tile to the underlapping point and extend it to cover the point.
     ///
-    /// And add the one that introduces the least new surface area.
+    /// And add the one that introduces the least new cost.
     fn handle_underlapper(&mut self, point: PointRstar) -> Result<()> {
         let Some((own_tile, own_covered_points)) = self.find_any_tile_for_point(point, None)?
         else {
             tracing::trace!("No tile found");
             return Ok(());
         };
-        let own_surface_area = own_tile.surface_area()?;
+        let own_cost = self.config.cost_model.cost(own_tile)?;
 
-        if !self.attempt_increasing_nearest_tile(point, own_surface_area)? {
+        if !self.attempt_increasing_nearest_tile(point, own_cost)? {
             self.add_tile(own_tile, &own_covered_points)?;
         }
 
@@ -414,12 +966,12 @@ impl Packer {
     }
 
     /// Increase the size of the nearest tile to the point such that it covers the point. If that
-    /// size increase is less than making a dedicated tile for the point, than accept the increased
+    /// cost increase is less than making a dedicated tile for the point, than accept the increased
     /// size of the tile.
     fn attempt_increasing_nearest_tile(
         &mut self,
         point: PointRstar,
-        surface_area_to_beat: f32,
+        cost_to_beat: f32,
     ) -> Result<bool> {
         if self.tiles.size() == 0 {
             return Ok(false);
@@ -427,15 +979,15 @@ impl Packer {
         let Some((mut nearest_tile, old_width)) = self.engulf_tile_to_point(&point)? else {
             return Ok(false);
         };
-        let nearest_tile_starting_surface_area = nearest_tile.data.surface_area()?;
+        let nearest_tile_starting_cost = self.config.cost_model.cost(nearest_tile.data)?;
         tracing::trace!("🏝️ Nearest tile for underlapping point ({point:?}): {nearest_tile:?}");
 
-        Self::shrink_wrap_tile_to_point(&mut nearest_tile, point);
+        Self::shrink_wrap_tile_to_point(&mut nearest_tile, point, self.config.earth);
         let nearest_tile_covered_points = self.ensure_tile_is_big_enough(&nearest_tile)?;
 
-        let nearest_tile_surface_increase =
-            nearest_tile.data.surface_area()? - nearest_tile_starting_surface_area;
-        if nearest_tile_surface_increase > surface_area_to_beat {
+        let nearest_tile_cost_increase =
+            self.config.cost_model.cost(nearest_tile.data)? - nearest_tile_starting_cost;
+        if nearest_tile_cost_increase > cost_to_beat {
             return Ok(false);
         }
 
@@ -458,6 +1010,9 @@ impl Packer {
         let Some(nearest_tile_reference) = self.tiles.nearest_neighbor(point.geom()) else {
             color_eyre::eyre::bail!("No nearest tile found for point: {point:?}");
         };
+        if self.frozen.contains(nearest_tile_reference) {
+            return Ok(None);
+        }
         let mut nearest_tile = *nearest_tile_reference;
         let old_width = nearest_tile.data.width;
 
@@ -479,11 +1034,15 @@ impl Packer {
         )]
         let new_width = (distance as f32 * 2.0) * overshoot;
         nearest_tile.data.width = new_width;
-        if new_width > 700_000.0 {
+        if new_width > MAXIMUM_TILE_WIDTH {
             return Ok(None);
         }
         let lonlat = point.geom().0;
-        if !lonlat.is_within(&nearest_tile.data.to_polygon_lonlat()) {
+        if !nearest_tile
+            .data
+            .to_polygon_lonlat(self.config.earth)
+            .intersects(&lonlat)
+        {
             tracing::warn!("Nearest tile {nearest_tile:?} can't be fit over point {point:?}");
             return Ok(None);
         }
@@ -498,14 +1057,18 @@ impl Packer {
         Ok(Some((nearest_tile, old_width)))
     }
 
-    /// Slowly shrink a tile until we find the last size within which `is_within()` returns true
+    /// Slowly shrink a tile until we find the last size within which `intersects()` returns true
     /// for the given point.
-    fn shrink_wrap_tile_to_point(tile: &mut TileRstar, point: PointRstar) {
+    fn shrink_wrap_tile_to_point(
+        tile: &mut TileRstar,
+        point: PointRstar,
+        earth: crate::projector::Earth,
+    ) {
         let first_width = tile.data.width;
         let step = 100.0;
         loop {
-            let polygon = tile.data.to_polygon_lonlat();
-            if !point.geom().0.is_within(&polygon) {
+            let polygon = tile.data.to_polygon_lonlat(earth);
+            if !polygon.intersects(&point.geom().0) {
                 #[expect(
                     clippy::float_cmp,
                     reason = "`first_width` is the original value, not the result of a calculation."
@@ -538,9 +1101,7 @@ impl Packer {
         "
     )]
     fn ensure_tile_is_big_enough(&mut self, tile: &TileRstar) -> Result<Vec<PointRstar>> {
-        let Some((tile_highest, mut tile_covered_points)) =
-            self.find_highest_point_in_tile(&tile.data)?
-        else {
+        let Some(tile_highest) = self.find_highest_point_in_tile(&tile.data) else {
             panic!(
                 "Should be impossible because tile was already created with non-zero elevations"
             );
@@ -558,14 +1119,13 @@ impl Packer {
             );
             tracing::debug!("  From: {}m to {}m", tile.data.width, resized_tile.width,);
             self.set_tile_width(tile.data.centre, resized_tile.width)?;
-            tile_covered_points = resized_covered_points;
+            Ok(resized_covered_points)
         } else {
             tracing::trace!(
                 "Resized tile doesn't contain higher points, so doesn't need to be resized again."
             );
+            self.find_points_in_tile(&tile.data)
         }
-
-        Ok(tile_covered_points)
     }
 
     /// Add a tile to the world.
@@ -574,7 +1134,7 @@ impl Packer {
         tracing::info!("Added tile: {tile:?}");
 
         if self.config.one.is_some() {
-            self.save_tiles_as_csv()?;
+            self.save_tiles_as_csv(false)?;
         }
 
         let before = self.stack.len();
@@ -596,8 +1156,8 @@ impl Packer {
 
     /// Find the minimum bounding box that would allow calculating all the lines of site based on
     /// how far the highest point can see.
-    fn find_minimum_tile_for_point(point: &LonLatCoord, highest: i32) -> crate::tile::Tile {
-        let width = Self::minimum_tile_size_for_elevation(highest);
+    fn find_minimum_tile_for_point(&self, point: &LonLatCoord, highest: i32) -> crate::tile::Tile {
+        let width = Self::minimum_tile_size_for_elevation(highest, &self.config.line_of_sight);
         tracing::trace!("Minimum extent for {point:?}: {width}");
         crate::tile::Tile {
             centre: *point,
@@ -605,37 +1165,36 @@ impl Packer {
         }
     }
 
-    /// Given a height, how far is the longest theoretical line of sight from it.
+    /// Given a height, how far is the longest theoretical line of sight from it. Refraction and
+    /// the observer and target heights all make lines of sight longer, so tiles sized with them
+    /// still capture every refracted line.
     // TODO:
     //   Add a subtile of meters calculated at the furthest point?
     //   I don't think so, I can't think of an intuitive explanation though.
-    fn minimum_tile_size_for_elevation(elevation_i32: i32) -> f32 {
-        if elevation_i32 <= MINIMUM_HEIGHT {
+    pub fn minimum_tile_size_for_elevation(
+        elevation: i32,
+        line_of_sight: &crate::config::LineOfSight,
+    ) -> f32 {
+        if elevation <= MINIMUM_HEIGHT {
             // Strike a balance between lots of tiny tiles or fewer bigger tiles.
             return 36_000.0;
         }
 
         #[expect(
             clippy::as_conversions,
-            clippy::cast_precision_loss,
-            reason = "Is there another way?"
+            clippy::cast_possible_truncation,
+            reason = "Lines of sight are much shorter than `f32::MAX`"
         )]
-        let elevation = elevation_i32 as f32;
+        let width = (crate::horizon::longest_line_of_sight(f64::from(elevation), line_of_sight)
+            * 2.0f64) as f32;
 
-        (2.0 * crate::projector::EARTH_RADIUS * 1000.0)
-            .mul_add(elevation, elevation.powi(2))
-            .sqrt()
-            * 2.0
+        width
     }
 
-    /// Find the highest DEM point in a candidate tile.
-    fn find_highest_point_in_tile(
-        &self,
-        tile: &crate::tile::Tile,
-    ) -> Result<Option<(PointRstar, Vec<PointRstar>)>> {
+    /// Find the highest DEM point in a candidate tile, or `None` if there's nothing but sea level
+    /// in it.
+    fn find_highest_point_in_tile(&self, tile: &crate::tile::Tile) -> Option<PointRstar> {
         let start = std::time::Instant::now();
-        let mut highest = None;
-        let mut covered_points = Vec::new();
 
         // In the compute kernel we actually need an auxiliary region of elevation data around the
         // main tile so that lines of sight on the edge of the main tile have data to calculate
@@ -644,60 +1203,22 @@ impl Packer {
             centre: tile.centre,
             width: tile.width * 3.0,
         };
-
-        // The AABB is nothing like a circle, but is quicker to search within, so we need a second step
-        // to clip points outside of the tile's real extent.
-        let tile_aabb = tile_and_auxiliary.to_aabb_lonlat()?;
-        let aabb_points = self.canonical.locate_in_envelope_intersecting(&tile_aabb);
-
-        let tile_polygon = tile.to_polygon_lonlat();
-        let tile_and_auxiliary_polygon = tile_and_auxiliary.to_polygon_lonlat();
-
-        let mut current_highest = i32::MIN;
-        let mut is_non_zero_detected = false;
-
-        for point in aabb_points {
-            if !point.geom().0.is_within(&tile_and_auxiliary_polygon) {
-                continue;
-            }
-
-            // We're in the circle of the tile and its auxiliary region now...
-
-            if point.data != 0i32 {
-                is_non_zero_detected = true;
-            }
-            if point.data > current_highest {
-                current_highest = point.data;
-                highest = Some(*point);
-            }
-
-            // We only want to keep track of the points that the _main_ tile, without the auxiliary
-            // region, covers.
-            if point.geom().0.is_within(&tile_polygon) {
-                covered_points.push(*point);
-            }
-        }
-
-        if !is_non_zero_detected {
-            return Ok(None);
+        let tile_and_auxiliary_polygon = tile_and_auxiliary.to_polygon_lonlat(self.config.earth);
+
+        let highest = self.pyramid.highest_in(&tile_and_auxiliary_polygon)?;
+        // Below sea level is only as high as the sea, so there could still be something that isn't
+        // sea level.
+        if highest.max_height <= 0i32 && !self.pyramid.has_non_zero_in(&tile_and_auxiliary_polygon)
+        {
+            return None;
         }
 
-        if current_highest > i32::MIN {
-            tracing::trace!(
-                "Found highest point {:?} in {}μs",
-                current_highest,
-                start.elapsed().as_micros(),
-            );
-        }
-
-        let Some(some_highest) = highest else {
-            #[expect(clippy::panic, reason = "There's no way to recover")]
-            {
-                panic!("Tile candidate doesn't contain any data?");
-            }
-        };
-
-        Ok(Some((some_highest, covered_points)))
+        tracing::trace!(
+            "Found highest point {:?} in {}μs",
+            highest.max_height,
+            start.elapsed().as_micros(),
+        );
+        Some(Self::subtile_to_point(&highest))
     }
 
     /// Remove any tiles that are completely covered by other tiles.
@@ -707,11 +1228,11 @@ impl Packer {
         tracing::info!("Removing nested tiles from {} tiles...", window_tiles.len());
 
         for tile in window_tiles {
-            if !cleaned.contains(&tile) {
+            if !cleaned.contains(&tile) || self.frozen.contains(&tile) {
                 continue;
             }
 
-            if Self::is_tile_nested(&tile, &cleaned) {
+            if Self::is_tile_nested(&tile, &cleaned, self.config.earth)? {
                 cleaned.remove(&tile);
                 continue;
             }
@@ -727,10 +1248,14 @@ impl Packer {
     }
 
     /// Is the tile completely inside another tile?
-    fn is_tile_nested(tile: &TileRstar, cleaned: &rstar::RTree<TileRstar>) -> bool {
-        let polygon = tile.data.to_polygon_lonlat();
+    fn is_tile_nested(
+        tile: &TileRstar,
+        cleaned: &rstar::RTree<TileRstar>,
+        earth: crate::projector::Earth,
+    ) -> Result<bool> {
+        let polygon = tile.data.to_polygon_lonlat(earth);
         let mut super_tile = geo::MultiPolygon::empty();
-        let iterator = cleaned.nearest_neighbor_iter(tile.geom()).enumerate();
+        let iterator = Self::neighbours(cleaned, &tile.data, earth)?.enumerate();
         for (count, neighbour) in iterator {
             if neighbour == tile {
                 continue;
@@ -739,51 +1264,58 @@ impl Packer {
                 break;
             }
 
-            let neighbour_polygon = neighbour.data.to_polygon_lonlat();
+            if !tile.data.might_overlap(neighbour.data, earth) {
+                continue;
+            }
+            let neighbour_polygon = neighbour.data.to_polygon_lonlat(earth);
             if neighbour_polygon.intersects(&polygon) {
                 super_tile = super_tile.union(&neighbour_polygon);
             }
         }
 
-        super_tile.contains(&polygon)
+        Ok(super_tile.contains(&polygon))
     }
 
     /// For any tile that has over 50% overlap with other tiles, search to find a nearby tile that
-    /// could be increased in size such that the increase of its surface area is less than the
-    /// overly overlapped tile.
+    /// could be increased in size such that the increase of its cost is less than the overly
+    /// overlapped tile.
     fn find_better_bigger_neighbour(
         &mut self,
         tile: &TileRstar,
         tiles: &mut rstar::RTree<TileRstar>,
     ) -> Result<()> {
         let minimum_overlap = 0.5f64;
-        let allowed_surface_increase = 1.5;
-        let overlap = self.calculate_overlap_amount(&tile.data);
+        let overlap = self.calculate_overlap_amount(&tile.data)?;
         if overlap < minimum_overlap {
             return Ok(());
         }
         tracing::trace!("Looking for better bigger neighbouring tile for: {tile:?}");
 
-        let Some(best) = Self::find_betterest_bigger_candidate(tile, tiles)? else {
+        let cost_model = self.config.cost_model;
+        let Some(best) = Self::find_betterest_bigger_candidate(
+            tile,
+            tiles,
+            &self.frozen,
+            cost_model,
+            self.config.earth,
+        )?
+        else {
             return Ok(());
         };
         self.nudge_extend_tile(&best)?;
 
-        let original_surface_area = tiles
+        let original = tiles
             .locate_at_point(best.geom())
             .context("Couldn't find tile in cloned RTree, probably a bug")?
-            .data
-            .surface_area()?;
-        let surface_area_increase = best.data.surface_area()? - original_surface_area;
-        let tile_surface_area = tile.data.surface_area()?;
-        if surface_area_increase <= tile_surface_area * allowed_surface_increase {
+            .data;
+        if cost_model.is_worth_growing(original, best.data, tile.data)? {
             let winner = tiles
                 .locate_at_point_mut(best.geom())
                 .context("Couldn't find tile in cloned RTree, probably a bug")?;
             winner.data.width = best.data.width;
             tracing::debug!(
-                "Better bigger neighbouring tile found ({surface_area_increase}m² \
-                vs {tile_surface_area}m²): {winner:?}. Old: {tile:?}"
+                "Better bigger neighbouring tile found ({cost_model:?}): {winner:?}. \
+                Old: {tile:?}"
             );
             tiles
                 .remove_at_point(tile.geom())
@@ -791,21 +1323,25 @@ impl Packer {
         } else {
             tracing::trace!(
                 "No better bigger neighbouring tile that doesn't increase total \
-                surface area found for: {tile:?}"
+                cost found for: {tile:?}"
             );
         }
 
         Ok(())
     }
 
-    /// Find the best candidate for replacing a tile with a bigger better neighbour.
+    /// Find the best candidate for replacing a tile with a bigger better neighbour. Frozen tiles
+    /// can't be made bigger.
     fn find_betterest_bigger_candidate(
         tile: &TileRstar,
         tiles: &rstar::RTree<TileRstar>,
+        frozen: &rstar::RTree<TileRstar>,
+        cost_model: cost::Model,
+        earth: crate::projector::Earth,
     ) -> Result<Option<TileRstar>> {
         let mut candidates = Vec::new();
-        let iterator = tiles.nearest_neighbor_iter(tile.geom()).enumerate();
-        let polygon = tile.data.to_polygon_lonlat();
+        let iterator = Self::neighbours(tiles, &tile.data, earth)?.enumerate();
+        let polygon = tile.data.to_polygon_lonlat(earth);
         for (count, neighbour) in iterator {
             if neighbour == tile {
                 continue;
@@ -813,7 +1349,7 @@ impl Packer {
             if count > 10 {
                 break;
             }
-            if tile.data.width > neighbour.data.width {
+            if tile.data.width > neighbour.data.width || frozen.contains(neighbour) {
                 continue;
             }
 
@@ -822,7 +1358,7 @@ impl Packer {
                 clippy::as_conversions,
                 reason = "I think this is the only way?"
             )]
-            let distance = tile.data.distance_from(neighbour.data.centre)? as f32;
+            let distance = tile.data.distance_from(neighbour.data.centre, earth)? as f32;
             let starting_width = distance + tile.data.width;
 
             // TODO: It's weird that we have to loop even bigger. Just calculating the distance
@@ -830,10 +1366,10 @@ impl Packer {
             let mut bigger = *neighbour;
             bigger.data.width = starting_width;
             for _ in 0..3000u32 {
-                if bigger.data.width > 700_000.0 {
+                if bigger.data.width > MAXIMUM_TILE_WIDTH {
                     break;
                 }
-                if bigger.data.to_polygon_lonlat().contains(&polygon) {
+                if bigger.data.to_polygon_lonlat(earth).contains(&polygon) {
                     candidates.push(bigger);
                     break;
                 }
@@ -841,7 +1377,7 @@ impl Packer {
             }
         }
 
-        candidates.sort_by(Self::compare_tile_surface_areas);
+        candidates.sort_by(|left, right| Self::compare_tile_costs(cost_model, left, right));
 
         match candidates.first() {
             Some(best) => Ok(Some(*best)),
@@ -854,17 +1390,17 @@ impl Packer {
         }
     }
 
-    /// Compare the surface areas of 2 tiles.
+    /// Compare the costs of 2 tiles.
     #[expect(
         clippy::unwrap_used,
         reason = "Any of these would be a bug. So let's clippy-ignore them all in one place"
     )]
-    fn compare_tile_surface_areas(left: &TileRstar, right: &TileRstar) -> std::cmp::Ordering {
-        left.data
-            .surface_area()
-            .unwrap()
-            .partial_cmp(&right.data.surface_area().unwrap())
-            .unwrap()
+    fn compare_tile_costs(
+        cost_model: cost::Model,
+        left: &TileRstar,
+        right: &TileRstar,
+    ) -> std::cmp::Ordering {
+        cost_model.compare(left.data, right.data).unwrap()
     }
 
     /// Extend each tile by the resolution of the underlying max subtile data.
@@ -876,6 +1412,9 @@ impl Packer {
         );
 
         for window_tile in window_tiles {
+            if self.frozen.contains(&window_tile) {
+                continue;
+            }
             self.nudge_extend_tile(&window_tile)?;
         }
 
@@ -903,14 +1442,21 @@ impl Packer {
     /// constant amount.
     fn nudge_extend_tile(&mut self, tile: &TileRstar) -> Result<()> {
         // This depends on the resolution set in the max-subtile pre-process step. It's not good
-        // that we hardcode it here :/
-        let subtile_resolution = 10.0;
+        // that we hardcode it here :/ But at least max subtiles files of any other resolution are
+        // refused when they're loaded.
+        #[expect(
+            clippy::as_conversions,
+            clippy::cast_precision_loss,
+            reason = "It's only 10"
+        )]
+        let subtile_resolution = SUBTILES_PER_DEGREE as f32;
         // Just add a little more for safe measure. Though the better aproach would be to take the
         // latitude of the tile corner that is furthest from the equator.
         let magic = 1.5;
         let latitude = tile.data.centre.0.y;
-        let extension =
-            (crate::projector::Convert::meters_per_degree(latitude) / subtile_resolution) * magic;
+        let extension = (crate::projector::Convert::meters_per_degree(self.config.earth, latitude)
+            / subtile_resolution)
+            * magic;
         let new_width = tile.data.width + extension;
         self.set_tile_width(tile.data.centre, new_width)?;
         self.ensure_tile_is_big_enough(tile)?;
@@ -919,68 +1465,78 @@ impl Packer {
     }
 
     /// Save the tiles to CSV file. Used by `atlas` and to render the tiles for debugging.
-    fn save_tiles_as_csv(&self) -> Result<()> {
+    ///
+    /// Finding the details of each tile, like its highest point, means searching all the points it
+    /// covers. So it's only worth doing for the final tiles, not every time we save progress.
+    fn save_tiles_as_csv(&self, with_details: bool) -> Result<()> {
+        let Some(output) = &self.output else {
+            return Ok(());
+        };
+
         let mut tiles = Vec::new();
         for tile in &self.tiles {
-            let centre = tile.data.centre.0;
-            let line = format!("{},{},{}", centre.x, centre.y, tile.data.width);
-            tiles.push(line);
+            let details = if with_details {
+                self.tile_details(&tile.data)?
+            } else {
+                None
+            };
+            tiles.push(crate::tiles::PackedTile {
+                tile: tile.data,
+                details,
+            });
         }
 
-        tracing::info!("Saving {} tiles to: {TILES_OUTPUT}", self.tiles.size());
-        std::fs::write(TILES_OUTPUT, tiles.join("\n"))?;
-
-        Ok(())
+        let file = crate::tiles::File {
+            metadata: self.metadata(),
+            tiles,
+        };
+        file.save(output)
     }
 
-    /// Save tiles and points [`GeoJSON`].
-    #[expect(dead_code, reason = "Useful for debugging.")]
-    fn save_tiles_and_points(tiles: &[crate::tile::Tile], points: &[LonLatCoord]) -> Result<()> {
-        let mut features: Vec<geo::Geometry> = Vec::new();
-
-        for tile in tiles {
-            let geojson_tile = tile.to_polygon_lonlat();
-            features.push(geojson_tile.into());
-        }
-
-        let mut geojson_points = Vec::new();
-        for point in points {
-            geojson_points.push(geo::Point::from(point.0));
+    /// The metadata for the tiles file, with the parameters that the tiles were packed with.
+    fn metadata(&self) -> crate::tiles::Metadata {
+        crate::tiles::Metadata {
+            packer: Some(crate::tiles::PackerParameters {
+                cost_model: self.config.cost_model,
+                bands: self.config.bands,
+                minimum_height: MINIMUM_HEIGHT,
+                window_radius: WINDOW_RADIUS,
+                maximum_tile_width: MAXIMUM_TILE_WIDTH,
+                line_of_sight: self.config.line_of_sight,
+                earth: self.config.earth,
+            }),
+            input_hash: Some(self.input_hash.clone()),
+            ..crate::tiles::Metadata::default()
         }
-        let multipoint = geo::MultiPoint::new(geojson_points);
-        features.push(multipoint.into());
-
-        let feature_collection = geojson::FeatureCollection::from(&features.into());
-
-        tracing::info!(
-            "DEBUG: saving {} tiles and {} points to: {TILES_OUTPUT}",
-            tiles.len(),
-            points.len()
-        );
-        let json = geojson::GeoJson::from(feature_collection);
-        std::fs::write(TILES_OUTPUT, json.to_string())?;
-
-        Ok(())
     }
 
-    /// Save polygons for debugging.
-    #[expect(dead_code, reason = "Useful for debugging.")]
-    fn save_polygons(polygons: &[geo::MultiPolygon]) -> Result<()> {
-        let mut features: Vec<geo::Geometry> = Vec::new();
-        for polygon in polygons.iter().cloned() {
-            features.push(polygon.into());
-        }
-
-        let feature_collection = geojson::FeatureCollection::from(&features.into());
+    /// All the packed tiles, sorted by longitude and then latitude so that they're always in the
+    /// same order.
+    pub fn tiles(&self) -> Vec<crate::tile::Tile> {
+        let mut tiles: Vec<crate::tile::Tile> = self.tiles.iter().map(|tile| tile.data).collect();
+        tiles.sort_by(|left, right| {
+            left.centre
+                .0
+                .x
+                .total_cmp(&right.centre.0.x)
+                .then(left.centre.0.y.total_cmp(&right.centre.0.y))
+        });
+        tiles
+    }
 
-        tracing::info!(
-            "DEBUG: saving {} polygons to: {TILES_OUTPUT}",
-            polygons.len()
-        );
-        let json = geojson::GeoJson::from(feature_collection);
-        std::fs::write(TILES_OUTPUT, json.to_string())?;
+    /// The highest point, number of points and overlap of a tile.
+    fn tile_details(&self, tile: &crate::tile::Tile) -> Result<Option<crate::tiles::TileDetails>> {
+        let covered_points = self.find_points_in_tile(tile)?;
+        let Some(highest) = covered_points.iter().max_by_key(|point| point.data) else {
+            return Ok(None);
+        };
 
-        Ok(())
+        Ok(Some(crate::tiles::TileDetails {
+            highest: *highest.geom(),
+            highest_elevation: highest.data,
+            covered_subtiles: u32::try_from(covered_points.len())?,
+            overlap_ratio: self.calculate_overlap_amount(tile)?,
+        }))
     }
 }
 
@@ -988,16 +1544,68 @@ impl Packer {
 mod test {
     use super::*;
 
+    #[expect(clippy::float_cmp, reason = "The poles are exact values")]
+    #[test]
+    fn bands_cover_every_row() {
+        let bands = Packer::bands(4, crate::projector::Earth::Wgs84).unwrap();
+        assert_eq!(bands.len(), 4);
+        assert_eq!(bands.first().unwrap().north, 90.0f64);
+        assert_eq!(bands.last().unwrap().south, -90.0f64);
+        for (north, south) in bands.iter().zip(bands.iter().skip(1)) {
+            assert!(north.north >= north.south);
+            assert!(north.south > south.north);
+        }
+    }
+
+    #[test]
+    fn points_on_the_edge_of_a_tile_are_covered() {
+        // Subtiles exactly on the antimeridian are on the edge of both halves of a tile that
+        // crosses it, so they're only in the tile if edges count.
+        let subtiles = [-17.1f32, -17.0, -16.9].map(|lat| crate::max_subtile::MaxSubTile {
+            lon: -180.0,
+            lat,
+            max_height: 500,
+        });
+        let config = <crate::config::Packer as clap::Parser>::parse_from(["packer"]);
+        let packer = Packer::from_subtiles(config, &subtiles).unwrap();
+        let tile = crate::tile::Tile {
+            centre: LonLatCoord(geo::coord! { x: 179.9f64, y: -17.0f64 }),
+            width: 100_000.0,
+        };
+
+        assert_eq!(packer.find_points_in_tile(&tile).unwrap().len(), 3);
+    }
+
     #[expect(
         clippy::float_cmp,
         reason = "Theren't aren't enough decimal places to worry about this"
     )]
     #[test]
     fn minimum_tile_size_for_elevation() {
-        assert_eq!(Packer::minimum_tile_size_for_elevation(0), 36_000.0);
-        assert_eq!(Packer::minimum_tile_size_for_elevation(1000), 225_769.8);
-        assert_eq!(Packer::minimum_tile_size_for_elevation(3000), 391_075.44);
-        assert_eq!(Packer::minimum_tile_size_for_elevation(8000), 638_748.75);
-        assert_eq!(Packer::minimum_tile_size_for_elevation(8848), 671_772.3);
+        let geometric = crate::config::LineOfSight {
+            refraction: 0.0,
+            observer_height: 0.0,
+            target_height: 0.0,
+        };
+        for (elevation, width) in [
+            (0i32, 36_000.0f32),
+            (1000i32, 225_769.8f32),
+            (3000i32, 391_075.44f32),
+            (8000i32, 638_748.75f32),
+            (8848i32, 671_772.3f32),
+        ] {
+            assert_eq!(
+                Packer::minimum_tile_size_for_elevation(elevation, &geometric),
+                width
+            );
+        }
+
+        let refracted = crate::config::LineOfSight {
+            refraction: crate::horizon::DEFAULT_REFRACTION,
+            ..geometric
+        };
+        let everest = Packer::minimum_tile_size_for_elevation(HIGHEST_ELEVATION, &refracted);
+        assert!(everest > Packer::minimum_tile_size_for_elevation(HIGHEST_ELEVATION, &geometric));
+        assert!(everest < MAXIMUM_TILE_WIDTH);
     }
 }
@@ -0,0 +1,192 @@
+//! Save and restore the state of a packer run, so that a run that stops for whatever reason can
+//! carry on from where it was.
+//!
+//! A checkpoint is a directory containing:
+//!   * `state.json`: the next window to pack and all the tiles found so far.
+//!   * `stack_history.bin`: every max subtile that has already been put on a window's stack. It's
+//!     in the same format as `max_subtiles.bin`.
+
+use color_eyre::Result;
+
+/// The file for everything apart from the stack history.
+const STATE_FILENAME: &str = "state.json";
+
+/// The file for the stack history.
+const STACK_HISTORY_FILENAME: &str = "stack_history.bin";
+
+/// Where the packer is up to.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct State {
+    /// The centre of the next window to pack.
+    pub centre: crate::projector::LonLatCoord,
+    /// Whether the next window is the last one in its row.
+    pub is_last_longitude: bool,
+    /// How many window steps have been taken so far.
+    pub steps: u32,
+    /// All the tiles found so far.
+    pub tiles: Vec<crate::tile::Tile>,
+    /// The earth that the tiles were packed on. A run can only be resumed on the same earth.
+    #[serde(default)]
+    pub earth: crate::projector::Earth,
+}
+
+/// Everything needed to resume a packer run.
+pub struct Checkpoint {
+    /// Where the packer is up to.
+    pub state: State,
+    /// All the max subtiles that have already been put on a window's stack.
+    pub stack_history: Vec<crate::max_subtile::MaxSubTile>,
+}
+
+impl Checkpoint {
+    /// Save the checkpoint. It's written to a temporary directory first, so that stopping halfway
+    /// through saving doesn't ruin the previous checkpoint.
+    ///
+    /// The previous checkpoint is only moved aside while the new one is moved in, and deleted
+    /// after. So there's always a whole checkpoint on disk, see `load()`.
+    pub fn save(&self, path: &std::path::Path) -> Result<()> {
+        let temporary = Self::sibling(path, "tmp");
+        let previous = Self::sibling(path, "old");
+        if temporary.exists() {
+            std::fs::remove_dir_all(&temporary)?;
+        }
+        std::fs::create_dir_all(&temporary)?;
+
+        std::fs::write(
+            temporary.join(STATE_FILENAME),
+            serde_json::to_string(&self.state)?,
+        )?;
+        std::fs::write(
+            temporary.join(STACK_HISTORY_FILENAME),
+            bytemuck::cast_slice(&self.stack_history),
+        )?;
+
+        if path.exists() {
+            if previous.exists() {
+                std::fs::remove_dir_all(&previous)?;
+            }
+            std::fs::rename(path, &previous)?;
+        }
+        std::fs::rename(&temporary, path)?;
+        if previous.exists() {
+            std::fs::remove_dir_all(&previous)?;
+        }
+
+        tracing::info!(
+            "Saved checkpoint with {} tiles and {} handled subtiles to: {path:?}",
+            self.state.tiles.len(),
+            self.stack_history.len()
+        );
+        Ok(())
+    }
+
+    /// Load a checkpoint. If saving stopped after the previous checkpoint was moved aside but
+    /// before the new one was moved in, the previous one is loaded instead.
+    pub fn load(path: &std::path::Path) -> Result<Self> {
+        let previous = Self::sibling(path, "old");
+        let directory = if !path.exists() && previous.exists() {
+            tracing::warn!("No checkpoint in {path:?}, loading the previous one: {previous:?}");
+            previous.as_path()
+        } else {
+            path
+        };
+
+        let state_path = directory.join(STATE_FILENAME);
+        let contents = std::fs::read_to_string(&state_path)
+            .map_err(|error| color_eyre::eyre::eyre!("Couldn't read {state_path:?}: {error}"))?;
+        let state: State = serde_json::from_str(&contents)?;
+
+        // The bytes aren't necessarily aligned for subtiles, eg when there aren't any.
+        let bytes = std::fs::read(directory.join(STACK_HISTORY_FILENAME))?;
+        let stack_history: Vec<crate::max_subtile::MaxSubTile> =
+            crate::max_subtile::file::parse_subtiles(&bytes).map_err(|error| {
+                color_eyre::eyre::eyre!("Couldn't parse stack history in {directory:?}: {error}")
+            })?;
+
+        tracing::info!(
+            "Loaded checkpoint with {} tiles and {} handled subtiles, resuming from: {:?}",
+            state.tiles.len(),
+            stack_history.len(),
+            state.centre
+        );
+        Ok(Self {
+            state,
+            stack_history,
+        })
+    }
+
+    /// A directory next to the checkpoint, with an extension added to its name.
+    fn sibling(path: &std::path::Path, extension: &str) -> std::path::PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".");
+        name.push(extension);
+        std::path::PathBuf::from(name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn save_and_load() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("checkpoint");
+        let tile = crate::tile::Tile {
+            centre: crate::projector::LonLatCoord(geo::coord! { x: 86.9f64, y: 27.9f64 }),
+            width: 671_772.3,
+        };
+        let subtile = crate::max_subtile::MaxSubTile {
+            lon: 86.95,
+            lat: 27.95,
+            max_height: 8848,
+        };
+        let checkpoint = Checkpoint {
+            state: State {
+                centre: crate::projector::LonLatCoord(geo::coord! { x: 100.0f64, y: 30.0f64 }),
+                is_last_longitude: false,
+                steps: 42,
+                tiles: vec![tile],
+                earth: crate::projector::Earth::Sphere,
+            },
+            stack_history: vec![subtile],
+        };
+
+        checkpoint.save(&path).unwrap();
+        // Saving again replaces the previous checkpoint.
+        checkpoint.save(&path).unwrap();
+
+        let loaded = Checkpoint::load(&path).unwrap();
+        assert_eq!(loaded.state.steps, 42);
+        assert_eq!(loaded.state.centre, checkpoint.state.centre);
+        assert_eq!(loaded.state.tiles, vec![tile]);
+        assert_eq!(loaded.state.earth, crate::projector::Earth::Sphere);
+        assert_eq!(loaded.stack_history, vec![subtile]);
+    }
+
+    #[test]
+    fn load_the_previous_checkpoint_if_saving_stopped_halfway() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("checkpoint");
+        let checkpoint = Checkpoint {
+            state: State {
+                centre: crate::projector::LonLatCoord(geo::coord! { x: 100.0f64, y: 30.0f64 }),
+                is_last_longitude: true,
+                steps: 7,
+                tiles: Vec::new(),
+                earth: crate::projector::Earth::Wgs84,
+            },
+            stack_history: Vec::new(),
+        };
+        checkpoint.save(&path).unwrap();
+        assert!(!directory.path().join("checkpoint.old").exists());
+        assert!(!directory.path().join("checkpoint.tmp").exists());
+
+        // As if it stopped after moving the previous checkpoint aside.
+        std::fs::rename(&path, directory.path().join("checkpoint.old")).unwrap();
+
+        let loaded = Checkpoint::load(&path).unwrap();
+        assert_eq!(loaded.state.steps, 7);
+        assert!(loaded.state.is_last_longitude);
+    }
+}
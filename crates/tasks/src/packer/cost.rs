@@ -0,0 +1,131 @@
+//! How much a tile costs. The packer tries to find the cheapest set of tiles that covers the world.
+
+use color_eyre::Result;
+
+/// The number of points across the Melbourne tile, that the compute cost is calibrated with.
+const MELBOURNE_POINTS: f32 = 4060.0;
+
+/// Melbourne takes 270 seconds with 16 cores on a Turin machine, so about 4320 CPU seconds in
+/// total.
+const MELBOURNE_CPU_SECONDS: f32 = 4320.0;
+
+/// How much a tile's cost can increase, as a multiple of the cost of the tile it replaces, for it
+/// to be worth growing the tile to cover another tile.
+const ALLOWED_COST_INCREASE: f32 = 1.5;
+
+/// How the packer decides which of 2 tiles is cheaper.
+#[derive(
+    clap::ValueEnum,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Model {
+    /// The surface area of the tile in m².
+    #[default]
+    SurfaceArea,
+    /// The estimated CPU seconds for the kernel to compute the tile. It's quadratic in the number
+    /// of points in the tile, so it grows with the 4th power of the tile's width, whereas its
+    /// surface area only grows with the square.
+    Compute,
+}
+
+impl Model {
+    /// The cost of a tile.
+    pub fn cost(self, tile: crate::tile::Tile) -> Result<f32> {
+        match self {
+            Self::SurfaceArea => tile.surface_area(),
+            Self::Compute => Ok(compute_seconds(tile.width)),
+        }
+    }
+
+    /// Compare the costs of 2 tiles.
+    pub fn compare(
+        self,
+        left: crate::tile::Tile,
+        right: crate::tile::Tile,
+    ) -> Result<std::cmp::Ordering> {
+        Ok(self.cost(left)?.total_cmp(&self.cost(right)?))
+    }
+
+    /// Whether it's worth growing a tile so that another tile can be removed. The increase in cost
+    /// has to be small enough compared to the cost of the removed tile.
+    pub fn is_worth_growing(
+        self,
+        original: crate::tile::Tile,
+        grown: crate::tile::Tile,
+        removed: crate::tile::Tile,
+    ) -> Result<bool> {
+        let cost_increase = self.cost(grown)? - self.cost(original)?;
+        Ok(cost_increase <= self.cost(removed)? * ALLOWED_COST_INCREASE)
+    }
+}
+
+/// The estimated CPU seconds for the kernel to compute a tile of the given width.
+///
+/// Every point in the tile looks along lines of sight that cross the whole tile, so the kernel's
+/// work is quadratic in the number of points, which itself is the square of the points across.
+pub fn compute_seconds(width: f32) -> f32 {
+    let points = width / crate::atlas::tile_job::DEM_SCALE;
+    MELBOURNE_CPU_SECONDS * (points / MELBOURNE_POINTS).powi(4)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn melbourne_compute_seconds() {
+        let seconds = compute_seconds(406_000.0);
+        assert!((seconds - 4320.0).abs() < 1.0, "{seconds}");
+    }
+
+    #[test]
+    fn compare_tiles_by_compute() {
+        let small = crate::tile::Tile {
+            centre: crate::projector::LonLatCoord(geo::coord! { x: 0.0f64, y: 0.0f64 }),
+            width: 100_000.0,
+        };
+        let big = crate::tile::Tile {
+            width: 200_000.0,
+            ..small
+        };
+        assert_eq!(
+            Model::Compute.compare(small, big).unwrap(),
+            std::cmp::Ordering::Less
+        );
+    }
+
+    #[test]
+    fn growing_is_cheaper_by_area_than_by_compute() {
+        let removed = crate::tile::Tile {
+            centre: crate::projector::LonLatCoord(geo::coord! { x: 0.0f64, y: 0.0f64 }),
+            width: 100_000.0,
+        };
+        let original = crate::tile::Tile {
+            centre: crate::projector::LonLatCoord(geo::coord! { x: 0.5f64, y: 0.0f64 }),
+            ..removed
+        };
+        let grown = crate::tile::Tile {
+            width: 140_000.0,
+            ..original
+        };
+
+        assert!(
+            Model::SurfaceArea
+                .is_worth_growing(original, grown, removed)
+                .unwrap()
+        );
+        assert!(
+            !Model::Compute
+                .is_worth_growing(original, grown, removed)
+                .unwrap()
+        );
+    }
+}
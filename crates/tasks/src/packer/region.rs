@@ -0,0 +1,92 @@
+//! Repack just a region of an existing packing, for when the max subtiles of that region change.
+
+use color_eyre::Result;
+use geo::{BooleanOps as _, Intersects as _};
+
+/// Where to save the differences between the base tiles and the repacked tiles by default.
+///
+/// It's next to the repacked tiles, eg: `output/himalayas_diff.geojson` for `output/himalayas.csv`.
+pub fn default_diff_output(tiles: &std::path::Path) -> std::path::PathBuf {
+    let stem = tiles.file_stem().unwrap_or_default().to_string_lossy();
+    tiles.with_file_name(format!("{stem}_diff.geojson"))
+}
+
+/// The area that repacking a region covers: the region itself and all the base tiles that touch
+/// it. Also returns the base tiles that are kept, because they don't touch the region.
+pub fn repack_area(
+    region: &geo::MultiPolygon,
+    base: &[crate::tile::Tile],
+    earth: crate::projector::Earth,
+) -> (geo::MultiPolygon, Vec<crate::tile::Tile>) {
+    let mut area = region.clone();
+    let mut kept = Vec::new();
+    for tile in base {
+        let polygon = tile.to_polygon_lonlat(earth);
+        if polygon.intersects(region) {
+            area = area.union(&polygon);
+        } else {
+            kept.push(*tile);
+        }
+    }
+
+    (area, kept)
+}
+
+/// The polygons of a region in lon/lat degrees.
+pub fn to_polygon(region: &crate::config::Region) -> Result<geo::MultiPolygon> {
+    match region {
+        crate::config::Region::BoundingBox {
+            west,
+            south,
+            east,
+            north,
+        } => Ok(geo::Rect::new(
+            geo::coord! { x: *west, y: *south },
+            geo::coord! { x: *east, y: *north },
+        )
+        .to_polygon()
+        .into()),
+        crate::config::Region::GeoJson(path) => {
+            let geojson: geojson::GeoJson = std::fs::read_to_string(path)?.parse()?;
+            let collection = geo::GeometryCollection::<f64>::try_from(&geojson)?;
+
+            let mut polygons = Vec::new();
+            for geometry in collection {
+                match geometry {
+                    geo::Geometry::Polygon(polygon) => polygons.push(polygon),
+                    geo::Geometry::MultiPolygon(multi_polygon) => polygons.extend(multi_polygon),
+                    geo::Geometry::Rect(rect) => polygons.push(rect.to_polygon()),
+                    geo::Geometry::Triangle(triangle) => polygons.push(triangle.to_polygon()),
+                    geo::Geometry::Point(_)
+                    | geo::Geometry::Line(_)
+                    | geo::Geometry::LineString(_)
+                    | geo::Geometry::MultiPoint(_)
+                    | geo::Geometry::MultiLineString(_)
+                    | geo::Geometry::GeometryCollection(_) => {
+                        color_eyre::eyre::bail!(
+                            "Region can only contain polygons, found: {geometry:?}"
+                        );
+                    }
+                }
+            }
+            if polygons.is_empty() {
+                color_eyre::eyre::bail!("Region doesn't contain any polygons");
+            }
+
+            Ok(geo::MultiPolygon::new(polygons))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn diff_is_saved_next_to_the_tiles() {
+        assert_eq!(
+            default_diff_output(std::path::Path::new("output/himalayas.csv")),
+            std::path::PathBuf::from("output/himalayas_diff.geojson")
+        );
+    }
+}
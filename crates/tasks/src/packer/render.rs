@@ -0,0 +1,636 @@
+//! Draw a tiles file, so that every change to the packer comes with something to look at.
+//!
+//! Tiles are coloured by their width, from purple for the smallest to yellow for the biggest, and
+//! drawn over the elevations of the max subtiles. Images are either PNGs or SVGs, in an
+//! equirectangular or a Web Mercator projection. `GeoJSON` is just the tiles and their highest
+//! points in lon/lat, for inspecting in a GIS.
+
+use std::fmt::Write as _;
+
+use color_eyre::Result;
+
+/// The colours of tile widths, from the smallest to the biggest tile. It's roughly viridis.
+const WIDTH_COLOURS: [[u8; 3]; 5] = [
+    [68, 1, 84],
+    [59, 82, 139],
+    [33, 145, 140],
+    [94, 201, 98],
+    [253, 231, 37],
+];
+
+/// The colour of the sea, or anywhere else without any elevation.
+const SEA_COLOUR: [u8; 3] = [16, 24, 40];
+
+/// How opaque tiles are over the elevations.
+const TILE_OPACITY: f64 = 0.4;
+
+/// The width in pixels of each square of elevations in SVGs. A `<rect>` for every single pixel
+/// would make huge files.
+const SVG_CELL: usize = 4;
+
+/// Web Mercator can't reach the poles. It's cut off at the latitude that makes the map square.
+const MERCATOR_MAX_LATITUDE: f64 = 85.051_128_779_806_59;
+
+/// The map projection of an image.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Projection {
+    /// Longitudes and latitudes are simply the x and y of the image.
+    #[default]
+    Equirectangular,
+    /// The projection of most web maps, including the website's.
+    WebMercator,
+}
+
+/// The kinds of file that can be rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// A PNG image.
+    Png,
+    /// An SVG image, where each tile is its own path.
+    Svg,
+    /// `GeoJSON` of the tiles and their highest points.
+    GeoJson,
+}
+
+impl Format {
+    /// The format of a path, from its extension.
+    fn from_path(path: &std::path::Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        match extension.as_str() {
+            "png" => Ok(Self::Png),
+            "svg" => Ok(Self::Svg),
+            "geojson" | "json" => Ok(Self::GeoJson),
+            _ => color_eyre::eyre::bail!(
+                "Can't render to {path:?}, use a `.png`, `.svg` or `.geojson` extension"
+            ),
+        }
+    }
+}
+
+/// Entrypoint.
+pub fn run(config: &crate::config::Render) -> Result<()> {
+    let format = Format::from_path(&config.output)?;
+    let file = crate::tiles::File::load(&config.tiles)?;
+    let earth = file.metadata.earth();
+    tracing::info!(
+        "Rendering {} tiles packed on {earth:?} to: {:?}",
+        file.tiles.len(),
+        config.output
+    );
+
+    match format {
+        Format::GeoJson => save_geojson(&file.tiles, earth, &config.output),
+        Format::Png | Format::Svg => {
+            let subtiles =
+                crate::max_subtile::Subtiler::load(&config.subtiles, config.legacy_subtiles)?
+                    .subtiles;
+            let canvas = Canvas::new(config.width, config.projection, earth, &subtiles)?;
+            let tiles: Vec<crate::tile::Tile> =
+                file.tiles.iter().map(|packed| packed.tile).collect();
+            if format == Format::Png {
+                canvas.save_png(&tiles, &config.output)
+            } else {
+                canvas.save_svg(&tiles, &config.output)
+            }
+        }
+    }
+}
+
+/// An image of the whole world to draw tiles on.
+struct Canvas {
+    /// The width of the image in pixels.
+    width: usize,
+    /// The height of the image in pixels.
+    height: usize,
+    /// How lon/lat coordinates are turned into pixels.
+    projection: Projection,
+    /// The earth that the tiles were packed on, which their polygons are made on.
+    earth: crate::projector::Earth,
+    /// The highest elevation in each pixel, row by row from the top left corner.
+    elevations: Vec<i32>,
+}
+
+impl Canvas {
+    /// Instantiate with the elevations of the max subtiles.
+    fn new(
+        width: u32,
+        projection: Projection,
+        earth: crate::projector::Earth,
+        subtiles: &[crate::max_subtile::MaxSubTile],
+    ) -> Result<Self> {
+        let pixels_wide = usize::try_from(width)?;
+        let height = match projection {
+            Projection::Equirectangular => pixels_wide.div_euclid(2),
+            Projection::WebMercator => pixels_wide,
+        };
+
+        let mut canvas = Self {
+            width: pixels_wide,
+            height,
+            projection,
+            earth,
+            elevations: vec![0i32; pixels_wide * height],
+        };
+        for subtile in subtiles {
+            let pixel = canvas.to_pixel(geo::coord! {
+                x: f64::from(subtile.lon),
+                y: f64::from(subtile.lat)
+            });
+            if let Some(index) = canvas.index(pixel)
+                && let Some(elevation) = canvas.elevations.get_mut(index)
+            {
+                *elevation = (*elevation).max(subtile.max_height);
+            }
+        }
+
+        Ok(canvas)
+    }
+
+    /// The width and height of the image as floats.
+    #[expect(
+        clippy::as_conversions,
+        clippy::cast_precision_loss,
+        reason = "Images are much smaller than `f64`'s precision"
+    )]
+    const fn size(&self) -> (f64, f64) {
+        (self.width as f64, self.height as f64)
+    }
+
+    /// The pixel coordinates of a lon/lat point. They're fractional so that the shapes of tiles
+    /// aren't distorted by rounding.
+    fn to_pixel(&self, lonlat: geo::Coord) -> geo::Coord {
+        let (width, height) = self.size();
+        let y = match self.projection {
+            Projection::Equirectangular => (90.0f64 - lonlat.y) / 180.0f64,
+            Projection::WebMercator => {
+                let latitude = lonlat
+                    .y
+                    .clamp(-MERCATOR_MAX_LATITUDE, MERCATOR_MAX_LATITUDE)
+                    .to_radians();
+                (1.0f64 - latitude.tan().asinh() / std::f64::consts::PI) / 2.0f64
+            }
+        };
+
+        geo::coord! {
+            x: (lonlat.x + 180.0f64) / 360.0f64 * width,
+            y: y * height,
+        }
+    }
+
+    /// The index of the pixel that contains the pixel coordinates, if they're in the image.
+    #[expect(
+        clippy::as_conversions,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "The coordinates are checked to be positive and are floored"
+    )]
+    const fn index(&self, pixel: geo::Coord) -> Option<usize> {
+        if pixel.x < 0.0f64 || pixel.y < 0.0f64 {
+            return None;
+        }
+
+        let column = pixel.x.floor() as usize;
+        let row = pixel.y.floor() as usize;
+        if column >= self.width || row >= self.height {
+            return None;
+        }
+
+        Some(row * self.width + column)
+    }
+
+    /// The rings of a tile's polygons in pixel coordinates. There are 2 for tiles that cross the
+    /// antimeridian.
+    fn tile_rings(&self, tile: &crate::tile::Tile) -> Vec<Vec<geo::Coord>> {
+        tile.to_polygon_lonlat(self.earth)
+            .into_iter()
+            .map(|part| {
+                part.exterior()
+                    .coords()
+                    .map(|coord| self.to_pixel(*coord))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The indices of every pixel whose centre is inside a ring, found with a scanline fill.
+    #[expect(
+        clippy::as_conversions,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss,
+        reason = "Pixel coordinates are clamped to the image"
+    )]
+    fn fill(&self, ring: &[geo::Coord]) -> Vec<usize> {
+        let (width, height) = self.size();
+        let top = ring.iter().map(|coord| coord.y).fold(height, f64::min);
+        let bottom = ring.iter().map(|coord| coord.y).fold(0.0f64, f64::max);
+        let first_row = top.max(0.0f64).floor() as usize;
+        let last_row = bottom.min(height).ceil() as usize;
+
+        let mut indices = Vec::new();
+        for row in first_row..last_row {
+            let y = row as f64 + 0.5f64;
+            let mut crossings: Vec<f64> = ring
+                .windows(2)
+                .filter_map(|edge| {
+                    let &[from, to] = edge else {
+                        return None;
+                    };
+                    ((from.y <= y) != (to.y <= y))
+                        .then(|| ((y - from.y) / (to.y - from.y)).mul_add(to.x - from.x, from.x))
+                })
+                .collect();
+            crossings.sort_by(f64::total_cmp);
+
+            for span in crossings.chunks_exact(2) {
+                let &[start, end] = span else {
+                    continue;
+                };
+                let first_column = (start - 0.5f64).ceil().max(0.0f64);
+                let last_column = (end - 0.5f64).floor().min(width - 1.0f64);
+                if last_column < first_column {
+                    continue;
+                }
+                for column in (first_column as usize)..=(last_column as usize) {
+                    indices.push(row * self.width + column);
+                }
+            }
+        }
+
+        indices
+    }
+
+    /// The indices of every pixel along the edges of a ring. The edges where tiles are split at the
+    /// antimeridian aren't the tiles' real edges, so they're left out.
+    #[expect(
+        clippy::as_conversions,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss,
+        reason = "Edges are never longer than the image"
+    )]
+    fn outline(&self, ring: &[geo::Coord]) -> Vec<usize> {
+        let (width, _) = self.size();
+        let mut indices = Vec::new();
+        for edge in ring.windows(2) {
+            let &[from, to] = edge else {
+                continue;
+            };
+            let is_on_antimeridian = (from.x - to.x).abs() < 0.000_001f64
+                && (from.x.abs() < 0.000_001f64 || (from.x - width).abs() < 0.000_001f64);
+            if is_on_antimeridian {
+                continue;
+            }
+            let steps = ((to.x - from.x).abs().max((to.y - from.y).abs()).ceil() as usize).max(1);
+            for step in 0..=steps {
+                let fraction = step as f64 / steps as f64;
+                let point = geo::coord! {
+                    x: (to.x - from.x).mul_add(fraction, from.x),
+                    y: (to.y - from.y).mul_add(fraction, from.y),
+                };
+                indices.extend(self.index(point));
+            }
+        }
+
+        indices
+    }
+
+    /// The colour of the pixels of an elevation. Land gets brighter the higher it is.
+    #[expect(
+        clippy::as_conversions,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "The brightness is clamped to `u8`'s range"
+    )]
+    fn elevation_colour(elevation: i32) -> [u8; 3] {
+        if elevation <= 0i32 {
+            return SEA_COLOUR;
+        }
+
+        let brightness = (f64::from(elevation) / f64::from(super::HIGHEST_ELEVATION))
+            .clamp(0.0f64, 1.0f64)
+            .sqrt();
+        let grey = 195.0f64.mul_add(brightness, 60.0f64).round() as u8;
+        [grey; 3]
+    }
+
+    /// Draw the elevations and the tiles, biggest first, so that smaller tiles aren't hidden.
+    fn draw(&self, tiles: &[crate::tile::Tile]) -> Vec<[u8; 3]> {
+        let mut pixels: Vec<[u8; 3]> = self
+            .elevations
+            .iter()
+            .map(|elevation| Self::elevation_colour(*elevation))
+            .collect();
+
+        let widths = WidthRange::new(tiles);
+        for tile in biggest_first(tiles) {
+            let colour = widths.colour(tile.width);
+            for ring in self.tile_rings(&tile) {
+                for index in self.fill(&ring) {
+                    if let Some(pixel) = pixels.get_mut(index) {
+                        *pixel = mix(*pixel, colour, TILE_OPACITY);
+                    }
+                }
+                for index in self.outline(&ring) {
+                    if let Some(pixel) = pixels.get_mut(index) {
+                        *pixel = colour;
+                    }
+                }
+            }
+        }
+
+        pixels
+    }
+
+    /// Save the tiles as a PNG.
+    fn save_png(&self, tiles: &[crate::tile::Tile], path: &std::path::Path) -> Result<()> {
+        let pixels = self.draw(tiles);
+
+        let writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let mut encoder = png::Encoder::new(
+            writer,
+            u32::try_from(self.width)?,
+            u32::try_from(self.height)?,
+        );
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()?
+            .write_image_data(pixels.as_flattened())?;
+
+        tracing::info!("Saved {}x{} PNG to: {path:?}", self.width, self.height);
+        Ok(())
+    }
+
+    /// Save the tiles as an SVG. Each tile has a tooltip with its width and centre.
+    fn save_svg(&self, tiles: &[crate::tile::Tile], path: &std::path::Path) -> Result<()> {
+        let (width, height) = (self.width, self.height);
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+        )?;
+        writeln!(
+            svg,
+            r#"<rect width="100%" height="100%" fill="{}"/>"#,
+            hex(SEA_COLOUR)
+        )?;
+
+        for row in (0..height).step_by(SVG_CELL) {
+            for column in (0..width).step_by(SVG_CELL) {
+                let mut highest = 0i32;
+                for cell_row in row..(row + SVG_CELL).min(height) {
+                    for cell_column in column..(column + SVG_CELL).min(width) {
+                        if let Some(elevation) = self.elevations.get(cell_row * width + cell_column)
+                        {
+                            highest = highest.max(*elevation);
+                        }
+                    }
+                }
+                if highest > 0i32 {
+                    writeln!(
+                        svg,
+                        r#"<rect x="{column}" y="{row}" width="{SVG_CELL}" height="{SVG_CELL}" fill="{}"/>"#,
+                        hex(Self::elevation_colour(highest))
+                    )?;
+                }
+            }
+        }
+
+        let widths = WidthRange::new(tiles);
+        for tile in biggest_first(tiles) {
+            let mut outline = String::new();
+            for ring in self.tile_rings(&tile) {
+                for (index, point) in ring.iter().enumerate() {
+                    let command = if index == 0 { "M" } else { "L" };
+                    write!(outline, "{command}{:.1},{:.1} ", point.x, point.y)?;
+                }
+                outline.push('Z');
+            }
+            let colour = hex(widths.colour(tile.width));
+            writeln!(
+                svg,
+                r#"<path d="{outline}" fill="{colour}" fill-opacity="{TILE_OPACITY}" stroke="{colour}"><title>{:.0}m wide tile at {:.4},{:.4}</title></path>"#,
+                tile.width, tile.centre.0.x, tile.centre.0.y
+            )?;
+        }
+        svg.push_str("</svg>\n");
+
+        std::fs::write(path, svg)?;
+        tracing::info!("Saved {width}x{height} SVG to: {path:?}");
+        Ok(())
+    }
+}
+
+/// The smallest and biggest widths of some tiles, for colouring them.
+struct WidthRange {
+    /// The width of the smallest tile.
+    smallest: f32,
+    /// The width of the biggest tile.
+    biggest: f32,
+}
+
+impl WidthRange {
+    /// Find the range of widths of some tiles.
+    fn new(tiles: &[crate::tile::Tile]) -> Self {
+        let widths = tiles.iter().map(|tile| tile.width);
+        Self {
+            smallest: widths.clone().fold(f32::INFINITY, f32::min),
+            biggest: widths.fold(0.0, f32::max),
+        }
+    }
+
+    /// The colour of a tile's width.
+    #[expect(
+        clippy::as_conversions,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss,
+        reason = "There are only a handful of colours"
+    )]
+    fn colour(&self, width: f32) -> [u8; 3] {
+        let range = (self.biggest - self.smallest).max(f32::EPSILON);
+        let position = f64::from(((width - self.smallest) / range).clamp(0.0, 1.0))
+            * (WIDTH_COLOURS.len() - 1) as f64;
+        let index = position.floor() as usize;
+        let Some(from) = WIDTH_COLOURS.get(index) else {
+            return SEA_COLOUR;
+        };
+        let to = WIDTH_COLOURS.get(index + 1).unwrap_or(from);
+
+        mix(*from, *to, position - position.floor())
+    }
+}
+
+/// Tiles sorted from the biggest to the smallest.
+fn biggest_first(tiles: &[crate::tile::Tile]) -> Vec<crate::tile::Tile> {
+    let mut sorted = tiles.to_vec();
+    sorted.sort_by(|left, right| right.width.total_cmp(&left.width));
+    sorted
+}
+
+/// Mix a colour with another colour by an amount from 0.0 to 1.0.
+#[expect(
+    clippy::as_conversions,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    reason = "Mixing 2 `u8`s is always in `u8`'s range"
+)]
+fn mix(colour: [u8; 3], other: [u8; 3], amount: f64) -> [u8; 3] {
+    let mut mixed = colour;
+    for (channel, target) in mixed.iter_mut().zip(other) {
+        let start = f64::from(*channel);
+        *channel = (f64::from(target) - start).mul_add(amount, start).round() as u8;
+    }
+    mixed
+}
+
+/// A colour as an SVG hex string.
+fn hex(colour: [u8; 3]) -> String {
+    let [red, green, blue] = colour;
+    format!("#{red:02x}{green:02x}{blue:02x}")
+}
+
+/// Save the tiles as `GeoJSON` polygons, along with the highest point of each tile when the tiles
+/// file has them.
+fn save_geojson(
+    tiles: &[crate::tiles::PackedTile],
+    earth: crate::projector::Earth,
+    path: &std::path::Path,
+) -> Result<()> {
+    let mut features = Vec::new();
+    for packed in tiles {
+        let mut properties = geojson::JsonObject::new();
+        properties.insert("width".to_owned(), packed.tile.width.into());
+
+        if let Some(details) = &packed.details {
+            properties.insert(
+                "highest_elevation".to_owned(),
+                details.highest_elevation.into(),
+            );
+            properties.insert(
+                "covered_subtiles".to_owned(),
+                details.covered_subtiles.into(),
+            );
+            properties.insert("overlap_ratio".to_owned(), details.overlap_ratio.into());
+
+            let mut point_properties = geojson::JsonObject::new();
+            point_properties.insert("elevation".to_owned(), details.highest_elevation.into());
+            features.push(feature(
+                geojson::Geometry::from(&geo::Point::from(details.highest.0)),
+                point_properties,
+            ));
+        }
+
+        features.push(feature(
+            geojson::Geometry::from(&packed.tile.to_polygon_lonlat(earth)),
+            properties,
+        ));
+    }
+
+    let feature_collection = geojson::FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    };
+
+    tracing::info!("Saving {} tiles as GeoJSON to: {path:?}", tiles.len());
+    std::fs::write(path, geojson::GeoJson::from(feature_collection).to_string())?;
+
+    Ok(())
+}
+
+/// A `GeoJSON` feature.
+const fn feature(geometry: geojson::Geometry, properties: geojson::JsonObject) -> geojson::Feature {
+    geojson::Feature {
+        bbox: None,
+        geometry: Some(geometry),
+        id: None,
+        properties: Some(properties),
+        foreign_members: None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn projections() {
+        let equirectangular = Canvas::new(
+            360,
+            Projection::Equirectangular,
+            crate::projector::Earth::Wgs84,
+            &[],
+        )
+        .unwrap();
+        let mercator = Canvas::new(
+            360,
+            Projection::WebMercator,
+            crate::projector::Earth::Wgs84,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(equirectangular.height, 180);
+        assert_eq!(mercator.height, 360);
+
+        for canvas in [&equirectangular, &mercator] {
+            let (width, height) = canvas.size();
+            let centre = canvas.to_pixel(geo::coord! { x: 0.0f64, y: 0.0f64 });
+            assert!((centre.x - width / 2.0f64).abs() < 0.000_001f64);
+            assert!((centre.y - height / 2.0f64).abs() < 0.000_001f64);
+            let corner = canvas.to_pixel(geo::coord! { x: -180.0f64, y: 90.0f64 });
+            assert!(corner.x.abs() < 0.000_001f64);
+            assert!(corner.y.abs() < 0.000_001f64);
+        }
+
+        // Web Mercator stretches latitudes further apart the further they are from the equator.
+        let north = geo::coord! { x: 0.0f64, y: 60.0f64 };
+        assert!((equirectangular.to_pixel(north).y - 30.0f64).abs() < 0.000_001f64);
+        assert!((mercator.to_pixel(north).y - 104.54f64).abs() < 0.01f64);
+    }
+
+    #[test]
+    fn fills_tiles() {
+        let canvas = Canvas::new(
+            360,
+            Projection::Equirectangular,
+            crate::projector::Earth::Wgs84,
+            &[],
+        )
+        .unwrap();
+        let square = [
+            geo::coord! { x: 10.0f64, y: 10.0f64 },
+            geo::coord! { x: 20.0f64, y: 10.0f64 },
+            geo::coord! { x: 20.0f64, y: 20.0f64 },
+            geo::coord! { x: 10.0f64, y: 20.0f64 },
+            geo::coord! { x: 10.0f64, y: 10.0f64 },
+        ];
+        let filled = canvas.fill(&square);
+        assert_eq!(filled.len(), 100);
+        assert!(filled.contains(&(10 * 360 + 10)));
+        assert!(!filled.contains(&(20 * 360 + 20)));
+
+        let tile = crate::tile::Tile {
+            centre: crate::projector::LonLatCoord(geo::coord! { x: 179.9f64, y: 0.0f64 }),
+            width: 500_000.0,
+        };
+        let pixels = canvas.draw(&[tile]);
+        let colour = WidthRange::new(&[tile]).colour(tile.width);
+        let east = pixels.get(
+            canvas
+                .index(geo::coord! { x: 359.5f64, y: 90.0f64 })
+                .unwrap(),
+        );
+        let west = pixels.get(canvas.index(geo::coord! { x: 0.5f64, y: 90.0f64 }).unwrap());
+        assert_eq!(east, Some(&mix(SEA_COLOUR, colour, TILE_OPACITY)));
+        assert_eq!(east, west);
+
+        let error = Format::from_path(std::path::Path::new("tiles.webp")).unwrap_err();
+        assert!(error.to_string().starts_with("Can't render to"));
+    }
+}
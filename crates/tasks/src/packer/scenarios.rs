@@ -0,0 +1,270 @@
+//! Pack small synthetic worlds and check the tiles that come out.
+//!
+//! Each scenario is a handful of max subtiles that exercises a different part of the packer. The
+//! packed tiles must cover every subtile with a big enough tile, and they must match the snapshot
+//! of the scenario's tiles in `tests/snapshots/packer/`, so that any change to the packing gets
+//! noticed. A missing snapshot is a failure. After an intended change to the packing, or to add a
+//! new scenario, record them all again with `UPDATE_SNAPSHOTS=1 cargo test scenarios`.
+
+use super::*;
+
+/// The resolution of the real max subtiles, in subtiles per degree.
+const RESOLUTION: f32 = 10.0;
+
+/// How different, in meters, a tile's width can be to its snapshot. Widths come from geodesics and
+/// projections, so they can differ in their last digits between platforms.
+const WIDTH_TOLERANCE: f32 = 1.0;
+
+/// A single max subtile. Longitudes are wrapped, so that hills can straddle the antimeridian.
+fn subtile(lon: f32, lat: f32, max_height: i32) -> crate::max_subtile::MaxSubTile {
+    crate::max_subtile::MaxSubTile {
+        lon: (lon + 180.0).rem_euclid(360.0) - 180.0,
+        lat,
+        max_height,
+    }
+}
+
+/// A square of subtiles centred on a peak, with heights that fall away from the peak to nothing
+/// just past `radius` subtiles away.
+fn hill(lon: f32, lat: f32, peak: i32, radius: i16) -> Vec<crate::max_subtile::MaxSubTile> {
+    let mut subtiles = Vec::new();
+    for row in -radius..=radius {
+        for column in -radius..=radius {
+            let distance = i32::from(row.abs().max(column.abs()));
+            let steps = i32::from(radius) + 1i32;
+            subtiles.push(subtile(
+                lon + f32::from(column) / RESOLUTION,
+                lat + f32::from(row) / RESOLUTION,
+                (peak * (steps - distance)).div_euclid(steps),
+            ));
+        }
+    }
+
+    subtiles
+}
+
+/// Pack the whole synthetic world with the default config.
+fn pack(
+    subtiles: &[crate::max_subtile::MaxSubTile],
+) -> (
+    crate::config::Packer,
+    crate::tiles::Metadata,
+    Vec<crate::tile::Tile>,
+) {
+    let config = <crate::config::Packer as clap::Parser>::parse_from(["packer"]);
+    let mut packer = Packer::from_subtiles(config.clone(), subtiles).unwrap();
+    packer.run_all().unwrap();
+
+    (config, packer.metadata(), packer.tiles())
+}
+
+/// Pack a scenario, check that its tiles cover everything and compare them to its snapshot.
+#[expect(
+    clippy::float_cmp,
+    reason = "Tiles are centred on the exact subtile coordinates"
+)]
+fn assert_packs(name: &str, subtiles: &[crate::max_subtile::MaxSubTile]) {
+    let (config, metadata, tiles) = pack(subtiles);
+    assert!(!tiles.is_empty(), "{name}: no tiles were packed");
+
+    let violations =
+        verify::find_violations(subtiles, &tiles, &config.line_of_sight, config.earth).unwrap();
+    assert!(violations.is_empty(), "{name}: {violations:?}");
+
+    for tile in &tiles {
+        assert!(tile.width > 0.0, "{name}: empty tile {tile:?}");
+        assert!(
+            subtiles
+                .iter()
+                .any(|subtile| f64::from(subtile.lon) == tile.centre.0.x
+                    && f64::from(subtile.lat) == tile.centre.0.y),
+            "{name}: tile isn't centred on a subtile {tile:?}"
+        );
+    }
+
+    assert_snapshot(name, metadata, &tiles);
+}
+
+/// Compare tiles to the snapshot of a scenario, or record the snapshot when `UPDATE_SNAPSHOTS` is
+/// set. The snapshot has the packer's real metadata, so that it's loaded on the right earth.
+fn assert_snapshot(name: &str, metadata: crate::tiles::Metadata, tiles: &[crate::tile::Tile]) {
+    let directory = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots/packer");
+    let path = directory.join(format!("{name}.csv"));
+    let file = crate::tiles::File {
+        metadata,
+        tiles: tiles
+            .iter()
+            .map(|tile| crate::tiles::PackedTile {
+                tile: *tile,
+                details: None,
+            })
+            .collect(),
+    };
+    let csv = file.to_csv().unwrap();
+
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(&path, csv).unwrap();
+        return;
+    }
+
+    let Ok(contents) = std::fs::read_to_string(&path) else {
+        panic!("{name}: no snapshot at {path:?}, record it with `UPDATE_SNAPSHOTS=1`");
+    };
+    let snapshot = crate::tiles::File::parse(&contents).unwrap();
+    assert_eq!(snapshot.metadata, file.metadata, "{name}: metadata changed");
+    let matches = snapshot.tiles.len() == tiles.len()
+        && snapshot.tiles.iter().zip(tiles).all(|(expected, actual)| {
+            expected.tile.centre == actual.centre
+                && (expected.tile.width - actual.width).abs() <= WIDTH_TOLERANCE
+        });
+    assert!(
+        matches,
+        "{name}: packed tiles don't match {path:?}. Packed:\n{csv}"
+    );
+}
+
+#[test]
+fn single_peak() {
+    assert_packs("single_peak", &hill(10.0, 45.0, 2000, 5));
+}
+
+#[test]
+fn mountain_range() {
+    let mut subtiles = Vec::new();
+    for index in 0i16..=16 {
+        let peak = 3000i32 + (i32::from(index) * 97i32).rem_euclid(1500);
+        subtiles.extend(hill(f32::from(index).mul_add(0.5, 6.0), 46.0, peak, 2));
+    }
+
+    assert_packs("mountain_range", &subtiles);
+}
+
+#[test]
+fn coastline_islands() {
+    let mut subtiles = Vec::new();
+    subtiles.extend(hill(-5.0, 50.0, 300, 2));
+    subtiles.extend(hill(-4.0, 50.3, 80, 1));
+    subtiles.extend(hill(-3.2, 49.8, 150, 1));
+    // The sea between the islands.
+    for column in 0i16..40 {
+        subtiles.push(subtile(-6.0 + f32::from(column) / RESOLUTION, 49.5, 0));
+    }
+
+    assert_packs("coastline_islands", &subtiles);
+}
+
+#[test]
+fn antimeridian() {
+    let mut subtiles = Vec::new();
+    subtiles.extend(hill(179.9, -17.5, 1200, 2));
+    subtiles.extend(hill(-179.9, -17.0, 900, 2));
+    assert!(subtiles.iter().any(|subtile| subtile.lon > 179.0));
+    assert!(subtiles.iter().any(|subtile| subtile.lon < -179.0));
+
+    assert_packs("antimeridian", &subtiles);
+}
+
+#[test]
+fn bands_pack_across_their_seams() {
+    // A line of hills running North to South through every band and every seam between them.
+    let mut subtiles = Vec::new();
+    for index in -14i16..=14 {
+        subtiles.extend(hill(10.0, f32::from(index) * 5.0, 1000, 1));
+    }
+    let config = <crate::config::Packer as clap::Parser>::parse_from(["packer", "--bands", "3"]);
+    let mut packer = Packer::from_subtiles(config.clone(), &subtiles).unwrap();
+    packer.run_all().unwrap();
+    let tiles = packer.tiles();
+
+    let violations =
+        verify::find_violations(&subtiles, &tiles, &config.line_of_sight, config.earth).unwrap();
+    assert!(violations.is_empty(), "{violations:?}");
+    for (index, tile) in tiles.iter().enumerate() {
+        assert!(
+            tiles
+                .iter()
+                .skip(index + 1)
+                .all(|other| other.centre != tile.centre),
+            "Duplicate tile: {tile:?}"
+        );
+    }
+}
+
+#[test]
+fn repack_a_region() {
+    let directory = tempfile::tempdir().unwrap();
+    let base = directory.path().join("base.csv");
+    let repacked = directory.path().join("repacked.csv");
+
+    // Hills far enough apart that their tiles don't touch.
+    let hills = |middle_peak: i32| {
+        let mut subtiles = hill(0.0, 45.0, 1000, 2);
+        subtiles.extend(hill(8.0, 45.0, middle_peak, 2));
+        subtiles.extend(hill(16.0, 45.0, 1000, 2));
+        subtiles
+    };
+    let base_config = <crate::config::Packer as clap::Parser>::parse_from([
+        "packer",
+        "--checkpoint",
+        &directory.path().join("checkpoint").display().to_string(),
+    ]);
+    let mut base_packer = Packer::from_subtiles(base_config, &hills(1000i32)).unwrap();
+    base_packer.output = Some(base.clone());
+    base_packer.run_all().unwrap();
+
+    // The middle hill turns out to be higher.
+    let subtiles = hills(2500i32);
+    let config = <crate::config::Packer as clap::Parser>::parse_from([
+        "packer",
+        "--region",
+        "7.5,44.5,8.5,45.5",
+        "--base",
+        &base.display().to_string(),
+        "--output",
+        &repacked.display().to_string(),
+    ]);
+    let mut region_packer = Packer::from_subtiles(config.clone(), &subtiles).unwrap();
+    region_packer.output = Some(repacked.clone());
+    region_packer.run_all().unwrap();
+
+    let tiles = region_packer.tiles();
+    let violations =
+        verify::find_violations(&subtiles, &tiles, &config.line_of_sight, config.earth).unwrap();
+    assert!(violations.is_empty(), "{violations:?}");
+
+    let base_file = crate::tiles::File::load(&base).unwrap();
+    let base_tiles: Vec<crate::tile::Tile> =
+        base_file.tiles.iter().map(|packed| packed.tile).collect();
+    let region_polygon = region::to_polygon(config.region.as_ref().unwrap()).unwrap();
+    let (repack_area, _) = region::repack_area(&region_polygon, &base_tiles, config.earth);
+
+    // Only the tile columns, as the details of a tile can change with its new neighbours.
+    let rows = |path: &std::path::Path| -> Vec<String> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .skip(2)
+            .map(|line| line.split(',').take(3).collect::<Vec<_>>().join(","))
+            .collect()
+    };
+    let base_rows = rows(&base);
+    let repacked_rows = rows(&repacked);
+    let outside: Vec<&String> = base_tiles
+        .iter()
+        .zip(&base_rows)
+        .filter(|(tile, _)| {
+            !tile
+                .to_polygon_lonlat(config.earth)
+                .intersects(&repack_area)
+        })
+        .map(|(_, row)| row)
+        .collect();
+    assert_eq!(outside.len(), 2, "{base_rows:?}");
+    for row in outside {
+        assert!(
+            repacked_rows.contains(row),
+            "{row} changed: {repacked_rows:?}"
+        );
+    }
+}
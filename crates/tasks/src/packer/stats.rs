@@ -0,0 +1,182 @@
+//! Estimate how much compute it takes to process all the tiles in a tiles file.
+
+use color_eyre::{Result, eyre::ContextCompat as _};
+
+/// The number of bars in the histogram of tile widths.
+const HISTOGRAM_BINS: usize = 20;
+
+/// The widest bar of the histogram, in characters.
+const HISTOGRAM_WIDTH: usize = 60;
+
+/// Statistics about a set of tiles.
+#[derive(Debug)]
+pub struct Stats {
+    /// The number of tiles.
+    pub count: usize,
+    /// The median width of the tiles, in meters.
+    pub median_width: f32,
+    /// The width of the biggest tile needed to cover half the world's area when processing tiles
+    /// from smallest to biggest.
+    pub half_area_width: f32,
+    /// The number of tiles needed to cover half the world's area when processing tiles from
+    /// smallest to biggest.
+    pub half_area_count: usize,
+    /// The estimated CPU seconds to compute every tile.
+    pub cpu_seconds: f64,
+    /// The number of tiles with widths in each bin of the histogram.
+    pub histogram: Vec<usize>,
+}
+
+impl Stats {
+    /// Calculate the statistics for some tiles.
+    pub fn new(tiles: &[crate::tile::Tile]) -> Result<Self> {
+        let mut widths: Vec<f32> = tiles.iter().map(|tile| tile.width).collect();
+        widths.sort_by(f32::total_cmp);
+
+        let middle = widths.len().div_euclid(2);
+        let median_width = if widths.len().is_multiple_of(2) {
+            (widths.get(middle.saturating_sub(1)).context("No tiles")?
+                + widths.get(middle).context("No tiles")?)
+                / 2.0
+        } else {
+            *widths.get(middle).context("No tiles")?
+        };
+
+        let total_area: f64 = widths.iter().map(|width| f64::from(*width).powi(2)).sum();
+        let mut area = 0.0f64;
+        let mut half_area_width = 0.0;
+        let mut half_area_count = 0;
+        for (index, width) in widths.iter().enumerate() {
+            area += f64::from(*width).powi(2);
+            if area > total_area / 2.0f64 {
+                half_area_width = *width;
+                half_area_count = index + 1;
+                break;
+            }
+        }
+
+        let cpu_seconds = widths
+            .iter()
+            .map(|width| f64::from(super::cost::compute_seconds(*width)))
+            .sum();
+
+        Ok(Self {
+            count: widths.len(),
+            median_width,
+            half_area_width,
+            half_area_count,
+            cpu_seconds,
+            histogram: Self::histogram(&widths),
+        })
+    }
+
+    /// Count the tiles in equally sized bins of widths, from the smallest to the biggest tile.
+    fn histogram(sorted_widths: &[f32]) -> Vec<usize> {
+        let mut bins = vec![0; HISTOGRAM_BINS];
+        let (Some(smallest), Some(biggest)) = (sorted_widths.first(), sorted_widths.last()) else {
+            return bins;
+        };
+        let range = (biggest - smallest).max(f32::EPSILON);
+
+        for width in sorted_widths {
+            #[expect(
+                clippy::as_conversions,
+                clippy::cast_possible_truncation,
+                clippy::cast_sign_loss,
+                clippy::cast_precision_loss,
+                reason = "The bin is always a small positive number"
+            )]
+            let bin = (((width - smallest) / range) * HISTOGRAM_BINS as f32) as usize;
+            if let Some(count) = bins.get_mut(bin.min(HISTOGRAM_BINS - 1)) {
+                *count += 1;
+            }
+        }
+
+        bins
+    }
+}
+
+/// Entrypoint.
+pub fn run(config: &crate::config::Stats) -> Result<()> {
+    let tiles = crate::tiles::load(&config.tiles)?;
+    let stats = Stats::new(&tiles)?;
+
+    let cpu_hours = stats.cpu_seconds / 60.0f64 / 60.0f64;
+    let days = cpu_hours / 24.0f64 / f64::from(config.cores);
+    let cost = cpu_hours * f64::from(config.cost_per_core_hour);
+
+    #[expect(clippy::print_stdout, reason = "Gotta output the stats somehow")]
+    {
+        println!("Tiles: {}", stats.count);
+        println!("Median width: {:.1}km", stats.median_width / 1000.0);
+        println!(
+            "Must process all tiles up to {:.1}km to get 50% of the world, which is {} tiles",
+            stats.half_area_width / 1000.0,
+            stats.half_area_count
+        );
+        println!(
+            "It will take {days:.1} days to compute on {} cores and cost ${cost:.2}",
+            config.cores
+        );
+
+        let (Some(smallest), Some(biggest)) = (
+            tiles.iter().map(|tile| tile.width).min_by(f32::total_cmp),
+            tiles.iter().map(|tile| tile.width).max_by(f32::total_cmp),
+        ) else {
+            return Ok(());
+        };
+        let most = stats
+            .histogram
+            .iter()
+            .max()
+            .copied()
+            .unwrap_or_default()
+            .max(1);
+        #[expect(
+            clippy::as_conversions,
+            clippy::cast_precision_loss,
+            reason = "There are only a few bins"
+        )]
+        let bin_width = (biggest - smallest) / HISTOGRAM_BINS as f32;
+        println!("Widths:");
+        for (index, count) in stats.histogram.iter().enumerate() {
+            #[expect(
+                clippy::as_conversions,
+                clippy::cast_precision_loss,
+                reason = "There are only a few bins"
+            )]
+            let from = bin_width.mul_add(index as f32, smallest);
+            let bar = "#".repeat((count * HISTOGRAM_WIDTH).div_euclid(most));
+            println!("{:>6.1}km {count:>6} {bar}", from / 1000.0);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tile(width: f32) -> crate::tile::Tile {
+        crate::tile::Tile {
+            centre: crate::projector::LonLatCoord(geo::coord! { x: 0.0f64, y: 0.0f64 }),
+            width,
+        }
+    }
+
+    #[expect(clippy::float_cmp, reason = "The widths are exact values")]
+    #[test]
+    fn half_area_and_median() {
+        let tiles = [tile(10.0), tile(10.0), tile(10.0), tile(20.0), tile(30.0)];
+        let stats = Stats::new(&tiles).unwrap();
+
+        assert_eq!(stats.count, 5);
+        assert_eq!(stats.median_width, 10.0);
+        assert_eq!(stats.half_area_width, 30.0);
+        assert_eq!(stats.half_area_count, 5);
+        assert_eq!(stats.histogram.iter().sum::<usize>(), 5);
+        assert_eq!(stats.histogram.first(), Some(&3));
+        assert_eq!(stats.histogram.last(), Some(&1));
+    }
+}
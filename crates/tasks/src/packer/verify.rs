@@ -0,0 +1,244 @@
+//! Prove that a tiles file covers the whole world.
+//!
+//! Every max subtile above `MINIMUM_HEIGHT` must be covered by at least one tile that is at least
+//! as wide as the longest theoretical line of sight from the subtile's highest point. Any subtile
+//! that isn't is a violation, and they're all saved as `GeoJSON` so they can be inspected on a map.
+
+use color_eyre::Result;
+use geo::Intersects as _;
+
+use crate::projector::LonLatCoord;
+
+/// A max subtile that isn't covered by a big enough tile.
+#[derive(Debug, Clone, Copy)]
+pub struct Violation {
+    /// The max subtile.
+    pub subtile: crate::max_subtile::MaxSubTile,
+    /// The width that a tile needs to be to contain every line of sight from the subtile.
+    pub required_width: f32,
+    /// The widest tile that covers the subtile, if there is one.
+    pub widest_covering_width: Option<f32>,
+}
+
+/// Entrypoint.
+pub fn run(config: &crate::config::Verify) -> Result<()> {
+    let subtiles =
+        crate::max_subtile::Subtiler::load(&config.subtiles, config.legacy_subtiles)?.subtiles;
+    let file = crate::tiles::File::load(&config.tiles)?;
+    let line_of_sight = line_of_sight(&file.metadata, &config.line_of_sight);
+    let earth = file.metadata.earth();
+    let tiles: Vec<crate::tile::Tile> = file.tiles.iter().map(|packed| packed.tile).collect();
+    tracing::info!(
+        "Verifying {} tiles against {} max subtiles, with {line_of_sight:?} on {earth:?}",
+        tiles.len(),
+        subtiles.len()
+    );
+
+    let violations = find_violations(&subtiles, &tiles, &line_of_sight, earth)?;
+    save_violations(&violations, &config.output)?;
+
+    if !violations.is_empty() {
+        color_eyre::eyre::bail!(
+            "{} max subtiles aren't covered by a big enough tile, see: {:?}",
+            violations.len(),
+            config.output
+        );
+    }
+
+    tracing::info!("Every max subtile is covered by a big enough tile");
+    Ok(())
+}
+
+/// The line of sight that the tiles were packed for. Only tiles files that don't record it use the
+/// one from the CLI.
+pub fn line_of_sight(
+    metadata: &crate::tiles::Metadata,
+    fallback: &crate::config::LineOfSight,
+) -> crate::config::LineOfSight {
+    if let Some(packer) = &metadata.packer {
+        return packer.line_of_sight;
+    }
+
+    tracing::warn!("The tiles don't record their line of sight, using {fallback:?}");
+    *fallback
+}
+
+/// Find all the max subtiles that aren't covered by a big enough tile. The highest ones come first.
+/// The tiles' polygons are made on the earth that they were packed on.
+pub fn find_violations(
+    subtiles: &[crate::max_subtile::MaxSubTile],
+    tiles: &[crate::tile::Tile],
+    line_of_sight: &crate::config::LineOfSight,
+    earth: crate::projector::Earth,
+) -> Result<Vec<Violation>> {
+    let points: Vec<rstar::primitives::GeomWithData<LonLatCoord, usize>> = subtiles
+        .iter()
+        .enumerate()
+        .filter(|(_, subtile)| subtile.max_height > super::MINIMUM_HEIGHT)
+        .map(|(index, subtile)| {
+            rstar::primitives::GeomWithData::new(
+                LonLatCoord(geo::coord! { x: subtile.lon.into(), y: subtile.lat.into() }),
+                index,
+            )
+        })
+        .collect();
+    let tree = rstar::RTree::bulk_load(points);
+
+    let mut widest: Vec<Option<f32>> = vec![None; subtiles.len()];
+    for tile in tiles {
+        let polygon = tile.to_polygon_lonlat(earth);
+        for aabb in crate::tile::Tile::polygon_aabbs_lonlat(&polygon)? {
+            for point in tree.locate_in_envelope_intersecting(&aabb) {
+                if !polygon.intersects(&point.geom().0) {
+                    continue;
+                }
+
+                if let Some(width) = widest.get_mut(point.data) {
+                    *width = Some(width.map_or(tile.width, |current| current.max(tile.width)));
+                }
+            }
+        }
+    }
+
+    let mut violations = Vec::new();
+    for point in &tree {
+        let subtile = *subtiles
+            .get(point.data)
+            .ok_or_else(|| color_eyre::eyre::eyre!("No subtile at index {}", point.data))?;
+        let widest_covering_width = widest.get(point.data).copied().flatten();
+        let required_width =
+            super::Packer::minimum_tile_size_for_elevation(subtile.max_height, line_of_sight);
+        if widest_covering_width.is_none_or(|width| width < required_width) {
+            violations.push(Violation {
+                subtile,
+                required_width,
+                widest_covering_width,
+            });
+        }
+    }
+    violations.sort_by_key(|violation| std::cmp::Reverse(violation.subtile.max_height));
+
+    Ok(violations)
+}
+
+/// Save the violations as `GeoJSON` points.
+fn save_violations(violations: &[Violation], path: &std::path::Path) -> Result<()> {
+    let mut features = Vec::new();
+    for violation in violations {
+        let point = geo::Point::new(
+            f64::from(violation.subtile.lon),
+            f64::from(violation.subtile.lat),
+        );
+        let mut properties = geojson::JsonObject::new();
+        properties.insert("max_height".to_owned(), violation.subtile.max_height.into());
+        properties.insert("required_width".to_owned(), violation.required_width.into());
+        properties.insert(
+            "widest_covering_width".to_owned(),
+            violation.widest_covering_width.into(),
+        );
+        properties.insert(
+            "violation".to_owned(),
+            if violation.widest_covering_width.is_some() {
+                "too_small"
+            } else {
+                "uncovered"
+            }
+            .into(),
+        );
+
+        features.push(geojson::Feature {
+            bbox: None,
+            geometry: Some(geojson::Geometry::from(&point)),
+            id: None,
+            properties: Some(properties),
+            foreign_members: None,
+        });
+    }
+
+    let feature_collection = geojson::FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    };
+
+    tracing::info!("Saving {} violations to: {path:?}", violations.len());
+    std::fs::write(path, geojson::GeoJson::from(feature_collection).to_string())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn subtile(lon: f32, lat: f32, max_height: i32) -> crate::max_subtile::MaxSubTile {
+        crate::max_subtile::MaxSubTile {
+            lon,
+            lat,
+            max_height,
+        }
+    }
+
+    #[test]
+    fn finds_uncovered_and_too_small_subtiles() {
+        let tiles = vec![crate::tile::Tile {
+            centre: LonLatCoord(geo::coord! { x: 0.0f64, y: 0.0f64 }),
+            width: 300_000.0,
+        }];
+        let subtiles = vec![
+            subtile(0.5, 0.5, 1000),
+            subtile(0.1, 0.1, 3000),
+            subtile(10.0, 10.0, 500),
+            subtile(20.0, 20.0, 50),
+        ];
+
+        let line_of_sight = crate::config::LineOfSight {
+            refraction: crate::horizon::DEFAULT_REFRACTION,
+            observer_height: 0.0,
+            target_height: 0.0,
+        };
+
+        let violations = find_violations(
+            &subtiles,
+            &tiles,
+            &line_of_sight,
+            crate::projector::Earth::Wgs84,
+        )
+        .unwrap();
+        assert_eq!(violations.len(), 2);
+
+        let too_small = violations.first().unwrap();
+        assert_eq!(too_small.subtile.max_height, 3000i32);
+        assert!(too_small.widest_covering_width.is_some());
+
+        let uncovered = violations.last().unwrap();
+        assert_eq!(uncovered.subtile.max_height, 500i32);
+        assert!(uncovered.widest_covering_width.is_none());
+    }
+
+    #[test]
+    fn uses_the_recorded_line_of_sight() {
+        let cli = crate::config::LineOfSight {
+            refraction: 0.0,
+            observer_height: 0.0,
+            target_height: 0.0,
+        };
+        let recorded = crate::config::LineOfSight {
+            refraction: crate::horizon::DEFAULT_REFRACTION,
+            ..cli
+        };
+        let mut metadata = crate::tiles::File::parse("0,0,1000\n").unwrap().metadata;
+        assert_eq!(line_of_sight(&metadata, &cli), cli);
+
+        metadata.packer = Some(crate::tiles::PackerParameters {
+            cost_model: crate::packer::cost::Model::default(),
+            bands: 1,
+            minimum_height: 100,
+            window_radius: 1_800_000.0,
+            maximum_tile_width: 800_000.0,
+            line_of_sight: recorded,
+            earth: crate::projector::Earth::Wgs84,
+        });
+        assert_eq!(line_of_sight(&metadata, &cli), recorded);
+    }
+}
@@ -0,0 +1,259 @@
+//! Sample an elevation profile between two points.
+//!
+//! This is for checking by hand whether a claimed line of sight is real. The points are sampled
+//! along the geodesic between the two points from the same virtual DEM that is used to stitch
+//! tiles. Each sample includes how much the earth's curvature, less refraction, drops the terrain
+//! away from the observer, so the profile can be plotted as a flat chart with the line of sight ray
+//! drawn straight from the observer to the target.
+
+use color_eyre::{Result, eyre::ContextCompat as _};
+
+use crate::projector::LonLatCoord;
+
+/// The default distance between samples in meters. It's the same resolution that tiles are
+/// stitched at.
+pub const DEFAULT_SPACING: f64 = 100.0;
+
+/// The maximum number of samples in a single profile.
+pub const MAX_SAMPLES: usize = 10_000;
+
+/// A single point along the profile.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct Sample {
+    /// The distance of the sample from the observer in meters.
+    pub distance: f64,
+    /// The lon/lat of the sample.
+    pub coordinate: LonLatCoord,
+    /// The elevation of the sample in meters. `None` if the DEM has no data here.
+    pub elevation: Option<f32>,
+    /// How far the sample has dropped below the observer's horizontal plane because of the
+    /// curvature of the earth, less refraction.
+    pub curvature_drop: f64,
+    /// The height of the line of sight ray at this sample. It is relative to the observer's
+    /// horizontal plane, so it should be compared to `elevation - curvature_drop`.
+    pub ray: f64,
+}
+
+/// An elevation profile between an observer and a target.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Profile {
+    /// The point of view.
+    pub from: LonLatCoord,
+    /// The point being looked at.
+    pub to: LonLatCoord,
+    /// The geodesic distance between the observer and the target in meters.
+    pub distance: f64,
+    /// The initial bearing from the observer to the target in degrees clockwise from North.
+    pub bearing: f64,
+    /// The refraction and the heights of the observer and target.
+    #[serde(flatten)]
+    pub line_of_sight: crate::config::LineOfSight,
+    /// Whether the target can be seen, ie no terrain breaks the line of sight ray.
+    pub is_visible: bool,
+    /// Whether the target is close enough to be seen at all over a perfectly smooth earth. If it
+    /// isn't, then any claim of a line of sight between them must be wrong.
+    pub is_plausible: bool,
+    /// All the samples from the observer to the target, inclusive.
+    pub samples: Vec<Sample>,
+}
+
+impl Profile {
+    /// Sample the elevation profile from the given DEM, usually the virtual DEM built by
+    /// `stitch::build_virtual_dem()`. The DEM must be in lon/lat coordinates.
+    #[expect(
+        clippy::as_conversions,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss,
+        reason = "The sample count is clamped to `MAX_SAMPLES`"
+    )]
+    pub fn sample(
+        dem: &std::path::Path,
+        from: LonLatCoord,
+        to: LonLatCoord,
+        line_of_sight: crate::config::LineOfSight,
+        earth: crate::projector::Earth,
+        spacing: f64,
+    ) -> Result<Self> {
+        if spacing <= 0.0f64 {
+            color_eyre::eyre::bail!("Sample spacing must be positive, got {spacing}");
+        }
+
+        let distance = earth.distance(from, to);
+        let bearing = earth.bearing(from, to);
+        let count = ((distance / spacing).ceil() as usize).clamp(1, MAX_SAMPLES);
+        let step = distance / count as f64;
+        tracing::debug!(
+            "Sampling {count} points over {distance}m from {from:?} to {to:?} in {dem:?}"
+        );
+
+        let elevations = Elevations::open(dem)?;
+        let origin = geo::Point::new(from.0.x, from.0.y);
+        let mut samples = Vec::with_capacity(count + 1);
+        for index in 0..=count {
+            let sample_distance = step * index as f64;
+            let point = earth.destination(origin, bearing, sample_distance);
+            let coordinate = LonLatCoord(point.0);
+            samples.push(Sample {
+                distance: sample_distance,
+                coordinate,
+                elevation: elevations.at(coordinate)?,
+                curvature_drop: crate::horizon::curvature_drop(
+                    sample_distance,
+                    line_of_sight.refraction,
+                ),
+                ray: 0.0,
+            });
+        }
+
+        let first = samples.first().context("No samples in profile")?;
+        let last = samples.last().context("No samples in profile")?;
+        let observer_elevation = f64::from(
+            first
+                .elevation
+                .context(format!("No elevation data at observer: {from:?}"))?,
+        );
+        let target_elevation = f64::from(
+            last.elevation
+                .context(format!("No elevation data at target: {to:?}"))?,
+        );
+        let observer = observer_elevation + f64::from(line_of_sight.observer_height);
+        let target =
+            target_elevation + f64::from(line_of_sight.target_height) - last.curvature_drop;
+        let is_plausible = crate::horizon::is_plausible(
+            observer_elevation,
+            target_elevation,
+            distance,
+            &line_of_sight,
+        );
+
+        let mut is_visible = true;
+        for sample in &mut samples {
+            sample.ray = if distance > 0.0f64 {
+                (target - observer).mul_add(sample.distance / distance, observer)
+            } else {
+                observer
+            };
+
+            let is_endpoint = sample.distance <= 0.0f64 || sample.distance >= distance;
+            if let Some(elevation) = sample.elevation
+                && !is_endpoint
+                && f64::from(elevation) - sample.curvature_drop > sample.ray
+            {
+                is_visible = false;
+            }
+        }
+
+        Ok(Self {
+            from,
+            to,
+            distance,
+            bearing,
+            line_of_sight,
+            is_visible,
+            is_plausible,
+            samples,
+        })
+    }
+}
+
+/// Look up elevations in a DEM.
+struct Elevations {
+    /// The DEM dataset.
+    dataset: gdal::Dataset,
+    /// The DEM's affine transform from pixels to lon/lat.
+    transform: gdal::GeoTransform,
+    /// The value that marks missing data.
+    nodata: Option<f64>,
+}
+
+impl Elevations {
+    /// Open a DEM.
+    fn open(path: &std::path::Path) -> Result<Self> {
+        let dataset = gdal::Dataset::open(path)?;
+        let transform = dataset.geo_transform()?;
+        let nodata = dataset.rasterband(1)?.no_data_value();
+
+        Ok(Self {
+            dataset,
+            transform,
+            nodata,
+        })
+    }
+
+    /// The elevation at a single point, from the pixel that contains it.
+    #[expect(
+        clippy::as_conversions,
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        reason = "Raster coordinates are always small, positive and checked against the raster size"
+    )]
+    fn at(&self, coordinate: LonLatCoord) -> Result<Option<f32>> {
+        let band = self.dataset.rasterband(1)?;
+        let (width, height) = band.size();
+        let column = ((coordinate.0.x - self.transform[0]) / self.transform[1]).floor();
+        let row = ((coordinate.0.y - self.transform[3]) / self.transform[5]).floor();
+        if column < 0.0f64 || row < 0.0f64 || column >= width as f64 || row >= height as f64 {
+            return Ok(None);
+        }
+
+        let buffer: gdal::raster::Buffer<f32> =
+            band.read_as((column as isize, row as isize), (1, 1), (1, 1), None)?;
+        let Some(elevation) = buffer.data().first().copied() else {
+            return Ok(None);
+        };
+
+        if self
+            .nodata
+            .is_some_and(|nodata| (f64::from(elevation) - nodata).abs() < f64::EPSILON)
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(elevation))
+    }
+}
+
+/// Print the elevation profile between two points as JSON.
+#[expect(clippy::print_stdout, reason = "Gotta output the JSON")]
+pub async fn run(config: &crate::config::Profile) -> Result<()> {
+    let machine = std::sync::Arc::new(crate::atlas::machines::local::Machine::connection());
+    crate::stitch::build_virtual_dem(&machine, &config.dems).await?;
+
+    let profile = Profile::sample(
+        &crate::stitch::virtual_dem_path(&config.dems),
+        LonLatCoord(geo::coord! { x: config.from.0, y: config.from.1 }),
+        LonLatCoord(geo::coord! { x: config.to.0, y: config.to.1 }),
+        config.line_of_sight,
+        config.earth,
+        config.spacing,
+    )?;
+
+    let json = serde_json::to_string_pretty(&profile)?;
+    println!("{json}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn great_circle_round_trip() {
+        let from = LonLatCoord(geo::coord! { x: -3.0f64, y: 53.0f64 });
+        let to = LonLatCoord(geo::coord! { x: -4.1f64, y: 54.2f64 });
+
+        for earth in [
+            crate::projector::Earth::Wgs84,
+            crate::projector::Earth::Sphere,
+        ] {
+            let distance = earth.distance(from, to);
+            let bearing = earth.bearing(from, to);
+            let end = earth.destination(geo::Point::new(-3.0f64, 53.0f64), bearing, distance);
+
+            assert!((end.x() - to.0.x).abs() < 0.000_001f64);
+            assert!((end.y() - to.0.y).abs() < 0.000_001f64);
+        }
+    }
+}
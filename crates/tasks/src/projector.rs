@@ -2,9 +2,41 @@
 
 use color_eyre::Result;
 
-/// The radius of the planet in kilometers.
+/// The radius of the planet in kilometers, when it's modelled as a sphere.
 pub const EARTH_RADIUS: f32 = 6371.0;
 
+/// The WGS84 equatorial radius in meters.
+const WGS84_EQUATORIAL_RADIUS: f64 = 6_378_137.0;
+
+/// The WGS84 flattening.
+const WGS84_FLATTENING: f64 = 1.0 / 298.257_223_563;
+
+/// The shape of the earth used for distances, bearings and destinations.
+///
+/// The stitcher reprojects the DEMs with `gdalwarp` to a WGS84 AEQD, so the geodesics on the WGS84
+/// ellipsoid are what make tile polygons agree with the rasters. The sphere is simpler and a bit
+/// faster, and it's what all the tiles were originally packed with. Tiles files record the earth
+/// that they were packed on, so their tiles' geometry is always remade on the same earth.
+#[derive(
+    clap::ValueEnum,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Earth {
+    /// Karney's geodesics on the WGS84 ellipsoid.
+    #[default]
+    Wgs84,
+    /// Great circles on a sphere with a radius of `EARTH_RADIUS`.
+    Sphere,
+}
+
 /// A latitude/longtitude coordinate.
 #[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, PartialEq, Default)]
 pub struct LonLatCoord(pub geo::Coord);
@@ -38,6 +70,111 @@ impl rstar::Point for LonLatCoord {
     }
 }
 
+impl Earth {
+    /// The radius of the sphere in meters.
+    fn sphere_radius() -> f64 {
+        f64::from(EARTH_RADIUS) * 1000.0
+    }
+
+    /// Find the lon/lat coordinate of a point based on its distance and bearing from another
+    /// point. The longitude isn't wrapped, so it's always within 180° of the origin's longitude,
+    /// even across the antimeridian.
+    #[expect(
+        clippy::suboptimal_flops,
+        reason = "I copied it from the `geo` crate and daren't mess with it."
+    )]
+    pub fn destination(self, origin: geo::Point, bearing: f64, meters: f64) -> geo::Point {
+        match self {
+            Self::Wgs84 => {
+                let destination =
+                    geo::Destination::destination(&geo::Geodesic, origin, bearing, meters);
+                let unwrapped = (destination.x() - origin.x() + 180.0f64).rem_euclid(360.0f64)
+                    + origin.x()
+                    - 180.0f64;
+                geo::Point::new(unwrapped, destination.y())
+            }
+            Self::Sphere => {
+                let center_lng = origin.x().to_radians();
+                let center_lat = origin.y().to_radians();
+                let bearing_rad = bearing.to_radians();
+
+                let rad = meters / Self::sphere_radius();
+
+                let lat = {
+                    center_lat.sin() * rad.cos() + center_lat.cos() * rad.sin() * bearing_rad.cos()
+                }
+                .asin();
+                let lng = { bearing_rad.sin() * rad.sin() * center_lat.cos() }
+                    .atan2(rad.cos() - center_lat.sin() * lat.sin())
+                    + center_lng;
+
+                geo::Point::new(lng.to_degrees(), lat.to_degrees())
+            }
+        }
+    }
+
+    /// The shortest distance in meters between two points.
+    pub fn distance(self, from: LonLatCoord, to: LonLatCoord) -> f64 {
+        match self {
+            Self::Wgs84 => geo::Distance::distance(&geo::Geodesic, from.0.into(), to.0.into()),
+            Self::Sphere => {
+                let from_lat = from.0.y.to_radians();
+                let to_lat = to.0.y.to_radians();
+                let delta_lat = to_lat - from_lat;
+                let delta_lon = (to.0.x - from.0.x).to_radians();
+
+                let haversine = (from_lat.cos() * to_lat.cos()).mul_add(
+                    (delta_lon / 2.0).sin().powi(2),
+                    (delta_lat / 2.0).sin().powi(2),
+                );
+
+                2.0 * Self::sphere_radius() * haversine.sqrt().asin()
+            }
+        }
+    }
+
+    /// The initial bearing in degrees clockwise from North of the shortest path from one point to
+    /// another.
+    pub fn bearing(self, from: LonLatCoord, to: LonLatCoord) -> f64 {
+        match self {
+            Self::Wgs84 => {
+                geo::Bearing::bearing(&geo::Geodesic, from.0.into(), to.0.into()).rem_euclid(360.0)
+            }
+            Self::Sphere => {
+                let from_lat = from.0.y.to_radians();
+                let to_lat = to.0.y.to_radians();
+                let delta_lon = (to.0.x - from.0.x).to_radians();
+
+                let y = delta_lon.sin() * to_lat.cos();
+                let x = from_lat.cos().mul_add(
+                    to_lat.sin(),
+                    -(from_lat.sin() * to_lat.cos() * delta_lon.cos()),
+                );
+
+                y.atan2(x).to_degrees().rem_euclid(360.0)
+            }
+        }
+    }
+
+    /// The width in meters of a degree of longitude at the given latitude.
+    pub fn meters_per_degree(self, latitude: f64) -> f64 {
+        let latitude_radians = latitude.to_radians();
+        let radius = match self {
+            Self::Wgs84 => {
+                // The radius of the circle of latitude on the ellipsoid.
+                let eccentricity_squared = WGS84_FLATTENING * (2.0f64 - WGS84_FLATTENING);
+                WGS84_EQUATORIAL_RADIUS
+                    / (eccentricity_squared * latitude_radians.sin().powi(2))
+                        .mul_add(-1.0, 1.0)
+                        .sqrt()
+            }
+            Self::Sphere => Self::sphere_radius(),
+        };
+
+        (radius * latitude_radians.cos()).to_radians().abs()
+    }
+}
+
 /// Convert between different coordinate system.
 pub struct Convert {
     /// The lat/lon base coordinates for the AEQD mercator projected coordinates.
@@ -87,17 +224,15 @@ impl Convert {
     }
 
     /// Calculate the width of a degree in meters at the given latitude.
-    pub fn meters_per_degree(latitude: f64) -> f32 {
+    pub fn meters_per_degree(earth: Earth, latitude: f64) -> f32 {
         #[expect(
             clippy::cast_possible_truncation,
             clippy::as_conversions,
             reason = "It's the only way"
         )]
-        (EARTH_RADIUS
-            * 1000.0
-            * latitude.to_radians().cos() as f32
-            * (std::f32::consts::PI / 180.0))
-            .abs()
+        let meters = earth.meters_per_degree(latitude) as f32;
+
+        meters
     }
 }
 
@@ -160,6 +295,40 @@ mod test {
         );
     }
 
+    #[test]
+    fn geodesics_on_wgs84_and_a_sphere() {
+        // Sofia to Plovdiv, from the `geo` crate's docs.
+        let sofia = LonLatCoord(geo::Coord {
+            x: 23.319941,
+            y: 42.698334,
+        });
+        let plovdiv = LonLatCoord(geo::Coord {
+            x: 24.742168,
+            y: 42.136097,
+        });
+        assert!((Earth::Wgs84.distance(sofia, plovdiv) - 132675.5018588206).abs() < 0.001);
+        assert!((Earth::Sphere.distance(sofia, plovdiv) - 132_675.5).abs() > 100.0);
+
+        for earth in [Earth::Wgs84, Earth::Sphere] {
+            let distance = earth.distance(sofia, plovdiv);
+            let bearing = earth.bearing(sofia, plovdiv);
+            let end = earth.destination(sofia.0.into(), bearing, distance);
+            assert!((end.x() - plovdiv.0.x).abs() < 0.000001);
+            assert!((end.y() - plovdiv.0.y).abs() < 0.000001);
+        }
+
+        assert!((Earth::Wgs84.meters_per_degree(0.0) - 111319.49).abs() < 0.01);
+        assert!((Earth::Sphere.meters_per_degree(0.0) - 111194.93).abs() < 0.01);
+    }
+
+    #[test]
+    fn destinations_across_the_antimeridian_arent_wrapped() {
+        let fiji = geo::Point::new(179.5, -17.0);
+        for earth in [Earth::Wgs84, Earth::Sphere] {
+            assert!(earth.destination(fiji, 90.0, 200_000.0).x() > 180.0);
+        }
+    }
+
     #[test]
     fn bristolish_to_degrees() {
         let base = LonLatCoord(geo::Coord {
@@ -17,7 +17,7 @@ pub async fn make_tile(
     machine: &Arc<crate::atlas::machines::connection::Connection>,
     config: &crate::config::Stitch,
 ) -> Result<String> {
-    build_virtual_dem(machine, config).await?;
+    build_virtual_dem(machine, &config.dems).await?;
     let filename = stitch(machine, config).await?;
     set_centre_as_extent(machine, config, &filename).await?;
 
@@ -26,22 +26,19 @@ pub async fn make_tile(
 
 /// Build the virtual "DEM" file that represents all the DEM data for the planet. Saves having to
 /// scan and parse the header for every single `.hgt` file every time we make a tile.
-async fn build_virtual_dem(
+pub async fn build_virtual_dem(
     machine: &Arc<crate::atlas::machines::connection::Connection>,
-    config: &crate::config::Stitch,
+    dems: &std::path::Path,
 ) -> Result<()> {
-    let vrt_path = std::path::Path::new(&config.dems).join(VIRTUAL_DEM_FILE);
+    let vrt_path = virtual_dem_path(dems);
     if vrt_path.exists() {
         tracing::info!("Not recreating already existing VRT index: {vrt_path:?}");
         return Ok(());
     }
 
-    tracing::warn!(
-        "Creating VRT index for {:?}. Don't do this on a S3 mount.",
-        config.dems
-    );
+    tracing::warn!("Creating VRT index for {dems:?}. Don't do this on a S3 mount.");
 
-    let hgts = find_all_hgts(config)?;
+    let hgts = find_all_hgts(dems)?;
 
     let vrt_path_string = vrt_path.display().to_string();
     let mut arguments = vec![vrt_path_string.as_str()];
@@ -54,17 +51,22 @@ async fn build_virtual_dem(
             executable: "gdalbuildvrt".into(),
             args: arguments,
             env: vec![],
-            current_dir: Some(config.dems.clone()),
+            current_dir: Some(dems.to_path_buf()),
         })
         .await?;
 
     Ok(())
 }
 
+/// The path to the virtual DEM for a folder of DEM files.
+pub fn virtual_dem_path(dems: &std::path::Path) -> std::path::PathBuf {
+    dems.join(VIRTUAL_DEM_FILE)
+}
+
 /// Find all the `*.hgt` files in the SRTM data folder.
-fn find_all_hgts(config: &crate::config::Stitch) -> Result<Vec<String>> {
+fn find_all_hgts(dems: &std::path::Path) -> Result<Vec<String>> {
     let mut hgts = Vec::new();
-    for result in std::fs::read_dir(config.dems.clone())? {
+    for result in std::fs::read_dir(dems)? {
         let file = result?.path().clone();
         if !file.is_file() {
             continue;
@@ -106,7 +108,7 @@ async fn stitch(
         "./output/{}",
         canonical_filename(config.centre.0, config.centre.1)
     );
-    let hgt_index = config.dems.join(VIRTUAL_DEM_FILE).display().to_string();
+    let hgt_index = virtual_dem_path(&config.dems).display().to_string();
 
     // We align to 48 for the vectorising CPU kernel.
     let align = 48.0;
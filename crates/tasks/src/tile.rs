@@ -1,8 +1,10 @@
 //! A single computable tile for the Total Viewshed algorithm.
 
 use color_eyre::{Result, eyre::ContextCompat as _};
-use geo::{Area as _, BoundingRect as _, Buffer as _};
-use rstar::PointDistance as _;
+use geo::{
+    Area as _, BooleanOps as _, BoundingRect as _, Buffer as _, Intersects as _, MapCoords as _,
+    Translate as _,
+};
 
 use crate::projector::LonLatCoord;
 
@@ -29,18 +31,83 @@ impl Tile {
         projecter.to_meters(self.centre)
     }
 
-    /// The Axis-Aligned Bounding Box for the tile. Note that this has an unintuitive shape as
+    /// The Axis-Aligned Bounding Boxes for the tile. Note that these have an unintuitive shape as
     /// "axis-alighed" in lon/lat coordinates are very much not circles nor squares near the poles.
-    /// Nevertheless the AABB is still useful for quicker first pass lookups of containing points.
-    /// A follow up `is_within()` for each found point can then be used to get the exact contents.
-    pub fn to_aabb_lonlat(self) -> Result<rstar::AABB<LonLatCoord>> {
-        let bbox = self
-            .to_polygon_lonlat()
-            .bounding_rect()
-            .context(format!("Couldn't find bbox for tile: {self:?}"))?;
-        let aabb = rstar::AABB::from_corners(LonLatCoord(bbox.min()), LonLatCoord(bbox.max()));
+    /// Nevertheless the AABBs are still useful for quicker first pass lookups of containing points.
+    /// A follow up `intersects()` for each found point can then be used to get the exact contents.
+    ///
+    /// There's one AABB for each side of the antimeridian that the tile covers. A single AABB for
+    /// a tile that crosses the antimeridian would otherwise span the whole globe.
+    pub fn to_aabbs_lonlat(
+        self,
+        earth: crate::projector::Earth,
+    ) -> Result<Vec<rstar::AABB<LonLatCoord>>> {
+        Self::polygon_aabbs_lonlat(&self.to_polygon_lonlat(earth))
+    }
+
+    /// The same as `to_aabbs_lonlat()`, for when the tile's lon/lat polygon has already been made.
+    pub fn polygon_aabbs_lonlat(
+        polygon: &geo::MultiPolygon<f64>,
+    ) -> Result<Vec<rstar::AABB<LonLatCoord>>> {
+        let mut aabbs = Vec::new();
+        for part in polygon {
+            let bbox = part
+                .bounding_rect()
+                .context(format!("Couldn't find bbox for tile polygon: {part:?}"))?;
+            aabbs.push(rstar::AABB::from_corners(
+                LonLatCoord(bbox.min()),
+                LonLatCoord(bbox.max()),
+            ));
+        }
 
-        Ok(aabb)
+        Ok(aabbs)
+    }
+
+    /// A quick lon/lat bounding box of the tile, without making its polygon. It's a little bigger
+    /// than the tile, to allow for the earth not being a perfect sphere. Longitudes aren't
+    /// wrapped, so they go past ±180° when the tile crosses the antimeridian, and tiles that
+    /// cover a pole span every longitude.
+    pub fn rough_bounds_lonlat(self, earth: crate::projector::Earth) -> geo::Rect<f64> {
+        let margin = 1.01f64;
+        let angular_radius =
+            self.radius() * margin / f64::from(crate::projector::EARTH_RADIUS * 1000.0);
+        let latitude_radius = angular_radius.to_degrees();
+        let south = (self.centre.0.y - latitude_radius).max(-90.0f64);
+        let north = (self.centre.0.y + latitude_radius).min(90.0f64);
+
+        // On a sphere, the furthest East and West that a circle reaches.
+        let ratio = angular_radius.sin() / self.centre.0.y.to_radians().cos();
+        let (west, east) = if self.enclosed_pole(earth).is_some() || ratio >= 1.0f64 {
+            (-180.0f64, 180.0f64)
+        } else {
+            let longitude_radius = ratio.asin().to_degrees();
+            (
+                self.centre.0.x - longitude_radius,
+                self.centre.0.x + longitude_radius,
+            )
+        };
+
+        geo::Rect::new(
+            geo::coord! { x: west, y: south },
+            geo::coord! { x: east, y: north },
+        )
+    }
+
+    /// Whether the tile might overlap another. It's quick because it only compares their
+    /// `rough_bounds_lonlat()`, so it's never wrong about tiles that don't overlap, but tiles that
+    /// are close to each other need `to_polygon_lonlat()` to be sure.
+    pub fn might_overlap(self, other: Self, earth: crate::projector::Earth) -> bool {
+        let bounds = self.rough_bounds_lonlat(earth);
+        let other_bounds = other.rough_bounds_lonlat(earth);
+        [-360.0f64, 0.0f64, 360.0f64]
+            .into_iter()
+            .any(|offset| bounds.intersects(&other_bounds.translate(offset, 0.0f64)))
+    }
+
+    /// Whether the tile is on both sides of the antimeridian. Tiles that cover a pole are on all
+    /// longitudes, so they don't count.
+    pub fn crosses_antimeridian(self, earth: crate::projector::Earth) -> bool {
+        self.enclosed_pole(earth).is_none() && self.to_polygon_lonlat(earth).0.len() > 1
     }
 
     /// Make a polygon representing the tile in metric coordinates.
@@ -53,20 +120,148 @@ impl Tile {
         Ok(circle)
     }
 
-    /// Make a polygon representing the tile in lon/lat coordinates.
-    pub fn to_polygon_lonlat(self) -> geo::Polygon<f64> {
+    /// Make a polygon representing the tile in lon/lat coordinates. Tiles that cross the
+    /// antimeridian are split into a polygon on each side of it.
+    pub fn to_polygon_lonlat(self, earth: crate::projector::Earth) -> geo::MultiPolygon<f64> {
+        if let Some(pole) = self.enclosed_pole(earth) {
+            return self.to_polar_cap_lonlat(pole, earth).into();
+        }
+
         let resolution: u16 = 360;
         let mut coordinates = Vec::with_capacity((resolution + 1).into());
 
         for i in 0..=resolution {
             let angle = f64::from(i) * 360.0f64 / f64::from(resolution);
             let centre = geo::Point::new(self.centre.0.x, self.centre.0.y);
-            let destination = Self::destination(centre, angle, self.radius());
+            let destination = earth.destination(centre, angle, self.radius());
             coordinates.push((destination.x(), destination.y()));
         }
 
         let circle = geo::LineString::from(coordinates);
-        geo::Polygon::new(circle, vec![])
+        Self::split_at_antimeridian(&geo::Polygon::new(circle, vec![]))
+    }
+
+    /// `Earth::destination()` doesn't wrap longitudes, so the edge of a tile near the antimeridian
+    /// can have longitudes beyond ±180°. Cut out the parts of the polygon that are beyond the
+    /// antimeridian and wrap them back around to the other side of the globe.
+    ///
+    /// The clipping isn't exact, so the cut edges are snapped back onto the antimeridian. Points
+    /// exactly on the antimeridian then lie on the edges of the parts, so they're only in the tile
+    /// according to `intersects()`, not `is_within()`.
+    fn split_at_antimeridian(polygon: &geo::Polygon<f64>) -> geo::MultiPolygon<f64> {
+        let is_wrapped = polygon
+            .bounding_rect()
+            .is_some_and(|bbox| bbox.min().x >= -180.0f64 && bbox.max().x <= 180.0f64);
+        if is_wrapped {
+            return polygon.clone().into();
+        }
+
+        let mut parts = Vec::new();
+        for offset in [-360.0f64, 0.0f64, 360.0f64] {
+            let globe = geo::Rect::new(
+                geo::coord! { x: -180.0f64 + offset, y: -90.0f64 },
+                geo::coord! { x: 180.0f64 + offset, y: 90.0f64 },
+            )
+            .to_polygon();
+            for part in polygon.intersection(&globe) {
+                parts.push(part.translate(-offset, 0.0f64).map_coords(|coord| {
+                    let is_on_antimeridian = (coord.x.abs() - 180.0f64).abs() < 0.000_001f64;
+                    geo::coord! {
+                        x: if is_on_antimeridian { 180.0f64.copysign(coord.x) } else { coord.x },
+                        y: coord.y
+                    }
+                }));
+            }
+        }
+
+        geo::MultiPolygon::new(parts)
+    }
+
+    /// The latitude of the pole that the tile covers, if it covers one.
+    pub fn enclosed_pole(self, earth: crate::projector::Earth) -> Option<f64> {
+        let pole = 90.0f64.copysign(self.centre.0.y);
+        let distance = earth.distance(
+            self.centre,
+            LonLatCoord(geo::coord! { x: self.centre.0.x, y: pole }),
+        );
+
+        (distance < self.radius()).then_some(pole)
+    }
+
+    /// Make a polygon for a tile that covers a pole.
+    ///
+    /// Every meridian passes through the pole, so in lon/lat coordinates the tile isn't a ring
+    /// around its centre anymore. Instead it's the band between its edge and the pole that spans
+    /// every longitude. Each meridian crosses the tile's edge exactly once, so we can solve for the
+    /// latitude of that crossing directly rather than walking the edge by bearing. On a sphere
+    /// there's an exact solution, on the ellipsoid we refine the sphere's solution.
+    fn to_polar_cap_lonlat(self, pole: f64, earth: crate::projector::Earth) -> geo::Polygon<f64> {
+        let resolution: u16 = 360;
+        let centre_lat = self.centre.0.y.to_radians();
+        let angular_radius = self.radius() / f64::from(crate::projector::EARTH_RADIUS * 1000.0);
+        let mut coordinates = Vec::with_capacity(usize::from(resolution) + 3);
+
+        for i in 0..=resolution {
+            let longitude = f64::from(i).mul_add(360.0f64 / f64::from(resolution), -180.0f64);
+            let delta_lon = (longitude - self.centre.0.x).to_radians();
+
+            // Points on the meridian at a given angular distance from the centre satisfy:
+            //   cos(distance) = sin(lat0)sin(lat) + cos(lat0)cos(lat)cos(Δlon)
+            let sin_part = centre_lat.sin();
+            let cos_part = centre_lat.cos() * delta_lon.cos();
+            let phase = sin_part.atan2(cos_part);
+            let amplitude = sin_part.hypot(cos_part);
+            let offset = (angular_radius.cos() / amplitude).clamp(-1.0, 1.0).acos();
+            let spherical_latitude = (-pole.signum()).mul_add(offset, phase).to_degrees();
+            let latitude = match earth {
+                crate::projector::Earth::Wgs84 => {
+                    self.refine_edge_latitude(longitude, spherical_latitude, earth)
+                }
+                crate::projector::Earth::Sphere => spherical_latitude,
+            };
+
+            coordinates.push((longitude, latitude));
+        }
+        coordinates.push((180.0f64, pole));
+        coordinates.push((-180.0f64, pole));
+
+        geo::Polygon::new(geo::LineString::from(coordinates), vec![])
+    }
+
+    /// Refine the latitude at which a meridian crosses the tile's edge, using the secant method
+    /// from a first guess. The distance from the centre changes smoothly along the meridian near
+    /// the edge, so it only takes a few iterations.
+    fn refine_edge_latitude(
+        self,
+        longitude: f64,
+        guess: f64,
+        earth: crate::projector::Earth,
+    ) -> f64 {
+        let error = |latitude: f64| {
+            earth.distance(
+                self.centre,
+                LonLatCoord(geo::coord! { x: longitude, y: latitude.clamp(-90.0f64, 90.0f64) }),
+            ) - self.radius()
+        };
+
+        let mut previous = guess;
+        let mut current = guess + 0.01f64;
+        let mut previous_error = error(previous);
+        for _ in 0u8..10 {
+            let current_error = error(current);
+            if current_error.abs() < 0.01f64
+                || (current_error - previous_error).abs() < f64::EPSILON
+            {
+                break;
+            }
+            let next =
+                current - current_error * (current - previous) / (current_error - previous_error);
+            previous = current;
+            previous_error = current_error;
+            current = next;
+        }
+
+        current.clamp(-90.0f64, 90.0f64)
     }
 
     /// The surface area covered by the tile.
@@ -81,38 +276,221 @@ impl Tile {
     }
 
     /// Calculate the distance in meters of the tile from the given point.
-    pub fn distance_from(&self, point_lonlat: LonLatCoord) -> Result<f64> {
-        let projector = crate::projector::Convert { base: point_lonlat };
-        let point = projector.to_meters(self.centre)?;
+    pub fn distance_from(
+        &self,
+        point_lonlat: LonLatCoord,
+        earth: crate::projector::Earth,
+    ) -> Result<f64> {
+        Ok(earth.distance(point_lonlat, self.centre))
+    }
 
-        Ok(self.centre_metric(point_lonlat)?.distance_2(&point).sqrt())
+    /// Canonical filename for the tile.
+    pub fn cog_filename(&self) -> String {
+        format!("{}_{}.tiff", self.centre.0.x, self.centre.0.y)
     }
 
+    /// Get the lon/lat coordinates of the centre of a tile from its canonical COG filename.
+    pub fn centre_from_cog_filename(path: &std::path::Path) -> Result<LonLatCoord> {
+        let filestem = path
+            .file_stem()
+            .context("Couldn't get file stem of COG tile")?
+            .display()
+            .to_string();
+        let parts: Vec<&str> = filestem.split('_').collect();
+
+        #[expect(
+            clippy::get_first,
+            reason = "Better to have the consistent access method."
+        )]
+        let lon: f64 = parts
+            .get(0)
+            .context("Longitude not present in path parts")?
+            .parse()?;
+        let lat: f64 = parts
+            .get(1)
+            .context("Latitude not present in path parts")?
+            .parse()?;
+
+        Ok(LonLatCoord(geo::coord! {x: lon, y: lat}))
+    }
+}
+
+/// Print the lon/lat extents of a processed tile as `west south east north`.
+///
+/// There's one line for each side of the antimeridian that the tile covers. Used to warp tiles
+/// that cross the antimeridian in separate halves, otherwise they'd be warped to rasters that span
+/// the whole globe.
+#[expect(clippy::print_stdout, reason = "Gotta output the extents")]
+pub fn print_extents(config: &crate::config::TileExtents) -> Result<()> {
+    let centre = Tile::centre_from_cog_filename(&config.tiff)?;
+    let dataset = gdal::Dataset::open(&config.tiff)?;
+    let transform = dataset.geo_transform()?;
+    let (pixels, _) = dataset.raster_size();
+
+    // The raster is square, so the tile that covers all of it is the circle around its corners.
     #[expect(
-        clippy::suboptimal_flops,
-        reason = "I copied it from the `geo` crate and daren't mess with it."
+        clippy::as_conversions,
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        reason = "Tiles are never big enough for this to matter"
     )]
-    /// Find the lon/lat coordinate of a point based in its distance and bearing from another
-    /// point.
-    fn destination(origin: geo::Point, bearing: f64, meters: f64) -> geo::Point {
-        let center_lng = origin.x().to_radians();
-        let center_lat = origin.y().to_radians();
-        let bearing_rad = bearing.to_radians();
+    let width = (pixels as f64 * transform[1].abs() * std::f64::consts::SQRT_2) as f32;
+    let tile = Tile { centre, width };
+
+    // The tile was warped to a WGS84 AEQD, so its edge is where the ellipsoid's geodesics reach.
+    for aabb in tile.to_aabbs_lonlat(crate::projector::Earth::Wgs84)? {
+        println!(
+            "{} {} {} {}",
+            aabb.lower().0.x,
+            aabb.lower().0.y,
+            aabb.upper().0.x,
+            aabb.upper().0.y
+        );
+    }
+
+    Ok(())
+}
 
-        let rad = meters / f64::from(crate::projector::EARTH_RADIUS * 1000.0);
+#[expect(
+    clippy::float_cmp,
+    reason = "The poles and antimeridian are exact values, not the result of calculations"
+)]
+#[cfg(test)]
+mod test {
+    use geo::Within as _;
 
-        let lat =
-            { center_lat.sin() * rad.cos() + center_lat.cos() * rad.sin() * bearing_rad.cos() }
-                .asin();
-        let lng = { bearing_rad.sin() * rad.sin() * center_lat.cos() }
-            .atan2(rad.cos() - center_lat.sin() * lat.sin())
-            + center_lng;
+    use super::*;
 
-        geo::Point::new(lng.to_degrees(), lat.to_degrees())
+    #[test]
+    fn tile_covering_the_north_pole() {
+        let tile = Tile {
+            centre: LonLatCoord(geo::coord! { x: 10.0f64, y: 88.0f64 }),
+            width: 800_000.0,
+        };
+        assert_eq!(
+            tile.enclosed_pole(crate::projector::Earth::Wgs84),
+            Some(90.0f64)
+        );
+
+        let polygon = tile.to_polygon_lonlat(crate::projector::Earth::Wgs84);
+        let across_the_pole = geo::coord! { x: -170.0f64, y: 89.0f64 };
+        let outside = geo::coord! { x: -170.0f64, y: 85.0f64 };
+        assert!(across_the_pole.is_within(&polygon));
+        assert!(!outside.is_within(&polygon));
+
+        let aabbs = tile
+            .to_aabbs_lonlat(crate::projector::Earth::Wgs84)
+            .unwrap();
+        let aabb = aabbs.first().unwrap();
+        assert_eq!(aabbs.len(), 1);
+        assert_eq!(aabb.lower().0.x, -180.0f64);
+        assert_eq!(aabb.upper().0, geo::coord! { x: 180.0f64, y: 90.0f64 });
     }
 
-    /// Canonical filename for the tile.
-    pub fn cog_filename(&self) -> String {
-        format!("{}_{}.tiff", self.centre.0.x, self.centre.0.y)
+    #[test]
+    fn tile_covering_the_south_pole() {
+        let tile = Tile {
+            centre: LonLatCoord(geo::coord! { x: 0.0f64, y: -90.0f64 }),
+            width: 1_000_000.0,
+        };
+        assert_eq!(
+            tile.enclosed_pole(crate::projector::Earth::Wgs84),
+            Some(-90.0f64)
+        );
+
+        let aabbs = tile
+            .to_aabbs_lonlat(crate::projector::Earth::Wgs84)
+            .unwrap();
+        let aabb = aabbs.first().unwrap();
+        assert_eq!(aabbs.len(), 1);
+        // The earth is flatter near the poles, so a degree of latitude there is longer than on a
+        // sphere, which would put the edge at -85.5°.
+        assert!((aabb.upper().0.y - -85.52f64).abs() < 0.01f64);
+        assert_eq!(aabb.lower().0.y, -90.0f64);
+
+        let spherical = tile
+            .to_aabbs_lonlat(crate::projector::Earth::Sphere)
+            .unwrap();
+        assert!((spherical.first().unwrap().upper().0.y - -85.5f64).abs() < 0.01f64);
+    }
+
+    #[test]
+    fn tile_crossing_the_antimeridian() {
+        let fiji = Tile {
+            centre: LonLatCoord(geo::coord! { x: 179.5f64, y: -17.0f64 }),
+            width: 400_000.0,
+        };
+        assert!(fiji.crosses_antimeridian(crate::projector::Earth::Wgs84));
+
+        let polygon = fiji.to_polygon_lonlat(crate::projector::Earth::Wgs84);
+        assert_eq!(polygon.0.len(), 2);
+        assert!(geo::coord! { x: -179.5f64, y: -17.0f64 }.is_within(&polygon));
+        assert!(geo::coord! { x: 179.5f64, y: -17.0f64 }.is_within(&polygon));
+        assert!(!geo::coord! { x: 0.0f64, y: -17.0f64 }.is_within(&polygon));
+        assert!(geo::Intersects::intersects(
+            &polygon,
+            &geo::coord! { x: -180.0f64, y: -17.0f64 }
+        ));
+
+        for aabb in fiji
+            .to_aabbs_lonlat(crate::projector::Earth::Wgs84)
+            .unwrap()
+        {
+            assert!(aabb.upper().0.x - aabb.lower().0.x < 5.0f64);
+        }
+    }
+
+    #[test]
+    fn tile_not_covering_a_pole() {
+        let tile = Tile {
+            centre: LonLatCoord(geo::coord! { x: 10.0f64, y: 80.0f64 }),
+            width: 800_000.0,
+        };
+        assert_eq!(tile.enclosed_pole(crate::projector::Earth::Wgs84), None);
+        assert!(!tile.crosses_antimeridian(crate::projector::Earth::Wgs84));
+    }
+
+    #[test]
+    fn rough_bounds_contain_the_tile() {
+        for (lon, lat, width) in [
+            (10.0f64, 45.0f64, 800_000.0f32),
+            (179.5f64, -17.0f64, 400_000.0f32),
+            (-120.0f64, 80.0f64, 800_000.0f32),
+            (0.0f64, 0.0f64, 36_000.0f32),
+        ] {
+            let tile = Tile {
+                centre: LonLatCoord(geo::coord! { x: lon, y: lat }),
+                width,
+            };
+            let bounds = tile.rough_bounds_lonlat(crate::projector::Earth::Wgs84);
+            for coord in tile
+                .to_polygon_lonlat(crate::projector::Earth::Wgs84)
+                .iter()
+                .flat_map(geo::Polygon::exterior)
+            {
+                assert!(
+                    [-360.0f64, 0.0f64, 360.0f64]
+                        .into_iter()
+                        .any(|offset| geo::coord! { x: coord.x + offset, y: coord.y }
+                            .is_within(&bounds)),
+                    "{coord:?} isn't in the rough bounds of {tile:?}"
+                );
+            }
+        }
+
+        let fiji = Tile {
+            centre: LonLatCoord(geo::coord! { x: 179.5f64, y: -17.0f64 }),
+            width: 400_000.0,
+        };
+        let tonga = Tile {
+            centre: LonLatCoord(geo::coord! { x: -179.5f64, y: -17.0f64 }),
+            width: 400_000.0,
+        };
+        let bristol = Tile {
+            centre: LonLatCoord(geo::coord! { x: -2.6f64, y: 51.5f64 }),
+            width: 400_000.0,
+        };
+        assert!(fiji.might_overlap(tonga, crate::projector::Earth::Wgs84));
+        assert!(!fiji.might_overlap(bristol, crate::projector::Earth::Wgs84));
     }
 }
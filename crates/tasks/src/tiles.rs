@@ -0,0 +1,373 @@
+//! The tiles files made by the packer, and tools for them.
+//!
+//! A tiles file is a CSV file with a header. Before the header there's a single comment line of
+//! JSON metadata about the whole file:
+//!
+//! ```text
+//! # {"version":1,"packer":{...},"input_hash":"crc32:1a2b3c4d"}
+//! lon,lat,width,highest_lon,highest_lat,highest_elevation,covered_subtiles,overlap_ratio
+//! -3.05,53.25,366610.2,-3.95,53.05,1085,2904,0.12
+//! ```
+//!
+//! The columns after `width` describe what the packer found in the tile. They're empty when the
+//! packer saves its progress part way through a run.
+//!
+//! Older tiles files are headerless `lon,lat,width` lines, they can still be loaded.
+
+pub mod diff;
+
+use color_eyre::{Result, eyre::ContextCompat as _};
+
+use crate::projector::LonLatCoord;
+
+/// The current version of the tiles file format.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The start of the line of metadata.
+const METADATA_PREFIX: &str = "# ";
+
+/// The header of the CSV columns.
+const HEADER: &str =
+    "lon,lat,width,highest_lon,highest_lat,highest_elevation,covered_subtiles,overlap_ratio";
+
+/// The parameters that the packer was run with.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PackerParameters {
+    /// How the packer decided which tiles are cheaper.
+    pub cost_model: crate::packer::cost::Model,
+    /// How many bands of latitudes were packed in parallel.
+    pub bands: u32,
+    /// Points at or below this height were packed with the smallest tiles.
+    pub minimum_height: i32,
+    /// The radius in meters of each packing window.
+    pub window_radius: f64,
+    /// The widest that a tile can be, in meters.
+    pub maximum_tile_width: f32,
+    /// The refraction and the observer and target heights that tiles were sized for.
+    pub line_of_sight: crate::config::LineOfSight,
+    /// The shape of the earth that the tiles' geometry was calculated on. Files packed before
+    /// this was recorded were all packed on a sphere.
+    #[serde(default = "packed_on_a_sphere")]
+    pub earth: crate::projector::Earth,
+}
+
+/// The earth that tiles were packed on before it was recorded in the metadata.
+const fn packed_on_a_sphere() -> crate::projector::Earth {
+    crate::projector::Earth::Sphere
+}
+
+/// Metadata about a whole tiles file.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Metadata {
+    /// The version of the tiles file format. Headerless files are version 0.
+    pub version: u32,
+    /// The parameters that the packer was run with.
+    pub packer: Option<PackerParameters>,
+    /// A hash of the max subtiles that the tiles were packed from.
+    pub input_hash: Option<String>,
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            packer: None,
+            input_hash: None,
+        }
+    }
+}
+
+impl Metadata {
+    /// The shape of the earth that the tiles were packed on, so that their geometry is remade on
+    /// the same earth. Files without packer parameters were all packed on a sphere.
+    pub fn earth(&self) -> crate::projector::Earth {
+        self.packer
+            .as_ref()
+            .map_or_else(packed_on_a_sphere, |packer| packer.earth)
+    }
+}
+
+/// What the packer found in a tile.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TileDetails {
+    /// The highest max subtile covered by the tile.
+    pub highest: LonLatCoord,
+    /// The elevation of the highest max subtile, in meters.
+    pub highest_elevation: i32,
+    /// How many max subtiles the tile covers.
+    pub covered_subtiles: u32,
+    /// How much of the tile's area is also covered by its neighbours, from 0 to 1.
+    pub overlap_ratio: f64,
+}
+
+/// A tile from a tiles file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PackedTile {
+    /// The tile.
+    pub tile: crate::tile::Tile,
+    /// What the packer found in the tile, if it was recorded.
+    pub details: Option<TileDetails>,
+}
+
+/// A whole tiles file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct File {
+    /// Metadata about the whole file.
+    pub metadata: Metadata,
+    /// All the tiles.
+    pub tiles: Vec<PackedTile>,
+}
+
+/// Load just the tiles from a tiles file.
+pub fn load(path: &std::path::Path) -> Result<Vec<crate::tile::Tile>> {
+    Ok(File::load(path)?
+        .tiles
+        .into_iter()
+        .map(|packed| packed.tile)
+        .collect())
+}
+
+/// Hash max subtiles, so that it's possible to tell whether tiles were packed from the same input.
+/// It's the same as the checksum in the max subtiles file's header.
+pub fn hash_subtiles(subtiles: &[crate::max_subtile::MaxSubTile]) -> String {
+    crate::max_subtile::file::checksum(subtiles)
+}
+
+impl File {
+    /// Load and validate a tiles file.
+    pub fn load(path: &std::path::Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| color_eyre::eyre::eyre!("Couldn't read {path:?}: {error}"))?;
+        let file = Self::parse(&contents)
+            .map_err(|error| color_eyre::eyre::eyre!("Invalid tiles file {path:?}: {error}"))?;
+        if file.metadata.version == 0 {
+            tracing::warn!("Loaded headerless tiles file {path:?}, consider repacking it");
+        }
+
+        Ok(file)
+    }
+
+    /// Parse and validate the contents of a tiles file. Any tile with a `NaN` or out of range
+    /// value, or with the same centre as another tile, is an error.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut lines = contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .peekable();
+
+        let mut metadata = Metadata {
+            version: 0,
+            packer: None,
+            input_hash: None,
+        };
+        if let Some((_, line)) = lines.next_if(|(_, line)| line.starts_with(METADATA_PREFIX)) {
+            metadata = serde_json::from_str(line.trim_start_matches(METADATA_PREFIX))?;
+            if metadata.version > FORMAT_VERSION {
+                color_eyre::eyre::bail!(
+                    "Unsupported tiles file version {}, expected at most {FORMAT_VERSION}",
+                    metadata.version
+                );
+            }
+        }
+        match lines.next_if(|(_, line)| line.starts_with("lon")) {
+            Some((_, header)) if header != HEADER => {
+                color_eyre::eyre::bail!("Unexpected header: {header}");
+            }
+            None if metadata.version != 0 => color_eyre::eyre::bail!("Missing header"),
+            Some(_) | None => (),
+        }
+
+        let mut tiles = Vec::new();
+        let mut centres = std::collections::HashSet::new();
+        for (index, line) in lines {
+            let line_number = index + 1;
+            let packed = Self::parse_line(line)
+                .map_err(|error| color_eyre::eyre::eyre!("Line {line_number}: {error}"))?;
+            let centre = packed.tile.centre.0;
+            if !centres.insert((centre.x.to_bits(), centre.y.to_bits())) {
+                color_eyre::eyre::bail!("Line {line_number}: duplicate tile at {centre:?}");
+            }
+            tiles.push(packed);
+        }
+
+        Ok(Self { metadata, tiles })
+    }
+
+    /// Parse a single tile.
+    fn parse_line(line: &str) -> Result<PackedTile> {
+        let parts: Vec<&str> = line.split(',').map(str::trim).collect();
+        let column = |index: usize, name: &str| -> Result<&str> {
+            parts.get(index).copied().context(format!("No {name}"))
+        };
+
+        let lon = Self::parse_finite(column(0, "longitude")?, "longitude")?;
+        let lat = Self::parse_finite(column(1, "latitude")?, "latitude")?;
+        let width = column(2, "width")?.parse::<f32>()?;
+        if !(-180.0f64..=180.0f64).contains(&lon) || !(-90.0f64..=90.0f64).contains(&lat) {
+            color_eyre::eyre::bail!("Centre out of range: {lon},{lat}");
+        }
+        if !width.is_finite() || width <= 0.0 {
+            color_eyre::eyre::bail!("Width must be a positive number: {width}");
+        }
+
+        let details_columns = parts.get(3..).unwrap_or_default();
+        let details = if details_columns.iter().all(|part| part.is_empty()) {
+            None
+        } else {
+            Some(TileDetails {
+                highest: LonLatCoord(geo::coord! {
+                    x: Self::parse_finite(column(3, "highest longitude")?, "highest longitude")?,
+                    y: Self::parse_finite(column(4, "highest latitude")?, "highest latitude")?,
+                }),
+                highest_elevation: column(5, "highest elevation")?.parse()?,
+                covered_subtiles: column(6, "covered subtiles")?.parse()?,
+                overlap_ratio: Self::parse_finite(column(7, "overlap ratio")?, "overlap ratio")?,
+            })
+        };
+
+        Ok(PackedTile {
+            tile: crate::tile::Tile {
+                centre: LonLatCoord(geo::coord! { x: lon, y: lat }),
+                width,
+            },
+            details,
+        })
+    }
+
+    /// Parse a float that must be a real number.
+    fn parse_finite(value: &str, name: &str) -> Result<f64> {
+        let number = value.parse::<f64>()?;
+        if !number.is_finite() {
+            color_eyre::eyre::bail!("The {name} isn't a finite number: {value}");
+        }
+
+        Ok(number)
+    }
+
+    /// Save the tiles file.
+    pub fn save(&self, path: &std::path::Path) -> Result<()> {
+        tracing::info!("Saving {} tiles to: {path:?}", self.tiles.len());
+        std::fs::write(path, self.to_csv()?)?;
+
+        Ok(())
+    }
+
+    /// The tiles file as CSV.
+    pub fn to_csv(&self) -> Result<String> {
+        let mut lines = vec![
+            format!(
+                "{METADATA_PREFIX}{}",
+                serde_json::to_string(&self.metadata)?
+            ),
+            HEADER.to_owned(),
+        ];
+        for packed in &self.tiles {
+            let centre = packed.tile.centre.0;
+            let details = packed.details.map_or_else(
+                || ",,,,".to_owned(),
+                |details| {
+                    format!(
+                        "{},{},{},{},{}",
+                        details.highest.0.x,
+                        details.highest.0.y,
+                        details.highest_elevation,
+                        details.covered_subtiles,
+                        details.overlap_ratio
+                    )
+                },
+            );
+            lines.push(format!(
+                "{},{},{},{details}",
+                centre.x, centre.y, packed.tile.width
+            ));
+        }
+
+        Ok(lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn packed(lon: f64, details: Option<TileDetails>) -> PackedTile {
+        PackedTile {
+            tile: crate::tile::Tile {
+                centre: LonLatCoord(geo::coord! { x: lon, y: 53.25 }),
+                width: 366_610.2,
+            },
+            details,
+        }
+    }
+
+    #[test]
+    fn save_and_parse() {
+        let file = File {
+            metadata: Metadata {
+                input_hash: Some("crc32:1a2b3c4d".to_owned()),
+                ..Metadata::default()
+            },
+            tiles: vec![
+                packed(
+                    -3.05,
+                    Some(TileDetails {
+                        highest: LonLatCoord(geo::coord! { x: -3.95f64, y: 53.05f64 }),
+                        highest_elevation: 1085,
+                        covered_subtiles: 2904,
+                        overlap_ratio: 0.12,
+                    }),
+                ),
+                packed(-1.05, None),
+            ],
+        };
+
+        let parsed = File::parse(&file.to_csv().unwrap()).unwrap();
+        assert_eq!(parsed, file);
+    }
+
+    #[test]
+    fn parse_headerless() {
+        let parsed = File::parse("-3.05,53.25,366610.2\n-1.05,53.25,366610.2\n").unwrap();
+        assert_eq!(parsed.metadata.version, 0);
+        assert_eq!(parsed.tiles, vec![packed(-3.05, None), packed(-1.05, None)]);
+    }
+
+    #[test]
+    fn headerless_files_were_packed_on_a_sphere() {
+        let mut metadata = File::parse("-3.05,53.25,366610.2\n").unwrap().metadata;
+        assert_eq!(metadata.earth(), crate::projector::Earth::Sphere);
+
+        let packer = PackerParameters {
+            cost_model: crate::packer::cost::Model::default(),
+            bands: 1,
+            minimum_height: 100,
+            window_radius: 1_800_000.0,
+            maximum_tile_width: 800_000.0,
+            line_of_sight: crate::config::LineOfSight {
+                refraction: crate::horizon::DEFAULT_REFRACTION,
+                observer_height: 0.0,
+                target_height: 0.0,
+            },
+            earth: crate::projector::Earth::Wgs84,
+        };
+        metadata.packer = Some(packer.clone());
+        assert_eq!(metadata.earth(), crate::projector::Earth::Wgs84);
+
+        let mut unrecorded = serde_json::to_value(&packer).unwrap();
+        unrecorded.as_object_mut().unwrap().remove("earth");
+        let parsed: PackerParameters = serde_json::from_value(unrecorded).unwrap();
+        assert_eq!(parsed.earth, crate::projector::Earth::Sphere);
+    }
+
+    #[test]
+    fn reject_nans_and_duplicates() {
+        let error = |contents: &str| File::parse(contents).unwrap_err().to_string();
+
+        assert!(error("-3.05,NaN,366610.2").starts_with("Line 1: The latitude isn't"));
+        assert!(error("-3.05,53.25,inf").starts_with("Line 1: Width must be"));
+        assert!(
+            error("-3.05,53.25,366610.2\n-3.05,53.25,1000.0").starts_with("Line 2: duplicate tile")
+        );
+    }
+}
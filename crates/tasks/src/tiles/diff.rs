@@ -0,0 +1,307 @@
+//! Compare 2 tiles files, eg from before and after a repack.
+//!
+//! Tiles are matched by their centres, within a tolerance, so that tiny floating point differences
+//! between packer runs don't count as changes.
+
+use color_eyre::Result;
+
+use crate::projector::LonLatCoord;
+
+/// The default distance in meters within which 2 tiles' centres count as the same.
+pub const DEFAULT_TOLERANCE: f64 = 100.0;
+
+/// The difference in meters between 2 tiles' widths before they count as resized.
+const WIDTH_TOLERANCE: f32 = 1.0;
+
+/// How a tile changed between the old and the new tiles files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    /// The tile is only in the new tiles.
+    Added,
+    /// The tile is only in the old tiles.
+    Removed,
+    /// The tile is in both but with a different width.
+    Resized,
+    /// The tile is the same in both.
+    Unchanged,
+}
+
+/// A tile and how it changed.
+#[derive(Debug, Clone, Copy)]
+pub struct ChangedTile {
+    /// How the tile changed.
+    pub change: Change,
+    /// The tile in the old tiles.
+    pub old: Option<crate::tile::Tile>,
+    /// The tile in the new tiles.
+    pub new: Option<crate::tile::Tile>,
+}
+
+impl ChangedTile {
+    /// The newest version of the tile.
+    fn tile(&self) -> Option<crate::tile::Tile> {
+        self.new.or(self.old)
+    }
+}
+
+/// All the changes between 2 tiles files.
+#[derive(Debug, Default)]
+pub struct Diff {
+    /// Every tile in either the old or the new tiles.
+    pub tiles: Vec<ChangedTile>,
+    /// The earth that the new tiles were packed on, which distances and polygons are made on.
+    pub earth: crate::projector::Earth,
+}
+
+impl Diff {
+    /// Compare old and new tiles. Tiles whose centres are within `tolerance` meters are the same
+    /// tile.
+    pub fn new(
+        old: &[crate::tile::Tile],
+        new: &[crate::tile::Tile],
+        tolerance: f64,
+        earth: crate::projector::Earth,
+    ) -> Self {
+        let old_tree = rstar::RTree::bulk_load(
+            old.iter()
+                .enumerate()
+                .map(|(index, tile)| rstar::primitives::GeomWithData::new(tile.centre, index))
+                .collect(),
+        );
+        let mut is_matched = vec![false; old.len()];
+        let mut tiles = Vec::new();
+
+        for new_tile in new {
+            let nearest = Self::candidates(&old_tree, new_tile.centre, tolerance, earth)
+                .filter(|candidate| !is_matched.get(candidate.data).copied().unwrap_or(true))
+                .map(|candidate| {
+                    let distance = earth.distance(new_tile.centre, *candidate.geom());
+                    (distance, candidate.data)
+                })
+                .filter(|(distance, _)| *distance <= tolerance)
+                .min_by(|left, right| left.0.total_cmp(&right.0));
+
+            let Some((_, index)) = nearest else {
+                tiles.push(ChangedTile {
+                    change: Change::Added,
+                    old: None,
+                    new: Some(*new_tile),
+                });
+                continue;
+            };
+
+            if let Some(matched) = is_matched.get_mut(index) {
+                *matched = true;
+            }
+            let old_tile = old.get(index).copied();
+            let is_resized =
+                old_tile.is_some_and(|tile| (tile.width - new_tile.width).abs() >= WIDTH_TOLERANCE);
+            tiles.push(ChangedTile {
+                change: if is_resized {
+                    Change::Resized
+                } else {
+                    Change::Unchanged
+                },
+                old: old_tile,
+                new: Some(*new_tile),
+            });
+        }
+
+        for (old_tile, _) in old.iter().zip(is_matched).filter(|(_, matched)| !matched) {
+            tiles.push(ChangedTile {
+                change: Change::Removed,
+                old: Some(*old_tile),
+                new: None,
+            });
+        }
+
+        Self { tiles, earth }
+    }
+
+    /// Old tiles that might be within `tolerance` meters of the centre. The search box is in
+    /// degrees, so it's stretched East to West by the latitude. It's also searched a whole turn
+    /// either side, so that tiles either side of the antimeridian are compared.
+    fn candidates(
+        tree: &rstar::RTree<rstar::primitives::GeomWithData<LonLatCoord, usize>>,
+        centre: LonLatCoord,
+        tolerance: f64,
+        earth: crate::projector::Earth,
+    ) -> impl Iterator<Item = &rstar::primitives::GeomWithData<LonLatCoord, usize>> {
+        let latitude_degrees =
+            tolerance / f64::from(crate::projector::Convert::meters_per_degree(earth, 0.0));
+        let longitude_degrees =
+            (latitude_degrees / centre.0.y.to_radians().cos().max(0.01f64)).min(180.0f64);
+
+        [-360.0f64, 0.0f64, 360.0f64]
+            .into_iter()
+            .flat_map(move |offset| {
+                let envelope = rstar::AABB::from_corners(
+                    LonLatCoord(geo::coord! {
+                        x: centre.0.x + offset - longitude_degrees,
+                        y: centre.0.y - latitude_degrees,
+                    }),
+                    LonLatCoord(geo::coord! {
+                        x: centre.0.x + offset + longitude_degrees,
+                        y: centre.0.y + latitude_degrees,
+                    }),
+                );
+                tree.locate_in_envelope(&envelope)
+            })
+    }
+
+    /// How many tiles changed in the given way.
+    pub fn count(&self, change: Change) -> usize {
+        self.tiles
+            .iter()
+            .filter(|tile| tile.change == change)
+            .count()
+    }
+
+    /// The new tiles that need processing because they're either added or resized.
+    pub fn changed_tiles(&self) -> Vec<crate::tile::Tile> {
+        self.tiles
+            .iter()
+            .filter(|tile| matches!(tile.change, Change::Added | Change::Resized))
+            .filter_map(|tile| tile.new)
+            .collect()
+    }
+
+    /// The tiles that are the same in both, as their old and new versions.
+    pub fn unchanged_tiles(&self) -> Vec<(crate::tile::Tile, crate::tile::Tile)> {
+        self.tiles
+            .iter()
+            .filter(|tile| tile.change == Change::Unchanged)
+            .filter_map(|tile| tile.old.zip(tile.new))
+            .collect()
+    }
+
+    /// A one line summary of all the changes.
+    pub fn summary(&self) -> String {
+        format!(
+            "Added: {}, removed: {}, resized: {}, unchanged: {}",
+            self.count(Change::Added),
+            self.count(Change::Removed),
+            self.count(Change::Resized),
+            self.count(Change::Unchanged)
+        )
+    }
+
+    /// Save every tile that isn't unchanged as `GeoJSON` polygons.
+    pub fn save(&self, path: &std::path::Path) -> Result<()> {
+        let mut features = Vec::new();
+        for changed in &self.tiles {
+            if changed.change == Change::Unchanged {
+                continue;
+            }
+            let Some(tile) = changed.tile() else {
+                continue;
+            };
+
+            let mut properties = geojson::JsonObject::new();
+            properties.insert("change".to_owned(), serde_json::to_value(changed.change)?);
+            properties.insert(
+                "old_width".to_owned(),
+                changed.old.map(|old| old.width).into(),
+            );
+            properties.insert(
+                "new_width".to_owned(),
+                changed.new.map(|new| new.width).into(),
+            );
+
+            features.push(geojson::Feature {
+                bbox: None,
+                geometry: Some(geojson::Geometry::from(&tile.to_polygon_lonlat(self.earth))),
+                id: None,
+                properties: Some(properties),
+                foreign_members: None,
+            });
+        }
+
+        let feature_collection = geojson::FeatureCollection {
+            bbox: None,
+            features,
+            foreign_members: None,
+        };
+
+        tracing::info!("Saving tiles diff to: {path:?}");
+        std::fs::write(path, geojson::GeoJson::from(feature_collection).to_string())?;
+
+        Ok(())
+    }
+}
+
+/// Entrypoint.
+#[expect(clippy::print_stdout, reason = "Gotta output the summary")]
+pub fn run(config: &crate::config::TilesDiff) -> Result<()> {
+    let old = super::load(&config.old)?;
+    let new = super::File::load(&config.new)?;
+    let new_tiles: Vec<crate::tile::Tile> = new.tiles.iter().map(|packed| packed.tile).collect();
+    let diff = Diff::new(&old, &new_tiles, config.tolerance, new.metadata.earth());
+    diff.save(&config.output)?;
+
+    println!("{}", diff.summary());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tile(longitude: f64, width: f32) -> crate::tile::Tile {
+        crate::tile::Tile {
+            centre: LonLatCoord(geo::coord! { x: longitude, y: 45.0f64 }),
+            width,
+        }
+    }
+
+    #[test]
+    fn classify_changes() {
+        let old = [tile(0.0, 1000.0), tile(1.0, 1000.0), tile(2.0, 1000.0)];
+        let new = [
+            tile(0.000_001, 1000.0),
+            tile(1.0, 2000.0),
+            tile(3.0, 1000.0),
+        ];
+        let diff = Diff::new(
+            &old,
+            &new,
+            DEFAULT_TOLERANCE,
+            crate::projector::Earth::Wgs84,
+        );
+
+        assert_eq!(diff.count(Change::Unchanged), 1);
+        assert_eq!(diff.count(Change::Resized), 1);
+        assert_eq!(diff.count(Change::Added), 1);
+        assert_eq!(diff.count(Change::Removed), 1);
+        assert_eq!(
+            diff.changed_tiles(),
+            vec![tile(1.0, 2000.0), tile(3.0, 1000.0)]
+        );
+        assert_eq!(
+            diff.unchanged_tiles(),
+            vec![(tile(0.0, 1000.0), tile(0.000_001, 1000.0))]
+        );
+        assert_eq!(
+            diff.summary(),
+            "Added: 1, removed: 1, resized: 1, unchanged: 1"
+        );
+    }
+
+    #[test]
+    fn tiles_either_side_of_the_antimeridian_are_the_same() {
+        let old = [tile(179.999_9, 1000.0)];
+        let new = [tile(-180.0, 1000.0)];
+        let diff = Diff::new(
+            &old,
+            &new,
+            DEFAULT_TOLERANCE,
+            crate::projector::Earth::Wgs84,
+        );
+
+        assert_eq!(diff.count(Change::Unchanged), 1);
+        assert_eq!(diff.count(Change::Added), 0);
+        assert_eq!(diff.count(Change::Removed), 0);
+    }
+}
@@ -58,8 +58,6 @@ function process_raw_tvs_tiff {
 	local input=$1
 	local output=$2
 
-	# These are in an array because I've been exploring splitting tiles that cross the antimeridian.
-	# Having an array makes it easier to diverge the processing into west and east branches.
 	local warp_args=(
 		"-overwrite"
 		"-tr" "100" "100"
@@ -71,11 +69,26 @@ function process_raw_tvs_tiff {
 		"-co" "COMPRESS=DEFLATE"
 		"-co" "TILED=YES"
 		"-co" "PREDICTOR=3"
-		"$input"
 	)
 
-	warp_args=("${warp_args[@]}" "$output")
-	gdalwarp "${warp_args[@]}"
+	# Tiles that cross the antimeridian would be warped to a raster that spans the whole globe. So
+	# they're split into a west and an east half, with the extent of each half coming from the
+	# Packer's own tile geometry.
+	local extents
+	mapfile -t extents < <(RUST_LOG=off "$TASKS_BIN" tile-extents "$input")
+
+	if ((${#extents[@]} <= 1)); then
+		gdalwarp "${warp_args[@]}" "$input" "$output"
+		return
+	fi
+
+	for index in "${!extents[@]}"; do
+		read -r -a extent <<<"${extents[$index]}"
+		gdalwarp "${warp_args[@]}" \
+			-te_srs EPSG:4326 \
+			-te "${extent[@]}" \
+			"$input" "${output%.tiff}_$index.tiff"
+	done
 }
 
 # Create overviews to speed up tile creation at lower zoom levels.
@@ -99,7 +112,11 @@ function prepare_tiff {
 
 	if (($(echo "$latitude > -80" | bc -l))); then
 		process_raw_tvs_tiff "$source" "$destination/$filename"
-		create_overviews_for_tiff "$destination/$filename"
+		for part in "$destination/$filename" "$destination/${filename%.tiff}"_[0-9].tiff; do
+			if [ -f "$part" ]; then
+				create_overviews_for_tiff "$part"
+			fi
+		done
 	else
 		echo "Not creating preparing heatmap tiff for Antartic tile: $input"
 	fi
@@ -114,6 +131,11 @@ function prepare_all_tiffs {
 	export -f process_raw_tvs_tiff
 	export -f create_overviews_for_tiff
 
+	# Build once up front, rather than having every parallel job run `cargo` itself.
+	cargo build --release --bin tasks
+	TASKS_BIN="$(pwd)/target/release/tasks"
+	export TASKS_BIN
+
 	mkdir -p "$destination_directory"
 
 	find "$source_directory" -name "*.tiff" |
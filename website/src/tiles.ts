@@ -0,0 +1,113 @@
+// Parse the tiles files made by the packer. This is the same format as
+// `crates/tasks/src/tiles.rs`, see there for the details. In short, it's a
+// line of JSON metadata, a CSV header and then a tile on each line. Older
+// headerless `lon,lat,width` files are still supported.
+
+const FORMAT_VERSION = 1;
+const METADATA_PREFIX = '# ';
+const HEADER =
+  'lon,lat,width,highest_lon,highest_lat,highest_elevation,covered_subtiles,overlap_ratio';
+
+export type TilesMetadata = {
+  version: number;
+  packer: Record<string, unknown> | null;
+  input_hash: string | null;
+};
+
+export type TileDetails = {
+  highest: [number, number];
+  highestElevation: number;
+  coveredSubtiles: number;
+  overlapRatio: number;
+};
+
+export type Tile = {
+  lon: number;
+  lat: number;
+  width: number;
+  details?: TileDetails;
+};
+
+export type TilesFile = {
+  metadata: TilesMetadata;
+  tiles: Tile[];
+};
+
+// Parse and validate a tiles file. Any tile with a `NaN` or out of range
+// value, or with the same centre as another tile, is an error.
+export function parseTiles(csv: string): TilesFile {
+  const lines = csv
+    .split(/\n/)
+    .map((line, index) => ({ line: line.trim(), number: index + 1 }))
+    .filter(({ line }) => line !== '');
+
+  let metadata: TilesMetadata = {
+    version: 0,
+    packer: null,
+    input_hash: null,
+  };
+  if (lines[0]?.line.startsWith(METADATA_PREFIX)) {
+    metadata = JSON.parse(lines[0].line.slice(METADATA_PREFIX.length));
+    lines.shift();
+    if (metadata.version > FORMAT_VERSION) {
+      throw new Error(`Unsupported tiles file version ${metadata.version}`);
+    }
+  }
+  if (lines[0]?.line.startsWith('lon')) {
+    if (lines[0].line !== HEADER) {
+      throw new Error(`Unexpected tiles file header: ${lines[0].line}`);
+    }
+    lines.shift();
+  } else if (metadata.version !== 0) {
+    throw new Error('Missing tiles file header');
+  }
+
+  const tiles = [];
+  const centres = new Set<string>();
+  for (const { line, number } of lines) {
+    const tile = parseTile(line, number);
+    const centre = `${tile.lon},${tile.lat}`;
+    if (centres.has(centre)) {
+      throw new Error(`Line ${number}: duplicate tile at ${centre}`);
+    }
+    centres.add(centre);
+    tiles.push(tile);
+  }
+
+  return { metadata, tiles };
+}
+
+function parseTile(line: string, number: number): Tile {
+  const parts = line.split(/,/).map((part) => part.trim());
+  const finite = (index: number, name: string) => {
+    const value = Number.parseFloat(parts[index]);
+    if (!Number.isFinite(value)) {
+      throw new Error(
+        `Line ${number}: the ${name} isn't a finite number: ${parts[index]}`,
+      );
+    }
+    return value;
+  };
+
+  const lon = finite(0, 'longitude');
+  const lat = finite(1, 'latitude');
+  const width = finite(2, 'width');
+  if (Math.abs(lon) > 180 || Math.abs(lat) > 90) {
+    throw new Error(`Line ${number}: centre out of range: ${lon},${lat}`);
+  }
+  if (width <= 0) {
+    throw new Error(`Line ${number}: width must be positive: ${width}`);
+  }
+
+  const tile: Tile = { lon, lat, width };
+  if (parts.slice(3).some((part) => part !== '')) {
+    tile.details = {
+      highest: [finite(3, 'highest longitude'), finite(4, 'highest latitude')],
+      highestElevation: finite(5, 'highest elevation'),
+      coveredSubtiles: finite(6, 'covered subtiles'),
+      overlapRatio: finite(7, 'overlap ratio'),
+    };
+  }
+
+  return tile;
+}
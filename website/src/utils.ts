@@ -1,10 +1,17 @@
 import nprogress from 'accessible-nprogress';
 import { LngLat, LngLatBounds } from 'maplibre-gl';
 
-export const VERSION = 'ryan-fullworld-raw';
-export const CDN_BUCKET = 'https://cdn.alltheviews.world';
+// These can all be overridden at build time, eg to point at a local `server --bundle`:
+//   PUBLIC_VERSION=local PUBLIC_CDN_BUCKET=http://localhost:3333 \
+//     PUBLIC_MAP_SERVER=http://localhost:3333 pnpm run build
+export const VERSION: string =
+  import.meta.env.PUBLIC_VERSION ?? 'ryan-fullworld-raw';
+export const CDN_BUCKET: string =
+  import.meta.env.PUBLIC_CDN_BUCKET ?? 'https://cdn.alltheviews.world';
 export const MAP_SERVER_SUBDOMAIN = 'pmtiles';
-export const MAP_SERVER = `https://${MAP_SERVER_SUBDOMAIN}.alltheviews.world`;
+export const MAP_SERVER: string =
+  import.meta.env.PUBLIC_MAP_SERVER ??
+  `https://${MAP_SERVER_SUBDOMAIN}.alltheviews.world`;
 export const WORLD_PMTILES = 'world.pmtiles/world'; // TODO move the file to its proper place
 export const PMTILES_SERVER = `${MAP_SERVER}/runs/${VERSION}/pmtiles/${WORLD_PMTILES}`;
 